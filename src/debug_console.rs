@@ -0,0 +1,439 @@
+use std::io::{BufRead, Write};
+
+use crate::emulator::{
+    debugger::{Access, Debugger, Run, Stop},
+    ram_search::{Filter, RamSearch, Size, Watch, WatchList},
+    Emulator, LINES_PER_FRAME,
+};
+
+const PROMPT: &str = "(sunrest) ";
+const DEFAULT_DUMP_SIZE: u16 = 0x40;
+const DEFAULT_DISASM_COUNT: usize = 10;
+// the candidates are only listed once the search is narrow enough
+const MAX_LISTED_CANDIDATES: usize = 50;
+const HELP: &str = "\
+Addresses and register values are hexadecimal ($ or 0x prefix optional), counts are decimal.
+An empty line repeats the last command.
+
+  c, continue          run until a breakpoint or watchpoint
+  s, step [n]          run n instructions (default 1)
+  n, next              step over a JSR
+  o, out               run until the current subroutine returns (RTS/RTI)
+  scanline <n>         run until the PPU reaches the scanline n
+  frame                run until the next frame
+  b, break <addr>      toggle an execution breakpoint
+  rw <addr>            toggle a read watchpoint
+  ww <addr>            toggle a write watchpoint
+  irq | nmi            toggle breaking on IRQ / NMI
+  l, list              list breakpoints and watchpoints
+  r, regs              show the registers
+  set <reg> <val>      change a register (a, x, y, p, sp, pc)
+  x <addr> [len]       dump memory (len in hex, default 40)
+  d, disasm [addr] [n] disassemble n instructions (default from PC)
+  search start [1|2]   start a RAM search of 1 or 2 bytes values (little-endian)
+  search <filter>      keep the values that are equal, changed, increased or decreased
+                       since the last search, or equal to a value (search value <val>)
+  search list          list the candidates
+  watch <addr> [1|2] [label]
+                       toggle a watch, the watches are shown after every run
+  watch                show the watches
+  h, help              show this help
+  q, quit              exit";
+
+/*
+ * Text front-end for the emulator debugger, it runs without any graphical interface.
+ */
+pub struct DebugConsole<R: BufRead, W: Write> {
+    emulator: Emulator,
+    debugger: Debugger,
+    input: R,
+    output: W,
+    last_command: String,
+    ram_search: Option<RamSearch>,
+    watches: WatchList,
+}
+
+impl<R: BufRead, W: Write> DebugConsole<R, W> {
+    pub fn new(emulator: Emulator, input: R, output: W) -> Self {
+        Self {
+            emulator,
+            debugger: Debugger::new(),
+            input,
+            output,
+            last_command: String::new(),
+            ram_search: None,
+            watches: WatchList::default(),
+        }
+    }
+
+    pub fn run(&mut self) {
+        let status = self.status();
+        writeln!(self.output, "{}", status).unwrap();
+        loop {
+            write!(self.output, "{}", PROMPT).unwrap();
+            self.output.flush().unwrap();
+
+            let mut line = String::new();
+            if self.input.read_line(&mut line).unwrap() == 0 {
+                break;
+            }
+
+            let line = match line.trim() {
+                "" => self.last_command.clone(),
+                line => line.to_string(),
+            };
+            if matches!(line.as_str(), "q" | "quit") {
+                break;
+            }
+
+            match self.execute(&line) {
+                Ok(text) => writeln!(self.output, "{}", text).unwrap(),
+                Err(err) => writeln!(self.output, "error: {}", err).unwrap(),
+            }
+            self.last_command = line;
+        }
+    }
+
+    fn execute(&mut self, line: &str) -> Result<String, String> {
+        let mut args = line.split_whitespace();
+        let command = args.next().unwrap_or_default();
+        let args: Vec<&str> = args.collect();
+
+        let text = match (command, args.as_slice()) {
+            ("c" | "continue", []) => self.run_debugger(Run::Continue),
+            ("s" | "step", []) => self.run_debugger(Run::Step),
+            ("s" | "step", [count]) => {
+                let count = parse_count(count)?;
+                for _ in 1..count {
+                    if self.debugger.run(&mut self.emulator, Run::Step) != Stop::Step {
+                        return Ok(self.status());
+                    }
+                }
+                self.run_debugger(Run::Step)
+            }
+            ("n" | "next", []) => self.run_debugger(Run::StepOver),
+            ("o" | "out", []) => self.run_debugger(Run::StepOut),
+            ("scanline", [scanline]) => self.run_debugger(Run::Scanline(parse_scanline(scanline)?)),
+            ("frame", []) => self.run_debugger(Run::Frame),
+            ("b" | "break", [addr]) => {
+                let addr = parse_hex(addr)?;
+                let enabled = !self.debugger.breakpoints().contains(&addr);
+                self.debugger.set_breakpoint(addr, enabled);
+                toggle_message("breakpoint", addr, enabled)
+            }
+            ("rw", [addr]) => self.toggle_watchpoint(Access::Read, parse_hex(addr)?),
+            ("ww", [addr]) => self.toggle_watchpoint(Access::Write, parse_hex(addr)?),
+            ("irq", []) => {
+                self.debugger.break_on_irq = !self.debugger.break_on_irq;
+                format!("break on IRQ: {}", self.debugger.break_on_irq)
+            }
+            ("nmi", []) => {
+                self.debugger.break_on_nmi = !self.debugger.break_on_nmi;
+                format!("break on NMI: {}", self.debugger.break_on_nmi)
+            }
+            ("l" | "list", []) => self.list(),
+            ("r" | "regs", []) => self.status(),
+            ("set", [reg, val]) => {
+                self.set_register(reg, parse_hex(val)?)?;
+                self.status()
+            }
+            ("x", [addr]) => self.dump(parse_hex(addr)?, DEFAULT_DUMP_SIZE),
+            ("x", [addr, len]) => self.dump(parse_hex(addr)?, parse_hex(len)?),
+            ("d" | "disasm", []) => self.disasm(self.emulator.registers().pc, DEFAULT_DISASM_COUNT),
+            ("d" | "disasm", [addr]) => self.disasm(parse_hex(addr)?, DEFAULT_DISASM_COUNT),
+            ("d" | "disasm", [addr, count]) => self.disasm(parse_hex(addr)?, parse_count(count)?),
+            ("search", ["start"]) => self.start_search(Size::Byte),
+            ("search", ["start", size]) => self.start_search(parse_size(size)?),
+            ("search", ["list"]) => self.list_candidates()?,
+            ("search", ["equal"]) => self.filter_search(Filter::Equal)?,
+            ("search", ["changed"]) => self.filter_search(Filter::Changed)?,
+            ("search", ["increased"]) => self.filter_search(Filter::Increased)?,
+            ("search", ["decreased"]) => self.filter_search(Filter::Decreased)?,
+            ("search", ["value", val]) => self.filter_search(Filter::Value(parse_hex(val)?))?,
+            ("watch", []) => self.watches.render(&self.emulator).join("\n"),
+            ("watch", [addr, rest @ ..]) => {
+                let (size, label) = match rest {
+                    [size, label @ ..] if parse_size(size).is_ok() => (parse_size(size)?, label),
+                    label => (Size::Byte, label),
+                };
+                let addr = parse_hex(addr)?;
+                let enabled = self.watches.toggle(Watch {
+                    addr,
+                    size,
+                    label: label.join(" "),
+                });
+                toggle_message("watch", addr, enabled)
+            }
+            ("h" | "help", []) => HELP.to_string(),
+            _ => return Err(format!("invalid command: {} (try help)", line)),
+        };
+        Ok(text)
+    }
+
+    fn run_debugger(&mut self, run: Run) -> String {
+        let stop = match self.debugger.run(&mut self.emulator, run) {
+            Stop::Step => String::new(),
+            Stop::Breakpoint(addr) => format!("breakpoint at {:04X}\n", addr),
+            Stop::Watchpoint(Access::Read, addr) => format!("read from {:04X}\n", addr),
+            Stop::Watchpoint(Access::Write, addr) => format!("write to {:04X}\n", addr),
+            Stop::Irq => "IRQ\n".to_string(),
+            Stop::Nmi => "NMI\n".to_string(),
+            Stop::Scanline(scanline) => format!("scanline {}\n", scanline),
+            Stop::Frame(frame) => format!("frame {}\n", frame),
+        };
+        let mut text = format!("{}{}", stop, self.status());
+        for line in self.watches.render(&self.emulator) {
+            text += &format!("\n{}", line);
+        }
+        text
+    }
+
+    fn start_search(&mut self, size: Size) -> String {
+        let search = RamSearch::new(&self.emulator, size);
+        let text = format!("{} candidates", search.len());
+        self.ram_search = Some(search);
+        text
+    }
+
+    fn filter_search(&mut self, filter: Filter) -> Result<String, String> {
+        let search = self
+            .ram_search
+            .as_mut()
+            .ok_or("no search started (try search start)")?;
+        Ok(format!(
+            "{} candidates",
+            search.filter(&self.emulator, filter)
+        ))
+    }
+
+    fn list_candidates(&self) -> Result<String, String> {
+        let search = self
+            .ram_search
+            .as_ref()
+            .ok_or("no search started (try search start)")?;
+        if search.len() > MAX_LISTED_CANDIDATES {
+            return Ok(format!(
+                "{} candidates, filter them down to {} to list them",
+                search.len(),
+                MAX_LISTED_CANDIDATES
+            ));
+        }
+        let lines: Vec<String> = search
+            .candidates(&self.emulator)
+            .iter()
+            .map(|candidate| match search.size() {
+                Size::Byte => format!(
+                    "{:04X}: {:02X} (was {:02X})",
+                    candidate.addr, candidate.current, candidate.previous
+                ),
+                Size::Word => format!(
+                    "{:04X}: {:04X} (was {:04X})",
+                    candidate.addr, candidate.current, candidate.previous
+                ),
+            })
+            .collect();
+        Ok(lines.join("\n"))
+    }
+
+    fn status(&self) -> String {
+        let emu = &self.emulator;
+        let (instruction, _) = emu.disasm(emu.registers().pc);
+        format!(
+            "{}  {}\nPPU:{:3},{:3} FRAME:{} CYC:{}",
+            instruction,
+            emu.registers(),
+            emu.scanline(),
+            emu.dot(),
+            emu.frame(),
+            emu.cpu_cycle(),
+        )
+    }
+
+    fn toggle_watchpoint(&mut self, access: Access, addr: u16) -> String {
+        let enabled = !self.emulator.watchpoints(access).contains(&addr);
+        self.emulator.set_watchpoint(access, addr, enabled);
+        let kind = match access {
+            Access::Read => "read watchpoint",
+            Access::Write => "write watchpoint",
+        };
+        toggle_message(kind, addr, enabled)
+    }
+
+    fn list(&self) -> String {
+        let format_list = |list: Vec<u16>| {
+            list.iter()
+                .map(|addr| format!("{:04X}", addr))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        format!(
+            "breakpoints: {}\nread watchpoints: {}\nwrite watchpoints: {}\nbreak on IRQ: {}, NMI: {}",
+            format_list(self.debugger.breakpoints()),
+            format_list(self.emulator.watchpoints(Access::Read)),
+            format_list(self.emulator.watchpoints(Access::Write)),
+            self.debugger.break_on_irq,
+            self.debugger.break_on_nmi,
+        )
+    }
+
+    fn set_register(&mut self, reg: &str, val: u16) -> Result<(), String> {
+        let mut regs = self.emulator.registers();
+        let byte = || u8::try_from(val).map_err(|_| format!("value out of range: {:X}", val));
+        match reg.to_lowercase().as_str() {
+            "a" => regs.a = byte()?,
+            "x" => regs.x = byte()?,
+            "y" => regs.y = byte()?,
+            "p" => regs.p = byte()?,
+            "sp" => regs.sp = byte()?,
+            "pc" => regs.pc = val,
+            _ => return Err(format!("invalid register: {}", reg)),
+        }
+        self.emulator.set_registers(regs);
+        Ok(())
+    }
+
+    fn dump(&self, addr: u16, len: u16) -> String {
+        let mut lines = Vec::new();
+        for row in (0..len).step_by(16) {
+            let row_addr = addr.wrapping_add(row);
+            let bytes: Vec<String> = (0..16.min(len - row))
+                .map(|i| {
+                    format!(
+                        "{:02X}",
+                        self.emulator.peek_memory(row_addr.wrapping_add(i))
+                    )
+                })
+                .collect();
+            lines.push(format!("{:04X}: {}", row_addr, bytes.join(" ")));
+        }
+        lines.join("\n")
+    }
+
+    fn disasm(&self, mut addr: u16, count: usize) -> String {
+        let mut lines = Vec::with_capacity(count);
+        for _ in 0..count {
+            let (text, next) = self.emulator.disasm(addr);
+            lines.push(text);
+            addr = next;
+        }
+        lines.join("\n")
+    }
+}
+
+fn toggle_message(kind: &str, addr: u16, enabled: bool) -> String {
+    let state = if enabled { "set" } else { "removed" };
+    format!("{} {} at {:04X}", kind, state, addr)
+}
+
+fn parse_hex(text: &str) -> Result<u16, String> {
+    let digits = text
+        .strip_prefix('$')
+        .or_else(|| text.strip_prefix("0x"))
+        .unwrap_or(text);
+    u16::from_str_radix(digits, 16).map_err(|_| format!("invalid hex number: {}", text))
+}
+
+fn parse_size(text: &str) -> Result<Size, String> {
+    match text {
+        "1" => Ok(Size::Byte),
+        "2" => Ok(Size::Word),
+        _ => Err(format!("invalid size: {} (expected 1 or 2)", text)),
+    }
+}
+
+fn parse_count(text: &str) -> Result<usize, String> {
+    text.parse()
+        .map_err(|_| format!("invalid number: {}", text))
+}
+
+// from 0 to 261, the last one is the pre-render line
+fn parse_scanline(text: &str) -> Result<usize, String> {
+    match parse_count(text)? {
+        scanline if scanline < LINES_PER_FRAME => Ok(scanline),
+        _ => Err(format!(
+            "the scanlines go from 0 to {}",
+            LINES_PER_FRAME - 1
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::cartridge;
+
+    fn mk_console(input: &str) -> DebugConsole<&[u8], Vec<u8>> {
+        // LDX #$02; INX; JMP $8000
+        let cartridge =
+            cartridge::Cartridge::with_program(&[(0x8000, &[0xA2, 0x02, 0xE8, 0x4C, 0x00, 0x80])]);
+        DebugConsole::new(Emulator::new(cartridge), input.as_bytes(), Vec::new())
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse_hex("$C000"), Ok(0xC000));
+        assert_eq!(parse_hex("0x10"), Ok(0x10));
+        assert_eq!(parse_hex("ff"), Ok(0xFF));
+        assert!(parse_hex("zz").is_err());
+        assert_eq!(parse_count("10"), Ok(10));
+        assert_eq!(parse_scanline("261"), Ok(261));
+        assert_eq!(
+            parse_scanline("262").unwrap_err(),
+            "the scanlines go from 0 to 261"
+        );
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut console = mk_console("");
+        console.execute("b 8003").unwrap();
+        console.execute("c").unwrap();
+        assert_eq!(console.emulator.registers().pc, 0x8003);
+        assert_eq!(console.emulator.registers().x, 0x03);
+
+        console.execute("set x 7f").unwrap();
+        assert_eq!(console.emulator.registers().x, 0x7F);
+        assert!(console.execute("set x 100").is_err());
+        assert!(console.execute("set q 1").is_err());
+
+        console.execute("s 2").unwrap();
+        assert_eq!(console.emulator.registers().pc, 0x8002);
+        assert!(console
+            .execute("x 8000 3")
+            .unwrap()
+            .starts_with("8000: A2 02 E8"));
+        assert!(console.execute("bogus").is_err());
+        assert!(console.execute("scanline 300").is_err());
+    }
+
+    #[test]
+    fn test_ram_search() {
+        let mut console = mk_console("");
+        assert!(console.execute("search changed").is_err());
+        assert_eq!(console.execute("search start").unwrap(), "10240 candidates");
+        console.emulator.write_memory(0x0042, 0x05);
+        assert_eq!(console.execute("search increased").unwrap(), "1 candidates");
+        assert_eq!(console.execute("search list").unwrap(), "0042: 05 (was 00)");
+        assert_eq!(console.execute("search value 6").unwrap(), "0 candidates");
+        assert!(console.execute("search start 3").is_err());
+
+        console.execute("watch 42 lives").unwrap();
+        console.execute("watch $0100 2").unwrap();
+        let text = console.execute("frame").unwrap();
+        assert!(text.ends_with("0042 lives = 05 (5)\n0100 = 0000 (0)"));
+        assert_eq!(
+            console.execute("watch 42").unwrap(),
+            "watch removed at 0042"
+        );
+        assert_eq!(console.execute("watch").unwrap(), "0100 = 0000 (0)");
+    }
+
+    #[test]
+    fn test_run() {
+        let mut console = mk_console("s\n\nq\n");
+        console.run();
+        assert_eq!(console.emulator.registers().pc, 0x8003);
+        let output = String::from_utf8(console.output.clone()).unwrap();
+        assert_eq!(output.matches(PROMPT).count(), 3);
+    }
+}
@@ -7,6 +7,14 @@ pub struct MemoryReader {
     repeat: bool,
 }
 
+state!(MemoryReader {
+    length,
+    address,
+    bytes_remaining,
+    current_address,
+    repeat,
+});
+
 pub struct NoMoreBytes;
 
 impl MemoryReader {
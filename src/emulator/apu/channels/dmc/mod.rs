@@ -14,6 +14,13 @@ pub struct Dmc {
     irq_enabled: bool,
 }
 
+state!(Dmc {
+    output_unit,
+    memory_reader,
+    timer,
+    irq_enabled,
+});
+
 impl Dmc {
     pub fn new() -> Self {
         Self {
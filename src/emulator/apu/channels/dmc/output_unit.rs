@@ -7,6 +7,14 @@ pub struct OutputUnit {
     buffer: Option<u8>,
 }
 
+state!(OutputUnit {
+    shift_register,
+    bits_remaining,
+    level,
+    silence,
+    buffer,
+});
+
 impl OutputUnit {
     pub fn starved(&self) -> bool {
         self.buffer.is_none()
@@ -11,6 +11,14 @@ pub struct Envelope {
     pub timer: Timer,
 }
 
+state!(Envelope {
+    decay,
+    start_flag,
+    fade,
+    repeat,
+    timer,
+});
+
 impl Envelope {
     pub fn output(&self) -> u8 {
         if self.fade {
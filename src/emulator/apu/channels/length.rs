@@ -5,6 +5,12 @@ pub struct Length {
     pub(super) halted: bool,
 }
 
+state!(Length {
+    val,
+    enabled,
+    halted,
+});
+
 impl Length {
     pub fn enabled(&self) -> bool {
         self.val > 0
@@ -11,6 +11,13 @@ pub struct Noise {
     pub length: Length,
 }
 
+state!(Noise {
+    shift,
+    envelope,
+    timer,
+    length,
+});
+
 impl Noise {
     pub fn new() -> Self {
         Self {
@@ -4,12 +4,16 @@ pub enum ShiftMode {
     Six,
 }
 
+state_enum!(ShiftMode { One = 0, Six = 1 });
+
 #[derive(Clone)]
 pub struct Shift {
     data: u16,
     mode: ShiftMode,
 }
 
+state!(Shift { data, mode });
+
 impl Default for Shift {
     fn default() -> Self {
         Self {
@@ -6,6 +6,8 @@ pub enum DutyCycle {
     Duty25Neg = 0b1001_1111,
 }
 
+state_enum!(DutyCycle { Duty12_5 = 0, Duty25 = 1, Duty50 = 2, Duty25Neg = 3 });
+
 impl DutyCycle {
     pub fn output(&self, step: u8) -> bool {
         let cyc = *self as u8;
@@ -22,6 +22,16 @@ pub struct Pulse {
     sweep: Sweep,
 }
 
+// the kind is fixed
+state!(Pulse {
+    length,
+    timer,
+    duty_cycle,
+    sequencer,
+    envelope,
+    sweep,
+});
+
 impl Pulse {
     pub fn new(kind: Kind) -> Self {
         Self {
@@ -9,6 +9,14 @@ pub struct Sweep {
     timer: Timer,
 }
 
+state!(Sweep {
+    enabled,
+    reload_flag,
+    negate,
+    shift,
+    timer,
+});
+
 impl Sweep {
     pub fn shift(&self) -> u8 {
         self.shift
@@ -1,6 +1,8 @@
 #[derive(Debug, Default, Clone, Copy)]
 pub struct Sequencer<const LEN: u8>(u8);
 
+state!(<const LEN: u8> Sequencer<LEN> { 0 });
+
 impl<const LEN: u8> Sequencer<LEN> {
     pub fn clock(&mut self) {
         self.0 = (self.0 + 1) % LEN;
@@ -4,6 +4,8 @@ pub struct Timer {
     pub counter: u16,
 }
 
+state!(Timer { period, counter });
+
 impl Timer {
     pub fn new(period: u16) -> Self {
         Self {
@@ -6,6 +6,13 @@ pub struct LinearCounter {
     control_flag: bool,
 }
 
+state!(LinearCounter {
+    load,
+    current,
+    reload_flag,
+    control_flag,
+});
+
 impl LinearCounter {
     pub fn set_control_flag(&mut self, value: bool) {
         self.control_flag = value;
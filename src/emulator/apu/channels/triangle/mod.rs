@@ -11,6 +11,13 @@ pub struct Triangle {
     pub length: Length,
 }
 
+state!(Triangle {
+    linear_counter,
+    sequencer,
+    timer,
+    length,
+});
+
 impl Triangle {
     pub fn new() -> Self {
         Self {
@@ -9,6 +9,8 @@ enum SequencerPeriod {
     FiveSteps,
 }
 
+state_enum!(SequencerPeriod { FourSteps = 0, FiveSteps = 1 });
+
 pub struct Apu {
     pub pulse1: channels::pulse::Pulse,
     pub pulse2: channels::pulse::Pulse,
@@ -38,6 +40,11 @@ impl Apu {
         }
     }
 
+    // the Reset button silences all the channels, as writing 0 to $4015
+    pub fn reset(&mut self) {
+        self.write(0x15, 0x00);
+    }
+
     pub fn is_hi_cycle(&self) -> bool {
         self.timer_cycle % 2 == 1
     }
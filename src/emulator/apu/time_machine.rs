@@ -13,6 +13,18 @@ pub struct TimeMachine {
     sequencer_cycle: usize,
 }
 
+state!(TimeMachine {
+    pulse1,
+    pulse2,
+    triangle,
+    noise,
+    dmc,
+    irq_inhibit,
+    sequencer_period,
+    timer_cycle,
+    sequencer_cycle,
+});
+
 impl TimeMachine {
     pub fn save(ppu: &Apu) -> Self {
         Self {
@@ -1,10 +1,11 @@
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Signal {
     pub pulse1: u8,
     pub pulse2: u8,
     pub triangle: u8,
     pub noise: u8,
     pub dmc: u8,
+    pub expansion: f32, // 0.0..=1.0
 }
 
 impl Signal {
@@ -18,10 +19,13 @@ impl Signal {
         let pulse_sample = SQUARE_OUT[p1 + p2];
         let tnd_sample = TND_OUT[3 * t + 2 * n + d];
 
-        pulse_sample + tnd_sample
+        pulse_sample + tnd_sample + self.expansion * EXPANSION_VOLUME
     }
 }
 
+// the expansion audio at full volume is about 2.4 times louder than a square wave channel
+const EXPANSION_VOLUME: f32 = SQUARE_OUT[15] * 2.4;
+
 // [n] = 95.52 / (8128.0 / n + 100)
 const SQUARE_OUT: [f32; 31] = [
     0.0,
@@ -9,6 +9,10 @@ impl ApuRegs {
         self.0.read(addr & APU_REGS_BIT_MASK)
     }
 
+    pub fn peek(&self, addr: u16) -> u8 {
+        self.0.peek(addr & APU_REGS_BIT_MASK)
+    }
+
     pub fn write(&mut self, addr: u16, val: u8) {
         self.0.write(addr & APU_REGS_BIT_MASK, val)
     }
@@ -2,11 +2,13 @@ mod apu_regs;
 mod ppu_regs;
 mod sram;
 mod time_machine;
+mod watchpoints;
 mod wram;
 
 pub use apu_regs::*;
 pub use ppu_regs::*;
 pub use time_machine::TimeMachine;
+pub use watchpoints::{Access, Watchpoints};
 
 use super::*;
 
@@ -19,6 +21,9 @@ const PRG_END: u16 = 0xFFFF;
 const SRAM_START: u16 = 0x6000;
 const SRAM_END: u16 = 0x7FFF;
 
+const EXPANSION_START: u16 = 0x4020;
+const EXPANSION_END: u16 = 0x5FFF;
+
 const OAM_DMA_ADDR: u16 = 0x4014;
 
 const PPU_REGS_START: u16 = 0x2000;
@@ -36,30 +41,46 @@ const APU_FRAME_COUNTER_ADDR: u16 = 0x4017;
 
 pub trait Addressable {
     fn read(&self, addr: u16) -> u8;
+    // what read would return, without its side effects
+    fn peek(&self, addr: u16) -> u8;
     fn write(&mut self, addr: u16, val: u8);
 }
 
+pub trait CartridgeIO: Addressable {
+    // the cartridge sees the accesses to $6000-$7FFF, some mappers have registers or RAM there
+    fn read_sram(&self, addr: u16) -> Option<u8>;
+    fn write_sram(&mut self, addr: u16, val: u8);
+    // $4020-$5FFF, None when nothing answers (open bus)
+    fn read_expansion(&self, addr: u16) -> Option<u8>;
+    fn peek_expansion(&self, addr: u16) -> Option<u8>;
+    fn write_expansion(&mut self, addr: u16, val: u8);
+}
+
 pub trait InputPort {
     fn read(&self) -> u8;
+    fn peek(&self) -> u8;
     fn write(&mut self, val: u8);
 }
 
 pub struct Bus {
-    cartridge_io: Box<dyn Addressable>,
+    cartridge_io: Box<dyn CartridgeIO>,
     ppu_regs: ppu_regs::PpuRegs,
     apu_regs: apu_regs::ApuRegs,
     wram: wram::Wram,
     sram: sram::Sram, // in reality this is on the cartridge
     oam_dma_page: Option<u8>,
+    pub watchpoints: Watchpoints,
 
     input_latch: u8,
+    // a joypad was read since the last take_input_polled, the frames without it are lag frames
+    input_polled: std::cell::Cell<bool>,
     pub port1: Option<Box<dyn InputPort>>,
     pub port2: Option<Box<dyn InputPort>>,
 }
 
 impl Bus {
     pub fn new(
-        cartridge_io: Box<dyn Addressable>,
+        cartridge_io: Box<dyn CartridgeIO>,
         ppu_regs_io: Box<dyn Addressable>,
         apu_regs_io: Box<dyn Addressable>,
     ) -> Self {
@@ -70,13 +91,34 @@ impl Bus {
             wram: wram::Wram::new(),
             sram: sram::Sram::new(),
             oam_dma_page: None,
+            watchpoints: Watchpoints::default(),
 
             input_latch: 0,
+            input_polled: std::cell::Cell::new(false),
             port1: None,
             port2: None,
         }
     }
 
+    // clears the RAM, the battery backed SRAM, the joypads and the watchpoints are kept
+    pub fn power_cycle(&mut self) {
+        self.wram = wram::Wram::new();
+        self.oam_dma_page = None;
+        self.input_latch = 0;
+    }
+
+    pub fn fill_ram(&mut self, pattern: crate::emulator::RamPattern) {
+        self.wram.fill(pattern);
+    }
+
+    pub fn fill_sram(&mut self, pattern: crate::emulator::RamPattern) {
+        self.sram.fill(pattern);
+    }
+
+    pub fn take_input_polled(&self) -> bool {
+        self.input_polled.replace(false)
+    }
+
     pub fn take_oam_dma_page(&mut self) -> Option<u8> {
         self.oam_dma_page.take()
     }
@@ -85,8 +127,14 @@ impl Bus {
         match addr {
             WRAM_START..=WRAM_END => self.wram.write(addr - WRAM_START, val),
             PPU_REGS_START..=PPU_REGS_END => self.ppu_regs.write(addr - PPU_REGS_START, val),
-            SRAM_START..=SRAM_END => self.sram.write(addr - SRAM_START, val),
+            SRAM_START..=SRAM_END => {
+                self.sram.write(addr - SRAM_START, val);
+                self.cartridge_io.write_sram(addr - SRAM_START, val);
+            }
             PRG_START..=PRG_END => self.cartridge_io.write(addr - PRG_START, val),
+            EXPANSION_START..=EXPANSION_END => self
+                .cartridge_io
+                .write_expansion(addr - EXPANSION_START, val),
             OAM_DMA_ADDR => self.oam_dma_page = Some(val),
             INPUT_PORT_CTRL_ADDR => self.input_latch = val,
             (APU_REGS_START..=APU_REGS_END) | APU_STATUS_ADDR | APU_FRAME_COUNTER_ADDR => {
@@ -100,10 +148,23 @@ impl Bus {
         match addr {
             WRAM_START..=WRAM_END => self.wram.read(addr - WRAM_START),
             PPU_REGS_START..=PPU_REGS_END => self.ppu_regs.read(addr - PPU_REGS_START),
-            SRAM_START..=SRAM_END => self.sram.read(addr - SRAM_START),
+            SRAM_START..=SRAM_END => self
+                .cartridge_io
+                .read_sram(addr - SRAM_START)
+                .unwrap_or_else(|| self.sram.read(addr - SRAM_START)),
             PRG_START..=PRG_END => self.cartridge_io.read(addr - PRG_START),
-            INPUT_PORT_1_ADDR => self.port1.as_ref().map(|p| p.read()).unwrap_or(0),
-            INPUT_PORT_2_ADDR => self.port2.as_ref().map(|p| p.read()).unwrap_or(0),
+            EXPANSION_START..=EXPANSION_END => self
+                .cartridge_io
+                .read_expansion(addr - EXPANSION_START)
+                .unwrap_or(0),
+            INPUT_PORT_1_ADDR => {
+                self.input_polled.set(true);
+                self.port1.as_ref().map(|p| p.read()).unwrap_or(0)
+            }
+            INPUT_PORT_2_ADDR => {
+                self.input_polled.set(true);
+                self.port2.as_ref().map(|p| p.read()).unwrap_or(0)
+            }
             APU_STATUS_ADDR => self.apu_regs.read(addr - APU_REGS_START),
             _ => {
                 log!("Attempted to read from unmapped CPU address: {addr:04X}");
@@ -112,6 +173,27 @@ impl Bus {
         }
     }
 
+    // same as read, but without side effects (eg. clearing the vblank flag or shifting the joypads)
+    pub fn peek(&self, addr: u16) -> u8 {
+        match addr {
+            WRAM_START..=WRAM_END => self.wram.read(addr - WRAM_START),
+            PPU_REGS_START..=PPU_REGS_END => self.ppu_regs.peek(addr - PPU_REGS_START),
+            SRAM_START..=SRAM_END => self
+                .cartridge_io
+                .read_sram(addr - SRAM_START)
+                .unwrap_or_else(|| self.sram.read(addr - SRAM_START)),
+            PRG_START..=PRG_END => self.cartridge_io.peek(addr - PRG_START),
+            EXPANSION_START..=EXPANSION_END => self
+                .cartridge_io
+                .peek_expansion(addr - EXPANSION_START)
+                .unwrap_or(0),
+            INPUT_PORT_1_ADDR => self.port1.as_ref().map(|p| p.peek()).unwrap_or(0),
+            INPUT_PORT_2_ADDR => self.port2.as_ref().map(|p| p.peek()).unwrap_or(0),
+            APU_STATUS_ADDR => self.apu_regs.peek(addr - APU_REGS_START),
+            _ => 0,
+        }
+    }
+
     pub fn update_ports_latch(&mut self) {
         if let Some(p) = self.port1.as_mut() {
             p.write(self.input_latch)
@@ -125,10 +207,16 @@ impl Bus {
 
 impl cpu::Memory for Bus {
     fn read(&self, addr: u16) -> u8 {
+        self.watchpoints.check(Access::Read, addr);
         self.read(addr)
     }
 
+    fn peek(&self, addr: u16) -> u8 {
+        self.peek(addr)
+    }
+
     fn write(&mut self, addr: u16, val: u8) {
+        self.watchpoints.check(Access::Write, addr);
         self.write(addr, val)
     }
 }
@@ -9,6 +9,10 @@ impl PpuRegs {
         self.0.read(addr & PPU_REGS_BIT_MASK)
     }
 
+    pub fn peek(&self, addr: u16) -> u8 {
+        self.0.peek(addr & PPU_REGS_BIT_MASK)
+    }
+
     pub fn write(&mut self, addr: u16, val: u8) {
         self.0.write(addr & PPU_REGS_BIT_MASK, val)
     }
@@ -1,13 +1,21 @@
+use crate::emulator::ram_pattern::{Ram, RamPattern};
+
 const SRAM_SIZE: usize = 0x2000;
 
 #[derive(Clone)]
 pub struct Sram(Box<[u8; SRAM_SIZE]>);
 
+state!(Sram { 0 });
+
 impl Sram {
     pub fn new() -> Self {
         Self(Box::new([0; SRAM_SIZE]))
     }
 
+    pub fn fill(&mut self, pattern: RamPattern) {
+        pattern.fill(self.0.as_mut_slice(), Ram::Sram);
+    }
+
     pub fn write(&mut self, addr: u16, val: u8) {
         self.0[addr as usize] = val;
     }
@@ -6,8 +6,17 @@ pub struct TimeMachine {
     sram: sram::Sram, // in reality this is on the cartridge
     oam_dma_page: Option<u8>,
     input_latch: u8,
+    input_polled: bool,
 }
 
+state!(TimeMachine {
+    wram,
+    sram,
+    oam_dma_page,
+    input_latch,
+    input_polled,
+});
+
 impl TimeMachine {
     pub fn save(bus: &Bus) -> Self {
         Self {
@@ -15,6 +24,7 @@ impl TimeMachine {
             sram: bus.sram.clone(),
             oam_dma_page: bus.oam_dma_page,
             input_latch: bus.input_latch,
+            input_polled: bus.input_polled.get(),
         }
     }
 
@@ -23,5 +33,6 @@ impl TimeMachine {
         bus.sram = self.sram.clone();
         bus.oam_dma_page = self.oam_dma_page;
         bus.input_latch = self.input_latch;
+        bus.input_polled.set(self.input_polled);
     }
 }
@@ -0,0 +1,93 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/*
+ * Only the accesses made by the CPU are watched, so inspecting the memory through the bus
+ * doesn't trigger them.
+ */
+#[derive(Default)]
+pub struct Watchpoints {
+    read: BTreeSet<u16>,
+    write: BTreeSet<u16>,
+    // in the order of the accesses
+    hits: RefCell<Vec<(Access, u16)>>,
+}
+
+impl Watchpoints {
+    pub fn set(&mut self, access: Access, addr: u16, enabled: bool) {
+        let set = match access {
+            Access::Read => &mut self.read,
+            Access::Write => &mut self.write,
+        };
+        if enabled {
+            set.insert(addr);
+        } else {
+            set.remove(&addr);
+        }
+    }
+
+    pub fn list(&self, access: Access) -> Vec<u16> {
+        match access {
+            Access::Read => self.read.iter().copied().collect(),
+            Access::Write => self.write.iter().copied().collect(),
+        }
+    }
+
+    // the first hit is the one that stops the execution, the others are discarded
+    pub fn take_hit(&self) -> Option<(Access, u16)> {
+        self.hits.take().first().copied()
+    }
+
+    pub fn take_hits(&self) -> Vec<(Access, u16)> {
+        self.hits.take()
+    }
+
+    pub(super) fn check(&self, access: Access, addr: u16) {
+        let set = match access {
+            Access::Read => &self.read,
+            Access::Write => &self.write,
+        };
+        if set.contains(&addr) {
+            self.hits.borrow_mut().push((access, addr));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watchpoints() {
+        let mut watchpoints = Watchpoints::default();
+        watchpoints.set(Access::Read, 0x0200, true);
+        watchpoints.set(Access::Write, 0x0300, true);
+
+        watchpoints.check(Access::Write, 0x0200);
+        watchpoints.check(Access::Read, 0x0300);
+        assert_eq!(watchpoints.take_hit(), None);
+
+        watchpoints.check(Access::Read, 0x0200);
+        watchpoints.check(Access::Write, 0x0300);
+        assert_eq!(watchpoints.take_hit(), Some((Access::Read, 0x0200)));
+        assert_eq!(watchpoints.take_hit(), None);
+
+        watchpoints.check(Access::Write, 0x0300);
+        watchpoints.check(Access::Read, 0x0200);
+        assert_eq!(
+            watchpoints.take_hits(),
+            vec![(Access::Write, 0x0300), (Access::Read, 0x0200)]
+        );
+
+        watchpoints.set(Access::Read, 0x0200, false);
+        watchpoints.check(Access::Read, 0x0200);
+        assert_eq!(watchpoints.take_hit(), None);
+        assert_eq!(watchpoints.list(Access::Write), vec![0x0300]);
+    }
+}
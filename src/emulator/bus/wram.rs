@@ -1,14 +1,22 @@
+use crate::emulator::ram_pattern::{Ram, RamPattern};
+
 const WRAM_SIZE: usize = 0x0800;
 const WRAM_BIT_MASK: u16 = 0x07FF;
 
 #[derive(Clone)]
 pub struct Wram(Box<[u8; WRAM_SIZE]>);
 
+state!(Wram { 0 });
+
 impl Wram {
     pub fn new() -> Self {
         Self(Box::new([0; WRAM_SIZE]))
     }
 
+    pub fn fill(&mut self, pattern: RamPattern) {
+        pattern.fill(self.0.as_mut_slice(), Ram::Wram);
+    }
+
     pub fn write(&mut self, addr: u16, val: u8) {
         self.0[self.resolve_address(addr) as usize] = val;
     }
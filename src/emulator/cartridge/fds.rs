@@ -0,0 +1,165 @@
+use super::*;
+
+pub const FDS_MAPPER_CODE: u8 = 20;
+const BIOS_SIZE: usize = 0x2000;
+const DISK_SIDE_SIZE: usize = 65500;
+const FWNES_HEADER_SIZE: usize = 16;
+const FWNES_MAGIC: [u8; 4] = [0x46, 0x44, 0x53, 0x1A];
+const DISK_MAGIC: &[u8] = b"\x01*NINTENDO-HVC*";
+
+// the gaps are measured in bits by the drive, here they are converted to bytes
+const LEADING_GAP_SIZE: usize = 28300 / 8;
+const BLOCK_GAP_SIZE: usize = 976 / 8;
+const BLOCK_START_MARK: u8 = 0x80;
+const FAKE_CRC: [u8; 2] = [0x4D, 0x62];
+
+const DISK_INFO_BLOCK_SIZE: usize = 56;
+const FILE_AMOUNT_BLOCK_SIZE: usize = 2;
+const FILE_HEADER_BLOCK_SIZE: usize = 16;
+
+pub struct FdsImageBuilder;
+
+impl FdsImageBuilder {
+    pub fn is_disk_image(data: &[u8]) -> bool {
+        data.starts_with(&FWNES_MAGIC) || data.starts_with(DISK_MAGIC)
+    }
+
+    // the disk sides, without the fwNES header
+    pub fn without_header(data: &[u8]) -> &[u8] {
+        if data.starts_with(&FWNES_MAGIC) {
+            &data[FWNES_HEADER_SIZE..]
+        } else {
+            data
+        }
+    }
+
+    pub fn build(bios: &[u8], data: &[u8]) -> Result<CartridgeData, String> {
+        if bios.len() != BIOS_SIZE {
+            return Err(format!(
+                "invalid FDS BIOS, {} bytes instead of {}",
+                bios.len(),
+                BIOS_SIZE
+            ));
+        }
+
+        let disk_sides: Vec<Vec<u8>> = Self::without_header(data)
+            .chunks(DISK_SIDE_SIZE)
+            .filter(|side| side.starts_with(DISK_MAGIC))
+            .map(Self::build_side)
+            .collect();
+
+        if disk_sides.is_empty() {
+            return Err("invalid FDS disk image".to_string());
+        }
+
+        Ok(CartridgeData {
+            mapper_code: FDS_MAPPER_CODE,
+            mirror_mode: MirrorMode::Horizontal,
+            prg_data: bios.to_vec(),
+            disk_sides,
+            ..Default::default()
+        })
+    }
+
+    /*
+     * The .fds files only have the blocks content, the drive also expects the gaps between
+     * them, the start mark and the CRC of each block, so they are added here.
+     */
+    fn build_side(side: &[u8]) -> Vec<u8> {
+        let mut raw = vec![0; LEADING_GAP_SIZE];
+
+        let mut pos = 0;
+        while pos < side.len() {
+            let block_size = match side[pos] {
+                1 => DISK_INFO_BLOCK_SIZE,
+                2 => FILE_AMOUNT_BLOCK_SIZE,
+                3 => FILE_HEADER_BLOCK_SIZE,
+                // the file size is in the bytes 13 and 14 of the file header block
+                4 if pos >= 3 => 1 + u16::from_le_bytes([side[pos - 3], side[pos - 2]]) as usize,
+                _ => break,
+            };
+            let block_end = (pos + block_size).min(side.len());
+
+            raw.push(BLOCK_START_MARK);
+            raw.extend_from_slice(&side[pos..block_end]);
+            raw.extend_from_slice(&FAKE_CRC);
+            raw.extend(std::iter::repeat_n(0, BLOCK_GAP_SIZE));
+            pos = block_end;
+        }
+
+        // unformatted area until the end of the disk
+        raw.resize(raw.len().max(DISK_SIDE_SIZE + LEADING_GAP_SIZE), 0);
+        raw
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mk_side(file_size: u16) -> Vec<u8> {
+        let mut side = DISK_MAGIC.to_vec();
+        side.resize(DISK_INFO_BLOCK_SIZE, 0x11);
+        side.extend_from_slice(&[2, 1]);
+        let mut file_header = vec![3; FILE_HEADER_BLOCK_SIZE];
+        file_header[13..15].copy_from_slice(&file_size.to_le_bytes());
+        side.extend_from_slice(&file_header);
+        side.push(4);
+        side.extend(std::iter::repeat_n(0x42, file_size as usize));
+        side.resize(DISK_SIDE_SIZE, 0);
+        side
+    }
+
+    #[test]
+    fn test_build_side() {
+        let raw = FdsImageBuilder::build_side(&mk_side(3));
+        assert!(raw[..LEADING_GAP_SIZE].iter().all(|&b| b == 0));
+
+        let mut pos = LEADING_GAP_SIZE;
+        assert_eq!(raw[pos], BLOCK_START_MARK);
+        assert_eq!(&raw[pos + 1..pos + 1 + DISK_MAGIC.len()], DISK_MAGIC);
+        pos += 1 + DISK_INFO_BLOCK_SIZE;
+        assert_eq!(raw[pos..pos + 2], FAKE_CRC);
+        pos += 2 + BLOCK_GAP_SIZE;
+
+        assert_eq!(raw[pos..pos + 3], [BLOCK_START_MARK, 2, 1]);
+        pos += 1 + FILE_AMOUNT_BLOCK_SIZE + 2 + BLOCK_GAP_SIZE;
+
+        assert_eq!(raw[pos], BLOCK_START_MARK);
+        pos += 1 + FILE_HEADER_BLOCK_SIZE + 2 + BLOCK_GAP_SIZE;
+
+        assert_eq!(raw[pos..pos + 5], [BLOCK_START_MARK, 4, 0x42, 0x42, 0x42]);
+        assert_eq!(raw[pos + 5..pos + 7], FAKE_CRC);
+        assert_eq!(raw[pos + 7], 0);
+    }
+
+    #[test]
+    fn test_build() {
+        let bios = vec![0xEA; BIOS_SIZE];
+        let mut data = mk_side(1);
+        data.extend(mk_side(2));
+
+        let cartridge = FdsImageBuilder::build(&bios, &data).unwrap();
+        assert_eq!(cartridge.mapper_code, FDS_MAPPER_CODE);
+        assert_eq!(cartridge.prg_data, bios);
+        assert_eq!(cartridge.disk_sides.len(), 2);
+
+        let mut fwnes = vec![0; FWNES_HEADER_SIZE];
+        fwnes[..4].copy_from_slice(&FWNES_MAGIC);
+        fwnes[4] = 2;
+        fwnes.extend(data);
+        assert!(FdsImageBuilder::is_disk_image(&fwnes));
+        let cartridge = FdsImageBuilder::build(&bios, &fwnes).unwrap();
+        assert_eq!(cartridge.disk_sides.len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_bios() {
+        assert!(FdsImageBuilder::build(&[0; 0x1000], &mk_side(1)).is_err());
+    }
+
+    #[test]
+    fn test_invalid_disk() {
+        assert!(FdsImageBuilder::build(&[0; BIOS_SIZE], &[0; DISK_SIDE_SIZE]).is_err());
+    }
+}
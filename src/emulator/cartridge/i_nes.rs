@@ -3,7 +3,7 @@ use super::*;
 const PRG_ROM_PAGE_SIZE: usize = 0x4000;
 const CHR_ROM_PAGE_SIZE: usize = 0x2000;
 const TRAINER_SIZE: usize = 0x200;
-const HEADER_SIZE: usize = 16;
+pub(super) const HEADER_SIZE: usize = 16;
 
 struct Flags6 {
     mirroring: MirrorMode,
@@ -15,7 +15,9 @@ struct Flags6 {
 impl From<u8> for Flags6 {
     fn from(value: u8) -> Self {
         Self {
-            mirroring: if value & 0b0000_0001 != 0 {
+            mirroring: if value & 0b0000_1000 != 0 {
+                MirrorMode::FourScreen
+            } else if value & 0b0000_0001 != 0 {
                 MirrorMode::Vertical
             } else {
                 MirrorMode::Horizontal
@@ -28,12 +30,14 @@ impl From<u8> for Flags6 {
 }
 
 struct Flags7 {
+    is_nes2: bool,
     mapper_hi: u8,
 }
 
 impl From<u8> for Flags7 {
     fn from(value: u8) -> Self {
         Self {
+            is_nes2: value & 0b0000_1100 == 0b0000_1000,
             mapper_hi: (value & 0b1111_0000) >> 4,
         }
     }
@@ -51,6 +55,7 @@ impl INesRomBuilder {
         let chr_banks = data[5] as usize;
         let flags6 = Flags6::from(data[6]);
         let flags7 = Flags7::from(data[7]);
+        let submapper = if flags7.is_nes2 { data[8] >> 4 } else { 0 };
 
         let prg_start = if flags6.has_trainer {
             TRAINER_SIZE
@@ -66,6 +71,7 @@ impl INesRomBuilder {
 
         CartridgeData {
             mapper_code: (flags7.mapper_hi << 4) | flags6.mapper_lo,
+            submapper,
             prg_banks,
             chr_banks,
             mirror_mode: flags6.mirroring,
@@ -73,6 +79,7 @@ impl INesRomBuilder {
             has_trainer: flags6.has_trainer,
             prg_data: prg_data.to_vec(),
             chr_data: chr_data.to_vec(),
+            disk_sides: Vec::new(),
         }
     }
 }
@@ -106,6 +113,29 @@ mod tests {
         assert_eq!(cartridge.prg_data[0], 0x42);
     }
 
+    #[test]
+    fn test_mirroring() {
+        assert_eq!(Flags6::from(0b0000_0000).mirroring, MirrorMode::Horizontal);
+        assert_eq!(Flags6::from(0b0000_0001).mirroring, MirrorMode::Vertical);
+        assert_eq!(Flags6::from(0b0000_1000).mirroring, MirrorMode::FourScreen);
+        assert_eq!(Flags6::from(0b0000_1001).mirroring, MirrorMode::FourScreen);
+    }
+
+    #[test]
+    fn test_nes2_submapper() {
+        let mut data = vec![0x4E, 0x45, 0x53, 0x1A, 0x02, 0x01, 0x20, 0x28, 0x20];
+        (0..HEADER_SIZE - data.len()).for_each(|_| data.push(0x00));
+        (0..PRG_ROM_PAGE_SIZE * 2 + CHR_ROM_PAGE_SIZE).for_each(|_| data.push(0x42));
+
+        let cartridge = INesRomBuilder::build(&data);
+        assert_eq!(cartridge.mapper_code, 34);
+        assert_eq!(cartridge.submapper, 2);
+
+        data[7] = 0x20; // iNES 1.0 ignores byte 8
+        let cartridge = INesRomBuilder::build(&data);
+        assert_eq!(cartridge.submapper, 0);
+    }
+
     #[test]
     #[should_panic]
     fn test_invalid_header() {
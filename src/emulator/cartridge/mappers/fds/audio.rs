@@ -0,0 +1,363 @@
+const TABLE_SIZE: usize = 64;
+const MAX_GAIN: u8 = 32;
+const DEFAULT_ENVELOPE_SPEED: u8 = 0xE8;
+// 2/2, 2/3, 2/4 and 2/5 of the volume, scaled by 30
+const MASTER_VOLUMES: [u16; 4] = [30, 20, 15, 12];
+const MOD_RESET: i8 = i8::MIN;
+const MOD_ADJUSTMENTS: [i8; 8] = [0, 1, 2, 4, MOD_RESET, -4, -2, -1];
+
+pub const OUTPUT_MAX: u16 = 63 * MAX_GAIN as u16;
+
+#[derive(Clone, Default)]
+struct Envelope {
+    disabled: bool,
+    increase: bool,
+    speed: u8,
+    gain: u8,
+    timer: usize,
+}
+
+state!(Envelope {
+    disabled,
+    increase,
+    speed,
+    gain,
+    timer,
+});
+
+impl Envelope {
+    fn write(&mut self, val: u8) {
+        self.disabled = val & 0b1000_0000 != 0;
+        self.increase = val & 0b0100_0000 != 0;
+        self.speed = val & 0b0011_1111;
+        if self.disabled {
+            self.gain = self.speed;
+        }
+    }
+
+    fn clock(&mut self, master_speed: u8) {
+        if self.disabled {
+            return;
+        }
+
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+
+        self.timer = 8 * (master_speed as usize + 1) * (self.speed as usize + 1);
+        if self.increase && self.gain < MAX_GAIN {
+            self.gain += 1;
+        } else if !self.increase && self.gain > 0 {
+            self.gain -= 1;
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Audio {
+    wave_table: [u8; TABLE_SIZE],
+    wave_write_enabled: bool,
+    wave_halted: bool,
+    wave_freq: u16,
+    wave_acc: u32,
+    wave_pos: usize,
+    master_volume: usize,
+    output: u16,
+
+    volume_envelope: Envelope,
+    mod_envelope: Envelope,
+    envelopes_halted: bool,
+    envelope_speed: u8,
+
+    mod_table: [i8; TABLE_SIZE],
+    mod_pos: usize,
+    mod_counter: i8,
+    mod_freq: u16,
+    mod_acc: u32,
+    mod_halted: bool,
+}
+
+state!(Audio {
+    wave_table,
+    wave_write_enabled,
+    wave_halted,
+    wave_freq,
+    wave_acc,
+    wave_pos,
+    master_volume,
+    output,
+    volume_envelope,
+    mod_envelope,
+    envelopes_halted,
+    envelope_speed,
+    mod_table,
+    mod_pos,
+    mod_counter,
+    mod_freq,
+    mod_acc,
+    mod_halted,
+});
+
+impl Audio {
+    pub fn new() -> Self {
+        Self {
+            wave_table: [0; TABLE_SIZE],
+            wave_write_enabled: false,
+            wave_halted: true,
+            wave_freq: 0,
+            wave_acc: 0,
+            wave_pos: 0,
+            master_volume: 0,
+            output: 0,
+
+            volume_envelope: Envelope::default(),
+            mod_envelope: Envelope::default(),
+            envelopes_halted: false,
+            envelope_speed: DEFAULT_ENVELOPE_SPEED,
+
+            mod_table: [0; TABLE_SIZE],
+            mod_pos: 0,
+            mod_counter: 0,
+            mod_freq: 0,
+            mod_acc: 0,
+            mod_halted: true,
+        }
+    }
+
+    pub fn output(&self) -> u16 {
+        self.output
+    }
+
+    pub fn read_wave_table(&self, addr: u16) -> u8 {
+        self.wave_table[addr as usize & (TABLE_SIZE - 1)]
+    }
+
+    pub fn write_wave_table(&mut self, addr: u16, val: u8) {
+        if self.wave_write_enabled {
+            self.wave_table[addr as usize & (TABLE_SIZE - 1)] = val & 0x3F;
+        }
+    }
+
+    pub fn volume_gain(&self) -> u8 {
+        self.volume_envelope.gain
+    }
+
+    pub fn mod_gain(&self) -> u8 {
+        self.mod_envelope.gain
+    }
+
+    // $4080-$408A
+    pub fn write(&mut self, reg: u16, val: u8) {
+        match reg {
+            0x0 => self.volume_envelope.write(val),
+            0x2 => self.wave_freq = (self.wave_freq & 0x0F00) | val as u16,
+            0x3 => {
+                self.wave_freq = (self.wave_freq & 0x00FF) | ((val as u16 & 0x0F) << 8);
+                self.wave_halted = val & 0b1000_0000 != 0;
+                self.envelopes_halted = val & 0b0100_0000 != 0;
+                if self.wave_halted {
+                    self.wave_acc = 0;
+                    self.wave_pos = 0;
+                }
+            }
+            0x4 => self.mod_envelope.write(val),
+            0x5 => self.mod_counter = ((val << 1) as i8) >> 1,
+            0x6 => self.mod_freq = (self.mod_freq & 0x0F00) | val as u16,
+            0x7 => {
+                self.mod_freq = (self.mod_freq & 0x00FF) | ((val as u16 & 0x0F) << 8);
+                self.mod_halted = val & 0b1000_0000 != 0;
+                if self.mod_halted {
+                    self.mod_acc = 0;
+                }
+            }
+            0x8 if self.mod_halted => {
+                let adjustment = MOD_ADJUSTMENTS[val as usize & 0x07];
+                self.mod_table[self.mod_pos] = adjustment;
+                self.mod_table[self.mod_pos + 1] = adjustment;
+                self.mod_pos = (self.mod_pos + 2) % TABLE_SIZE;
+            }
+            0x9 => {
+                self.wave_write_enabled = val & 0b1000_0000 != 0;
+                self.master_volume = val as usize & 0b11;
+            }
+            0xA => self.envelope_speed = val,
+            _ => (),
+        }
+    }
+
+    pub fn clock(&mut self) {
+        if !self.envelopes_halted && !self.wave_halted && self.envelope_speed != 0 {
+            self.volume_envelope.clock(self.envelope_speed);
+            self.mod_envelope.clock(self.envelope_speed);
+        }
+
+        if !self.mod_halted && self.mod_freq != 0 {
+            self.mod_acc += self.mod_freq as u32;
+            if self.mod_acc > 0xFFFF {
+                self.mod_acc &= 0xFFFF;
+                self.step_modulator();
+            }
+        }
+
+        if !self.wave_halted && !self.wave_write_enabled {
+            self.wave_acc += self.pitch();
+            if self.wave_acc > 0xFFFF {
+                self.wave_acc &= 0xFFFF;
+                self.wave_pos = (self.wave_pos + 1) % TABLE_SIZE;
+            }
+        }
+
+        // while the wave table is writable the output holds the last value
+        if !self.wave_write_enabled {
+            let gain = self.volume_envelope.gain.min(MAX_GAIN) as u16;
+            let sample = self.wave_table[self.wave_pos] as u16;
+            self.output = sample * gain * MASTER_VOLUMES[self.master_volume] / 30;
+        }
+    }
+
+    fn step_modulator(&mut self) {
+        let adjustment = self.mod_table[self.mod_pos];
+        self.mod_counter = if adjustment == MOD_RESET {
+            0
+        } else {
+            // the counter is a 7 bit signed value
+            let counter = self.mod_counter.wrapping_add(adjustment);
+            (counter << 1) >> 1
+        };
+        self.mod_pos = (self.mod_pos + 1) % TABLE_SIZE;
+    }
+
+    // https://www.nesdev.org/wiki/FDS_audio#Frequency_calculation
+    fn pitch(&self) -> u32 {
+        let pitch = self.wave_freq as i32;
+        if self.mod_halted {
+            return pitch as u32;
+        }
+
+        let counter = self.mod_counter as i32;
+        let mut temp = counter * self.mod_envelope.gain as i32;
+        let remainder = temp & 0x0F;
+        temp >>= 4;
+        if remainder > 0 && temp & 0x80 == 0 {
+            temp += if counter < 0 { -1 } else { 2 };
+        }
+
+        if temp >= 192 {
+            temp -= 256;
+        } else if temp < -64 {
+            temp += 256;
+        }
+
+        temp *= pitch;
+        let remainder = temp & 0x3F;
+        temp >>= 6;
+        if remainder >= 32 {
+            temp += 1;
+        }
+
+        (pitch + temp).max(0) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mk_audio() -> Audio {
+        let mut audio = Audio::new();
+        audio.write(0x9, 0x80); // enable wave table writes
+        for i in 0..TABLE_SIZE as u16 {
+            audio.write_wave_table(i, i as u8);
+        }
+        audio.write(0x9, 0x00);
+        audio.write(0x0, 0x80 | 0x20); // direct volume gain of 32
+        audio
+    }
+
+    #[test]
+    fn test_wave_table_write_protection() {
+        let mut audio = mk_audio();
+        audio.write_wave_table(1, 0x3F);
+        assert_eq!(audio.read_wave_table(1), 1);
+        audio.write(0x9, 0x80);
+        audio.write_wave_table(1, 0xFF);
+        assert_eq!(audio.read_wave_table(1), 0x3F);
+    }
+
+    #[test]
+    fn test_wave_output() {
+        let mut audio = mk_audio();
+        audio.write(0x2, 0x00);
+        audio.write(0x3, 0x01); // frequency 0x100, one step every 256 cycles
+        for _ in 0..256 {
+            audio.clock();
+        }
+        assert_eq!(audio.output(), 32);
+        for _ in 0..256 {
+            audio.clock();
+        }
+        assert_eq!(audio.output(), 64);
+
+        audio.write(0x9, 0x03); // 2/5 of the volume
+        audio.clock();
+        assert_eq!(audio.output(), 64 * 12 / 30);
+
+        audio.write(0x3, 0x80); // halt
+        audio.clock();
+        assert_eq!(
+            audio.output(),
+            0,
+            "the wave goes back to its first position"
+        );
+    }
+
+    #[test]
+    fn test_volume_envelope() {
+        let mut audio = mk_audio();
+        audio.write(0xA, 0x00); // envelope speed 0 (the fastest)
+        audio.write(0x3, 0x00);
+        audio.write(0x0, 0b0100_0000); // increase with speed 0
+        assert_eq!(audio.volume_gain(), 0x20);
+        audio.write(0x0, 0b0000_0000); // decrease with speed 0
+        audio.write(0xA, 0x01);
+        // the first clock steps right away, the next one after 8 * 2 * 1 cycles
+        for _ in 0..18 {
+            audio.clock();
+        }
+        assert_eq!(audio.volume_gain(), 0x1E);
+    }
+
+    #[test]
+    fn test_modulation() {
+        let mut audio = mk_audio();
+        audio.write(0x7, 0x80); // halt the modulator to write its table
+        for _ in 0..32 {
+            audio.write(0x8, 0x01); // +1
+        }
+        assert_eq!(audio.mod_pos, 0);
+        audio.write(0x5, 0x3F);
+        assert_eq!(audio.mod_counter, 63);
+        audio.write(0x6, 0xFF);
+        audio.write(0x7, 0x0F); // fastest modulation
+        for _ in 0..17 {
+            audio.clock();
+        }
+        assert_eq!(audio.mod_counter, -64, "the counter wraps as a 7 bit value");
+    }
+
+    #[test]
+    fn test_pitch() {
+        let mut audio = mk_audio();
+        audio.write(0x2, 0x00);
+        audio.write(0x3, 0x01);
+        assert_eq!(audio.pitch(), 0x100);
+
+        audio.write(0x7, 0x00);
+        audio.write(0x4, 0x80 | 0x10); // mod gain of 16
+        audio.write(0x5, 0x02);
+        assert_eq!(audio.pitch(), 0x100 + 8);
+        audio.write(0x5, 0x7E); // -2
+        assert_eq!(audio.pitch(), 0x100 - 8);
+    }
+}
@@ -0,0 +1,328 @@
+// cycles to spin the disk up to the first byte and to move from one byte to the next
+const SPIN_UP_DELAY: usize = 50000;
+const BYTE_DELAY: usize = 150;
+// how long the drive stays empty when switching the disk side
+const SIDE_SWITCH_DELAY: usize = 1_000_000;
+
+#[derive(Clone)]
+pub struct Drive {
+    sides: Vec<Vec<u8>>,
+    side: Option<usize>,
+    pending_side: Option<(usize, usize)>,
+    position: usize,
+    delay: usize,
+
+    motor_on: bool,
+    reset_transfer: bool,
+    read_mode: bool,
+    crc_control: bool,
+    ready: bool,
+    irq_enabled: bool,
+
+    end_of_head: bool,
+    scanning: bool,
+    gap_ended: bool,
+    transfer_complete: bool,
+    read_data: u8,
+    write_data: u8,
+    pub irq: bool,
+}
+
+// the sides keep what the game wrote on them
+state!(Drive {
+    sides,
+    side,
+    pending_side,
+    position,
+    delay,
+    motor_on,
+    reset_transfer,
+    read_mode,
+    crc_control,
+    ready,
+    irq_enabled,
+    end_of_head,
+    scanning,
+    gap_ended,
+    transfer_complete,
+    read_data,
+    write_data,
+    irq,
+});
+
+impl Drive {
+    pub fn new(sides: Vec<Vec<u8>>) -> Self {
+        Self {
+            sides,
+            side: Some(0),
+            pending_side: None,
+            position: 0,
+            delay: 0,
+
+            motor_on: false,
+            reset_transfer: false,
+            read_mode: true,
+            crc_control: false,
+            ready: false,
+            irq_enabled: false,
+
+            end_of_head: true,
+            scanning: false,
+            gap_ended: false,
+            transfer_complete: false,
+            read_data: 0,
+            write_data: 0,
+            irq: false,
+        }
+    }
+
+    pub fn side_count(&self) -> usize {
+        self.sides.len()
+    }
+
+    pub fn side(&self) -> Option<usize> {
+        self.side
+    }
+
+    pub fn insert(&mut self, side: usize) {
+        if side < self.sides.len() {
+            self.side = Some(side);
+            self.pending_side = None;
+        }
+    }
+
+    pub fn eject(&mut self) {
+        self.side = None;
+        self.pending_side = None;
+    }
+
+    pub fn switch_side(&mut self) {
+        let next = self.side.map(|s| (s + 1) % self.sides.len()).unwrap_or(0);
+        self.side = None;
+        self.pending_side = Some((next, SIDE_SWITCH_DELAY));
+    }
+
+    pub fn write_data(&mut self, val: u8) {
+        self.write_data = val;
+        self.transfer_complete = false;
+        self.irq = false;
+    }
+
+    pub fn write_control(&mut self, val: u8) {
+        self.motor_on = val & 0b0000_0001 != 0;
+        self.reset_transfer = val & 0b0000_0010 != 0;
+        self.read_mode = val & 0b0000_0100 != 0;
+        self.crc_control = val & 0b0001_0000 != 0;
+        self.ready = val & 0b0100_0000 != 0;
+        self.irq_enabled = val & 0b1000_0000 != 0;
+        self.irq = false;
+    }
+
+    // the bits 1 and 6 of $4030
+    pub fn read_status(&mut self) -> u8 {
+        let status = self.peek_status();
+        self.transfer_complete = false;
+        self.irq = false;
+        status
+    }
+
+    pub fn peek_status(&self) -> u8 {
+        (self.transfer_complete as u8) << 1 | (self.end_of_head as u8) << 6
+    }
+
+    pub fn read_data(&mut self) -> u8 {
+        self.transfer_complete = false;
+        self.irq = false;
+        self.read_data
+    }
+
+    pub fn peek_data(&self) -> u8 {
+        self.read_data
+    }
+
+    // $4032
+    pub fn read_drive_status(&self) -> u8 {
+        let inserted = self.side.is_some();
+        (!inserted as u8) | ((!inserted || !self.scanning) as u8) << 1 | (!inserted as u8) << 2
+    }
+
+    pub fn clock(&mut self) {
+        if let Some((side, delay)) = self.pending_side {
+            if delay == 0 {
+                self.insert(side);
+            } else {
+                self.pending_side = Some((side, delay - 1));
+            }
+        }
+
+        let Some(side) = self.side else {
+            self.end_of_head = true;
+            self.scanning = false;
+            return;
+        };
+
+        if !self.motor_on {
+            self.end_of_head = true;
+            self.scanning = false;
+            return;
+        }
+
+        if self.reset_transfer && !self.scanning {
+            return;
+        }
+
+        if self.end_of_head {
+            self.delay = SPIN_UP_DELAY;
+            self.end_of_head = false;
+            self.position = 0;
+            self.gap_ended = false;
+            return;
+        }
+
+        if self.delay > 0 {
+            self.delay -= 1;
+            return;
+        }
+
+        self.scanning = true;
+        if self.read_mode {
+            self.read_byte(side);
+        } else {
+            self.write_byte(side);
+        }
+
+        self.position += 1;
+        if self.position >= self.sides[side].len() {
+            self.motor_on = false;
+        } else {
+            self.delay = BYTE_DELAY;
+        }
+    }
+
+    fn read_byte(&mut self, side: usize) {
+        let data = self.sides[side][self.position];
+        let mut need_irq = self.irq_enabled;
+
+        if !self.ready {
+            self.gap_ended = false;
+        } else if data != 0 && !self.gap_ended {
+            // the first non zero byte is the block start mark, it is not transferred
+            self.gap_ended = true;
+            need_irq = false;
+        }
+
+        if self.gap_ended {
+            self.transfer_complete = true;
+            self.read_data = data;
+            self.irq |= need_irq;
+        }
+    }
+
+    fn write_byte(&mut self, side: usize) {
+        let mut data = self.write_data;
+        if !self.crc_control {
+            self.transfer_complete = true;
+            self.irq |= self.irq_enabled;
+        }
+
+        if !self.ready {
+            data = 0;
+        }
+
+        // the written byte lands behind the head, the data register is double buffered
+        if let Some(pos) = self.position.checked_sub(2) {
+            self.sides[side][pos] = data;
+        }
+        self.gap_ended = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mk_drive() -> Drive {
+        let mut side = vec![0; 4];
+        side.extend_from_slice(&[0x80, 0x01, 0x02, 0x03]);
+        Drive::new(vec![side, vec![0; 8]])
+    }
+
+    fn clock_byte(drive: &mut Drive) {
+        for _ in 0..=BYTE_DELAY {
+            drive.clock();
+        }
+    }
+
+    fn spin_up(drive: &mut Drive) {
+        drive.clock();
+        for _ in 0..SPIN_UP_DELAY {
+            drive.clock();
+        }
+    }
+
+    #[test]
+    fn test_read() {
+        let mut drive = mk_drive();
+        drive.write_control(0b1100_0101); // motor on, read mode, ready, IRQ enabled
+        spin_up(&mut drive);
+
+        for _ in 0..5 {
+            clock_byte(&mut drive);
+            assert!(!drive.irq, "no transfers in the gap and start mark");
+        }
+
+        for expected in [0x01, 0x02, 0x03] {
+            clock_byte(&mut drive);
+            assert!(drive.irq);
+            assert_eq!(drive.read_status() & 0x02, 0x02);
+            assert_eq!(drive.read_data(), expected);
+            assert!(!drive.irq);
+        }
+    }
+
+    #[test]
+    fn test_write() {
+        let mut drive = mk_drive();
+        drive.write_control(0b0100_0001); // motor on, write mode, ready
+        spin_up(&mut drive);
+
+        drive.write_data(0xAA);
+        for _ in 0..4 {
+            clock_byte(&mut drive);
+        }
+        assert_eq!(drive.sides[0][0..3], [0xAA, 0xAA, 0x00]);
+    }
+
+    #[test]
+    fn test_drive_status() {
+        let mut drive = mk_drive();
+        assert_eq!(drive.read_drive_status(), 0b010, "inserted, not ready");
+
+        drive.eject();
+        assert_eq!(drive.read_drive_status(), 0b111);
+
+        drive.insert(1);
+        assert_eq!(drive.side(), Some(1));
+        drive.write_control(0b0000_0101);
+        spin_up(&mut drive);
+        clock_byte(&mut drive);
+        assert_eq!(drive.read_drive_status(), 0b000);
+    }
+
+    #[test]
+    fn test_switch_side() {
+        let mut drive = mk_drive();
+        drive.switch_side();
+        assert_eq!(drive.side(), None);
+        for _ in 0..=SIDE_SWITCH_DELAY {
+            drive.clock();
+        }
+        assert_eq!(drive.side(), Some(1));
+
+        drive.switch_side();
+        for _ in 0..=SIDE_SWITCH_DELAY {
+            drive.clock();
+        }
+        assert_eq!(drive.side(), Some(0));
+    }
+}
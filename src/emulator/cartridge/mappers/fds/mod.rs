@@ -0,0 +1,376 @@
+mod audio;
+mod drive;
+
+use super::*;
+use std::cell::RefCell;
+
+pub use audio::OUTPUT_MAX as AUDIO_OUTPUT_MAX;
+
+const RAM_SIZE: usize = 0x8000;
+// the RAM is at $6000-$DFFF and the BIOS at $E000-$FFFF
+const RAM_END: u16 = 0x5FFF;
+const SRAM_OFFSET: usize = 0x6000;
+const BIOS_START: u16 = 0x6000;
+
+// register addresses relative to $4020
+const IRQ_RELOAD_LO: u16 = 0x00;
+const IRQ_RELOAD_HI: u16 = 0x01;
+const IRQ_CONTROL: u16 = 0x02;
+const MASTER_IO_ENABLE: u16 = 0x03;
+const WRITE_DATA: u16 = 0x04;
+const DRIVE_CONTROL: u16 = 0x05;
+const EXT_CONNECTOR_WRITE: u16 = 0x06;
+const DISK_STATUS: u16 = 0x10;
+const READ_DATA: u16 = 0x11;
+const DRIVE_STATUS: u16 = 0x12;
+const EXT_CONNECTOR_READ: u16 = 0x13;
+const WAVE_TABLE_START: u16 = 0x20;
+const WAVE_TABLE_END: u16 = 0x5F;
+const AUDIO_REGS_START: u16 = 0x60;
+const AUDIO_REGS_END: u16 = 0x6A;
+const VOLUME_GAIN: u16 = 0x70;
+const MOD_GAIN: u16 = 0x72;
+
+#[derive(Clone, Default)]
+struct Timer {
+    reload: u16,
+    counter: u16,
+    repeat: bool,
+    enabled: bool,
+    irq: bool,
+}
+
+state!(Timer {
+    reload,
+    counter,
+    repeat,
+    enabled,
+    irq,
+});
+
+impl Timer {
+    fn clock(&mut self) {
+        if !self.enabled {
+            return;
+        }
+
+        if self.counter == 0 {
+            self.irq = true;
+            self.counter = self.reload;
+            self.enabled = self.repeat;
+        } else {
+            self.counter -= 1;
+        }
+    }
+}
+
+/*
+ * Famicom Disk System: the RAM adapter provides 32KB of PRG RAM, 8KB of CHR RAM (handled by
+ * the cartridge as any board without CHR ROM), the disk drive interface, a timer IRQ and an
+ * extra wavetable audio channel. The PRG data of the cartridge is the BIOS.
+ */
+#[derive(Clone)]
+pub struct Fds {
+    ram: Vec<u8>,
+    drive: RefCell<drive::Drive>,
+    timer: RefCell<Timer>,
+    audio: audio::Audio,
+    mirror_mode: MirrorMode,
+    disk_regs_enabled: bool,
+    audio_regs_enabled: bool,
+    ext_connector: u8,
+}
+
+state!(Fds {
+    ram,
+    drive,
+    timer,
+    audio,
+    mirror_mode,
+    disk_regs_enabled,
+    audio_regs_enabled,
+    ext_connector,
+});
+
+impl Fds {
+    pub fn new(info: &CartridgeData) -> Self {
+        Self {
+            ram: vec![0; RAM_SIZE],
+            drive: RefCell::new(drive::Drive::new(info.disk_sides.clone())),
+            timer: RefCell::new(Timer::default()),
+            audio: audio::Audio::new(),
+            mirror_mode: info.mirror_mode,
+            disk_regs_enabled: false,
+            audio_regs_enabled: false,
+            ext_connector: 0,
+        }
+    }
+
+    pub fn disk_sides(&self) -> usize {
+        self.drive.borrow().side_count()
+    }
+
+    pub fn disk_side(&self) -> Option<usize> {
+        self.drive.borrow().side()
+    }
+
+    pub fn audio_output(&self) -> u16 {
+        self.audio.output()
+    }
+
+    pub fn insert_disk(&mut self, side: usize) {
+        self.drive.get_mut().insert(side);
+    }
+
+    pub fn eject_disk(&mut self) {
+        self.drive.get_mut().eject();
+    }
+
+    pub fn switch_disk_side(&mut self) {
+        self.drive.get_mut().switch_side();
+    }
+}
+
+impl Mappable for Fds {
+    fn configure(&mut self, addr: u16, val: u8) {
+        if addr <= RAM_END {
+            self.ram[addr as usize] = val;
+        }
+    }
+
+    fn read_prg(&self, addr: u16) -> Option<u8> {
+        if addr <= RAM_END {
+            Some(self.ram[addr as usize])
+        } else {
+            None
+        }
+    }
+
+    fn prg_addr(&self, addr: u16) -> usize {
+        (addr - BIOS_START) as usize
+    }
+
+    fn chr_addr(&self, addr: u16) -> usize {
+        addr as usize
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.mirror_mode
+    }
+
+    fn read_sram(&self, addr: u16) -> Option<u8> {
+        Some(self.ram[SRAM_OFFSET + addr as usize])
+    }
+
+    fn fill_ram(&mut self, pattern: crate::emulator::RamPattern) {
+        pattern.fill(&mut self.ram, crate::emulator::ram_pattern::Ram::Prg);
+    }
+
+    fn configure_sram(&mut self, addr: u16, val: u8) {
+        self.ram[SRAM_OFFSET + addr as usize] = val;
+    }
+
+    fn read_expansion(&self, addr: u16) -> Option<u8> {
+        let val = match addr {
+            DISK_STATUS if self.disk_regs_enabled => {
+                let mut timer = self.timer.borrow_mut();
+                let timer_irq = timer.irq as u8;
+                timer.irq = false;
+                timer_irq | self.drive.borrow_mut().read_status()
+            }
+            READ_DATA if self.disk_regs_enabled => self.drive.borrow_mut().read_data(),
+            DRIVE_STATUS if self.disk_regs_enabled => self.drive.borrow().read_drive_status(),
+            // bit 7 is the battery status
+            EXT_CONNECTOR_READ if self.disk_regs_enabled => 0x80 | (self.ext_connector & 0x7F),
+            WAVE_TABLE_START..=WAVE_TABLE_END if self.audio_regs_enabled => {
+                self.audio.read_wave_table(addr - WAVE_TABLE_START)
+            }
+            VOLUME_GAIN if self.audio_regs_enabled => self.audio.volume_gain(),
+            MOD_GAIN if self.audio_regs_enabled => self.audio.mod_gain(),
+            _ => return None,
+        };
+        Some(val)
+    }
+
+    fn peek_expansion(&self, addr: u16) -> Option<u8> {
+        match addr {
+            DISK_STATUS if self.disk_regs_enabled => {
+                Some(self.timer.borrow().irq as u8 | self.drive.borrow().peek_status())
+            }
+            READ_DATA if self.disk_regs_enabled => Some(self.drive.borrow().peek_data()),
+            _ => self.read_expansion(addr),
+        }
+    }
+
+    fn write_expansion(&mut self, addr: u16, val: u8) {
+        match addr {
+            IRQ_RELOAD_LO => {
+                let timer = self.timer.get_mut();
+                timer.reload = (timer.reload & 0xFF00) | val as u16;
+            }
+            IRQ_RELOAD_HI => {
+                let timer = self.timer.get_mut();
+                timer.reload = (timer.reload & 0x00FF) | (val as u16) << 8;
+            }
+            IRQ_CONTROL => {
+                let timer = self.timer.get_mut();
+                timer.repeat = val & 0b01 != 0;
+                timer.enabled = val & 0b10 != 0 && self.disk_regs_enabled;
+                if timer.enabled {
+                    timer.counter = timer.reload;
+                } else {
+                    timer.irq = false;
+                }
+            }
+            MASTER_IO_ENABLE => {
+                self.disk_regs_enabled = val & 0b01 != 0;
+                self.audio_regs_enabled = val & 0b10 != 0;
+                if !self.disk_regs_enabled {
+                    let timer = self.timer.get_mut();
+                    timer.enabled = false;
+                    timer.irq = false;
+                    self.drive.get_mut().irq = false;
+                }
+            }
+            WRITE_DATA if self.disk_regs_enabled => self.drive.get_mut().write_data(val),
+            DRIVE_CONTROL if self.disk_regs_enabled => {
+                self.mirror_mode = if val & 0b0000_1000 != 0 {
+                    MirrorMode::Horizontal
+                } else {
+                    MirrorMode::Vertical
+                };
+                self.drive.get_mut().write_control(val);
+            }
+            EXT_CONNECTOR_WRITE if self.disk_regs_enabled => self.ext_connector = val,
+            WAVE_TABLE_START..=WAVE_TABLE_END if self.audio_regs_enabled => {
+                self.audio.write_wave_table(addr - WAVE_TABLE_START, val)
+            }
+            AUDIO_REGS_START..=AUDIO_REGS_END if self.audio_regs_enabled => {
+                self.audio.write(addr - AUDIO_REGS_START, val)
+            }
+            _ => (),
+        }
+    }
+
+    fn clock(&mut self) {
+        self.timer.get_mut().clock();
+        self.drive.get_mut().clock();
+        self.audio.clock();
+    }
+
+    // the FDS IRQs are level triggered, they stay active until acknowledged through $4030
+    fn take_irq(&mut self) -> bool {
+        self.timer.get_mut().irq || self.drive.get_mut().irq
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mk_fds() -> Fds {
+        let info = CartridgeData {
+            disk_sides: vec![vec![0; 0x100], vec![0; 0x100]],
+            ..Default::default()
+        };
+        let mut fds = Fds::new(&info);
+        fds.write_expansion(MASTER_IO_ENABLE, 0b11);
+        fds
+    }
+
+    #[test]
+    fn test_ram() {
+        let mut fds = mk_fds();
+        fds.configure_sram(0x0000, 0x01);
+        fds.configure(0x0000, 0x02);
+        fds.configure(0x5FFF, 0x03);
+        fds.configure(0x6000, 0x04);
+        assert_eq!(fds.read_sram(0x0000), Some(0x01));
+        assert_eq!(fds.read_prg(0x0000), Some(0x02));
+        assert_eq!(fds.read_prg(0x5FFF), Some(0x03));
+        assert_eq!(fds.read_prg(0x6000), None, "the BIOS is not writable");
+        assert_eq!(fds.prg_addr(0x6000), 0x0000);
+        assert_eq!(fds.prg_addr(0x7FFF), 0x1FFF);
+    }
+
+    #[test]
+    fn test_fill_ram() {
+        let mut fds = mk_fds();
+        fds.fill_ram(crate::emulator::RamPattern::Ones);
+        assert_eq!(fds.read_prg(0x0000), Some(0xFF));
+        assert_eq!(fds.read_sram(0x1FFF), Some(0xFF));
+    }
+
+    #[test]
+    fn test_timer_irq() {
+        let mut fds = mk_fds();
+        fds.write_expansion(IRQ_RELOAD_LO, 0x02);
+        fds.write_expansion(IRQ_RELOAD_HI, 0x00);
+        fds.write_expansion(IRQ_CONTROL, 0b11); // enabled, repeat
+
+        for _ in 0..2 {
+            fds.clock();
+            fds.clock();
+            assert!(!fds.take_irq());
+            fds.clock();
+            assert!(fds.take_irq());
+            assert!(fds.take_irq(), "the IRQ stays active until acknowledged");
+            assert_eq!(fds.peek_expansion(DISK_STATUS).unwrap() & 0x01, 0x01);
+            assert!(fds.take_irq(), "peeking doesn't acknowledge the IRQ");
+            assert_eq!(fds.read_expansion(DISK_STATUS).unwrap() & 0x01, 0x01);
+            assert!(!fds.take_irq());
+        }
+
+        fds.write_expansion(IRQ_CONTROL, 0b10); // enabled, no repeat
+        for _ in 0..3 {
+            fds.clock();
+        }
+        assert!(fds.take_irq());
+        fds.read_expansion(DISK_STATUS);
+        for _ in 0..10 {
+            fds.clock();
+        }
+        assert!(!fds.take_irq());
+    }
+
+    #[test]
+    fn test_disk_regs_disabled() {
+        let mut fds = mk_fds();
+        fds.write_expansion(MASTER_IO_ENABLE, 0b00);
+        fds.write_expansion(IRQ_RELOAD_LO, 0x00);
+        fds.write_expansion(IRQ_CONTROL, 0b10);
+        fds.clock();
+        assert!(!fds.take_irq());
+        assert_eq!(fds.read_expansion(DRIVE_STATUS), None);
+    }
+
+    #[test]
+    fn test_mirror_mode() {
+        let mut fds = mk_fds();
+        fds.write_expansion(DRIVE_CONTROL, 0b0000_0000);
+        assert_eq!(fds.mirror_mode(), MirrorMode::Vertical);
+        fds.write_expansion(DRIVE_CONTROL, 0b0000_1000);
+        assert_eq!(fds.mirror_mode(), MirrorMode::Horizontal);
+    }
+
+    #[test]
+    fn test_disk_swap() {
+        let mut fds = mk_fds();
+        assert_eq!(fds.disk_sides(), 2);
+        assert_eq!(fds.disk_side(), Some(0));
+        assert_eq!(fds.read_expansion(DRIVE_STATUS).unwrap() & 0x01, 0x00);
+        fds.eject_disk();
+        assert_eq!(fds.disk_side(), None);
+        assert_eq!(fds.read_expansion(DRIVE_STATUS).unwrap() & 0x01, 0x01);
+        fds.insert_disk(1);
+        assert_eq!(fds.disk_side(), Some(1));
+    }
+
+    #[test]
+    fn test_wave_table() {
+        let mut fds = mk_fds();
+        fds.write_expansion(AUDIO_REGS_START + 9, 0x80);
+        fds.write_expansion(WAVE_TABLE_START + 3, 0x15);
+        assert_eq!(fds.read_expansion(WAVE_TABLE_START + 3), Some(0x15));
+    }
+}
@@ -8,6 +8,13 @@ pub struct Mapper000 {
     mirror_mode: MirrorMode,
 }
 
+state!(Mapper000 {
+    prg_bank1,
+    prg_bank2,
+    chr_bank,
+    mirror_mode,
+});
+
 impl Mapper000 {
     pub fn new(info: &CartridgeData) -> Self {
         assert!(
@@ -16,6 +16,17 @@ pub struct Mapper001 {
     last_prg_bank: usize,
 }
 
+state!(Mapper001 {
+    load_register,
+    control_register,
+    prg_bank_16_hi,
+    prg_bank_16_lo,
+    prg_bank_32,
+    chr_bank_4_hi,
+    chr_bank_4_lo,
+    chr_bank_8,
+});
+
 impl Mapper001 {
     pub fn new(info: &CartridgeData) -> Self {
         let last_prg_bank = info.prg_banks - 1;
@@ -119,6 +130,8 @@ impl Mappable for Mapper001 {
 #[derive(Clone)]
 struct LoadRegister(u8);
 
+state!(LoadRegister { 0 });
+
 impl LoadRegister {
     fn new() -> Self {
         Self(0b0010_0000)
@@ -162,6 +175,8 @@ enum ChrRomMode {
 #[derive(Clone)]
 struct ControlRegister(u8);
 
+state!(ControlRegister { 0 });
+
 impl ControlRegister {
     fn write(&mut self, val: u8) {
         self.0 = val;
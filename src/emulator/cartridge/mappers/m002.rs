@@ -7,6 +7,12 @@ pub struct Mapper002 {
     mirror_mode: MirrorMode,
 }
 
+state!(Mapper002 {
+    lo_prg_bank,
+    hi_prg_bank,
+    mirror_mode,
+});
+
 impl Mapper002 {
     pub fn new(info: &CartridgeData) -> Self {
         Self {
@@ -6,6 +6,11 @@ pub struct Mapper003 {
     mirror_mode: MirrorMode,
 }
 
+state!(Mapper003 {
+    chr_bank,
+    mirror_mode,
+});
+
 impl Mapper003 {
     pub fn new(info: &CartridgeData) -> Self {
         Self {
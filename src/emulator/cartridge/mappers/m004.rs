@@ -1,7 +1,19 @@
 use super::*;
 
+const MMC6_PRG_RAM_SIZE: usize = 0x0400;
+const MMC6_PRG_RAM_START: u16 = 0x1000;
+const MMC6_PRG_RAM_HI_HALF: u16 = 0x0200;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Chip {
+    Mmc3,
+    // StarTropics boards, with 1KB of PRG RAM inside the mapper
+    Mmc6,
+}
+
 #[derive(Clone)]
 pub struct Mapper004 {
+    chip: Chip,
     mirror_mode: MirrorMode,
     prg_mode: PrgBankMode,
     chr_inversion: bool,
@@ -12,6 +24,9 @@ pub struct Mapper004 {
 
     prg_ram_protect: bool,
     pgr_ram_enabled: bool,
+    mmc6_ram: Vec<u8>,
+    mmc6_ram_enabled: bool,
+    mmc6_ram_protect: Mmc6RamProtect,
 
     prg_banks: [Bank<0x2000>; 4],
     chr_banks: [Bank<0x0400>; 8],
@@ -19,19 +34,60 @@ pub struct Mapper004 {
     last_prg_bank: usize,
 }
 
+// the chip is fixed
+state!(Mapper004 {
+    mirror_mode,
+    prg_mode,
+    chr_inversion,
+    selected_reg,
+    registers,
+    irq,
+    prg_ram_protect,
+    pgr_ram_enabled,
+    mmc6_ram,
+    mmc6_ram_enabled,
+    mmc6_ram_protect,
+    prg_banks,
+    chr_banks,
+});
+
 impl Mapper004 {
     pub fn new(info: &CartridgeData) -> Self {
+        // NES 2.0 submappers: 1 = MMC6, 4 = MMC3A (the old IRQ behavior)
+        let chip = if info.submapper == 1 {
+            Chip::Mmc6
+        } else {
+            Chip::Mmc3
+        };
+        let irq_revision = if info.submapper == 4 {
+            IrqRevision::A
+        } else {
+            IrqRevision::B
+        };
+        let mmc6_ram_size = if chip == Chip::Mmc6 {
+            MMC6_PRG_RAM_SIZE
+        } else {
+            0
+        };
+
         let mut mapper = Self {
+            chip,
             mirror_mode: MirrorMode::Horizontal,
             prg_mode: PrgBankMode::A,
             chr_inversion: false,
             selected_reg: 0,
             registers: [0; 8],
 
-            irq: std::cell::RefCell::new(Irq::default()),
+            irq: std::cell::RefCell::new(Irq {
+                revision: irq_revision,
+                ..Default::default()
+            }),
 
             prg_ram_protect: false,
             pgr_ram_enabled: false,
+            mmc6_ram: vec![0; mmc6_ram_size],
+            mmc6_ram_enabled: false,
+            mmc6_ram_protect: Mmc6RamProtect::default(),
 
             prg_banks: [Bank(0); 4],
             chr_banks: [Bank(0); 8],
@@ -45,6 +101,22 @@ impl Mapper004 {
         mapper.prg_banks[3].select(mapper.last_prg_bank);
         mapper
     }
+
+    // the IRQ counter counts the rises of the PPU A12 line, seen on every CHR fetch
+    pub(super) fn clock_a12(&self, addr: u16) {
+        self.irq.borrow_mut().register_a12_state(addr);
+    }
+
+    pub(super) fn chr_bank(&self, addr: u16) -> Bank<0x0400> {
+        self.chr_banks[(addr as usize >> 10) & 0b111]
+    }
+
+    fn mmc6_ram_addr(&self, addr: u16) -> Option<usize> {
+        if self.chip != Chip::Mmc6 || addr < MMC6_PRG_RAM_START {
+            return None;
+        }
+        Some(addr as usize & (MMC6_PRG_RAM_SIZE - 1))
+    }
 }
 
 impl Mappable for Mapper004 {
@@ -55,6 +127,10 @@ impl Mappable for Mapper004 {
                     self.selected_reg = val as usize & 0x07;
                     self.prg_mode = PrgBankMode::from(val & 0x40 == 0);
                     self.chr_inversion = val & 0x80 != 0;
+                    self.mmc6_ram_enabled = val & 0x20 != 0;
+                    if !self.mmc6_ram_enabled {
+                        self.mmc6_ram_protect = Mmc6RamProtect::default();
+                    }
                 }
                 _ => {
                     self.registers[self.selected_reg] = val as usize;
@@ -100,6 +176,11 @@ impl Mappable for Mapper004 {
                         MirrorMode::Horizontal
                     }
                 }
+                _ if self.chip == Chip::Mmc6 => {
+                    if self.mmc6_ram_enabled {
+                        self.mmc6_ram_protect = Mmc6RamProtect::from(val);
+                    }
+                }
                 _ => {
                     self.prg_ram_protect = val & 0x40 == 0;
                     self.pgr_ram_enabled = val & 0x80 == 0;
@@ -107,9 +188,19 @@ impl Mappable for Mapper004 {
             },
             0x4000..=0x5FFF => match addr & 1 {
                 0 => self.irq.borrow_mut().latch = val,
-                _ => self.irq.borrow_mut().counter = 0,
+                _ => {
+                    let mut irq = self.irq.borrow_mut();
+                    irq.counter = 0;
+                    irq.reload = true;
+                }
             },
-            0x6000..=0x7FFF => self.irq.borrow_mut().enabled = (addr & 0x0001) == 1,
+            0x6000..=0x7FFF => {
+                let mut irq = self.irq.borrow_mut();
+                irq.enabled = (addr & 0x0001) == 1;
+                if !irq.enabled {
+                    irq.irq = None;
+                }
+            }
             _ => unreachable!(),
         }
     }
@@ -126,20 +217,45 @@ impl Mappable for Mapper004 {
     }
 
     fn chr_addr(&self, addr: u16) -> usize {
-        self.irq.borrow_mut().register_a12_state(addr);
+        self.clock_a12(addr);
+        self.peek_chr_addr(addr)
+    }
 
-        match addr {
-            0x0000..=0x03FF => self.chr_banks[0],
-            0x0400..=0x07FF => self.chr_banks[1],
-            0x0800..=0x0BFF => self.chr_banks[2],
-            0x0C00..=0x0FFF => self.chr_banks[3],
-            0x1000..=0x13FF => self.chr_banks[4],
-            0x1400..=0x17FF => self.chr_banks[5],
-            0x1800..=0x1BFF => self.chr_banks[6],
-            0x1C00..=0x1FFF => self.chr_banks[7],
-            _ => unreachable!(),
+    fn peek_chr_addr(&self, addr: u16) -> usize {
+        self.chr_bank(addr).resolve_address(addr)
+    }
+
+    fn fill_ram(&mut self, pattern: crate::emulator::RamPattern) {
+        pattern.fill(&mut self.mmc6_ram, crate::emulator::ram_pattern::Ram::Prg);
+    }
+
+    fn read_sram(&self, addr: u16) -> Option<u8> {
+        if self.chip != Chip::Mmc6 {
+            return None;
+        }
+
+        // the half not readable, and $6000-$6FFF, read as 0
+        let readable = self.mmc6_ram_enabled
+            && self
+                .mmc6_ram_protect
+                .readable(addr & MMC6_PRG_RAM_HI_HALF != 0);
+        let val = match self.mmc6_ram_addr(addr) {
+            Some(ram_addr) if readable => self.mmc6_ram[ram_addr],
+            _ => 0,
+        };
+        Some(val)
+    }
+
+    fn configure_sram(&mut self, addr: u16, val: u8) {
+        if let Some(ram_addr) = self.mmc6_ram_addr(addr) {
+            if self.mmc6_ram_enabled
+                && self
+                    .mmc6_ram_protect
+                    .writable(addr & MMC6_PRG_RAM_HI_HALF != 0)
+            {
+                self.mmc6_ram[ram_addr] = val;
+            }
         }
-        .resolve_address(addr)
     }
 
     fn mirror_mode(&self) -> MirrorMode {
@@ -157,6 +273,8 @@ enum PrgBankMode {
     B,
 }
 
+state_enum!(PrgBankMode { A = 0, B = 1 });
+
 impl From<bool> for PrgBankMode {
     fn from(value: bool) -> Self {
         if value {
@@ -167,15 +285,83 @@ impl From<bool> for PrgBankMode {
     }
 }
 
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+struct Mmc6RamProtect {
+    lo_read: bool,
+    lo_write: bool,
+    hi_read: bool,
+    hi_write: bool,
+}
+
+state!(Mmc6RamProtect {
+    lo_read,
+    lo_write,
+    hi_read,
+    hi_write,
+});
+
+impl From<u8> for Mmc6RamProtect {
+    fn from(value: u8) -> Self {
+        Self {
+            lo_write: value & 0b0001_0000 != 0,
+            lo_read: value & 0b0010_0000 != 0,
+            hi_write: value & 0b0100_0000 != 0,
+            hi_read: value & 0b1000_0000 != 0,
+        }
+    }
+}
+
+impl Mmc6RamProtect {
+    fn readable(&self, hi: bool) -> bool {
+        if hi {
+            self.hi_read
+        } else {
+            self.lo_read
+        }
+    }
+
+    fn writable(&self, hi: bool) -> bool {
+        if hi {
+            self.hi_write
+        } else {
+            self.lo_write
+        }
+    }
+}
+
+/*
+ * Revision A (old, NEC) only fires when the counter is decremented to 0 or reloaded by $C001.
+ * Revision B (new, Sharp) fires whenever the counter is 0 after being clocked, so a latch of 0
+ * generates an IRQ on every scanline.
+ */
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+enum IrqRevision {
+    A,
+    #[default]
+    B,
+}
+
 #[derive(Default, Clone)]
 struct Irq {
+    pub revision: IrqRevision,
     pub a12_state: bool,
     pub enabled: bool,
+    pub reload: bool,
     pub counter: u8,
     pub latch: u8,
     pub irq: Option<()>,
 }
 
+// the revision is fixed
+state!(Irq {
+    a12_state,
+    enabled,
+    reload,
+    counter,
+    latch,
+    irq,
+});
+
 impl Irq {
     pub fn register_a12_state(&mut self, addr: u16) {
         let a12_state = addr & 0x1000 != 0;
@@ -186,13 +372,21 @@ impl Irq {
     }
 
     fn step_scanline(&mut self) {
-        if self.counter == 0 {
+        let prev_counter = self.counter;
+        if self.counter == 0 || self.reload {
             self.counter = self.latch;
         } else {
             self.counter -= 1;
-            if self.counter == 0 && self.enabled {
-                self.irq = Some(());
-            }
+        }
+
+        let fire = match self.revision {
+            IrqRevision::A => self.counter == 0 && (prev_counter != 0 || self.reload),
+            IrqRevision::B => self.counter == 0,
+        };
+        self.reload = false;
+
+        if fire && self.enabled {
+            self.irq = Some(());
         }
     }
 }
@@ -209,6 +403,119 @@ mod tests {
         }
     }
 
+    fn mk_info_submapper(submapper: u8) -> CartridgeData {
+        CartridgeData {
+            submapper,
+            ..mk_info()
+        }
+    }
+
+    fn clock_scanline(mapper: &Mapper004) {
+        mapper.chr_addr(0x0000);
+        mapper.chr_addr(0x1000);
+    }
+
+    #[test]
+    fn test_irq_latch_zero() {
+        // rev B fires on every scanline when the latch is 0
+        let mut mapper = Mapper004::new(&mk_info());
+        mapper.configure(0x6001, 0);
+        mapper.configure(0x4000, 0);
+        mapper.configure(0x4001, 0);
+        for _ in 0..3 {
+            clock_scanline(&mapper);
+            assert!(mapper.take_irq());
+        }
+
+        // rev A only fires once, right after the reload
+        let mut mapper = Mapper004::new(&mk_info_submapper(4));
+        mapper.configure(0x6001, 0);
+        mapper.configure(0x4000, 0);
+        mapper.configure(0x4001, 0);
+        clock_scanline(&mapper);
+        assert!(mapper.take_irq());
+        for _ in 0..3 {
+            clock_scanline(&mapper);
+            assert!(!mapper.take_irq());
+        }
+    }
+
+    #[test]
+    fn test_irq_acknowledge() {
+        let mut mapper = Mapper004::new(&mk_info());
+        mapper.configure(0x6001, 0);
+        mapper.configure(0x4000, 1);
+        mapper.configure(0x4001, 0);
+        clock_scanline(&mapper);
+        clock_scanline(&mapper);
+        mapper.configure(0x6000, 0);
+        assert!(!mapper.take_irq(), "writing $E000 acknowledges the IRQ");
+    }
+
+    #[test]
+    fn test_mmc6_ram() {
+        let mut mapper = Mapper004::new(&mk_info_submapper(1));
+        mapper.configure_sram(0x1000, 0x42);
+        assert_eq!(mapper.read_sram(0x1000), Some(0), "RAM starts disabled");
+
+        mapper.configure(0x0000, 0b0010_0000); // enable RAM
+        mapper.configure(0x2001, 0b1111_0000); // both halves read/write
+        mapper.configure_sram(0x1000, 0x42);
+        mapper.configure_sram(0x1200, 0x24);
+        assert_eq!(mapper.read_sram(0x1000), Some(0x42));
+        assert_eq!(mapper.read_sram(0x1400), Some(0x42), "1KB mirrored");
+        assert_eq!(mapper.read_sram(0x1200), Some(0x24));
+        assert_eq!(mapper.read_sram(0x0000), Some(0), "$6000-$6FFF reads as 0");
+
+        mapper.configure(0x2001, 0b1010_0000); // both halves read only
+        mapper.configure_sram(0x1000, 0x11);
+        assert_eq!(mapper.read_sram(0x1000), Some(0x42));
+
+        mapper.configure(0x2001, 0b1000_0000); // only the high half is readable
+        assert_eq!(mapper.read_sram(0x1000), Some(0));
+        assert_eq!(mapper.read_sram(0x1200), Some(0x24));
+
+        mapper.configure(0x0000, 0b0000_0000); // disable RAM
+        mapper.configure(0x2001, 0b1111_0000); // ignored while disabled
+        assert_eq!(mapper.read_sram(0x1200), Some(0));
+
+        let mapper = Mapper004::new(&mk_info());
+        assert_eq!(mapper.read_sram(0x1000), None, "MMC3 uses the regular SRAM");
+    }
+
+    #[test]
+    fn test_mmc6_fill_ram() {
+        let mut mapper = Mapper004::new(&mk_info_submapper(1));
+        mapper.fill_ram(crate::emulator::RamPattern::Ones);
+        mapper.configure(0x0000, 0b0010_0000); // enable RAM
+        mapper.configure(0x2001, 0b1111_0000); // both halves read/write
+        assert_eq!(mapper.read_sram(0x1000), Some(0xFF));
+        assert_eq!(mapper.read_sram(0x13FF), Some(0xFF));
+    }
+
+    #[test]
+    fn test_state() {
+        use crate::emulator::state;
+
+        let mut mapper = Mapper004::new(&mk_info_submapper(1));
+        mapper.configure(0x0000, 0b0010_0110); // enable RAM, select R6
+        mapper.configure(0x0001, 3);
+        mapper.configure(0x2001, 0b1111_0000);
+        mapper.configure_sram(0x1000, 0x42);
+        mapper.configure(0x4000, 1);
+        mapper.configure(0x6001, 0);
+        clock_scanline(&mapper);
+        let data = state::write(&mapper);
+
+        let mut loaded = Mapper004::new(&mk_info_submapper(1));
+        state::read(&mut loaded, &data).unwrap();
+        assert_eq!(state::write(&loaded), data);
+        assert_eq!(loaded.prg_addr(0x0000), mapper.prg_addr(0x0000));
+        assert_eq!(loaded.read_sram(0x1000), Some(0x42));
+        clock_scanline(&loaded);
+        assert!(loaded.take_irq(), "the IRQ counter is restored");
+    }
+
     #[test]
     fn test_irq() {
         let mut mapper = Mapper004::new(&mk_info());
@@ -241,6 +548,24 @@ mod tests {
         assert!(!mapper.take_irq());
     }
 
+    #[test]
+    fn test_peek_chr_addr() {
+        let mut mapper = Mapper004::new(&mk_info());
+        mapper.configure(0x6001, 0);
+        mapper.configure(0x4000, 0);
+        mapper.configure(0x4001, 0);
+        mapper.configure(0x0000, 0);
+        mapper.configure(0x0001, 4);
+        for _ in 0..3 {
+            assert_eq!(mapper.peek_chr_addr(0x0000), 0x1000);
+            mapper.peek_chr_addr(0x1000);
+        }
+        assert!(!mapper.take_irq(), "peeking doesn't clock the IRQ counter");
+        assert_eq!(mapper.chr_addr(0x0000), 0x1000);
+        mapper.chr_addr(0x1000);
+        assert!(mapper.take_irq());
+    }
+
     #[test]
     fn test_mirror_mode() {
         let mut mapper = Mapper004::new(&mk_info());
@@ -0,0 +1,98 @@
+use super::*;
+
+#[derive(Clone)]
+pub struct Mapper011 {
+    prg_bank: Bank<0x8000>,
+    chr_bank: Bank<0x2000>,
+    prg_bank_count: usize,
+    chr_bank_count: usize,
+    mirror_mode: MirrorMode,
+}
+
+state!(Mapper011 {
+    prg_bank,
+    chr_bank,
+    mirror_mode,
+});
+
+impl Mapper011 {
+    pub fn new(info: &CartridgeData) -> Self {
+        Self {
+            prg_bank: Bank(0),
+            chr_bank: Bank(0),
+            prg_bank_count: (info.prg_banks / 2).max(1),
+            chr_bank_count: info.chr_banks.max(1),
+            mirror_mode: info.mirror_mode,
+        }
+    }
+}
+
+impl Mappable for Mapper011 {
+    fn configure(&mut self, _addr: u16, val: u8) {
+        // CCCC LLPP (the LL bits are the lockout defeat, ignored here)
+        let prg = val as usize & 0b0000_0011;
+        let chr = (val as usize & 0b1111_0000) >> 4;
+        self.prg_bank.select(prg % self.prg_bank_count);
+        self.chr_bank.select(chr % self.chr_bank_count);
+    }
+
+    fn prg_addr(&self, addr: u16) -> usize {
+        self.prg_bank.resolve_address(addr)
+    }
+
+    fn chr_addr(&self, addr: u16) -> usize {
+        self.chr_bank.resolve_address(addr)
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.mirror_mode
+    }
+
+    fn has_bus_conflicts(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mk_info(prg_banks: usize, chr_banks: usize, mirror_mode: MirrorMode) -> CartridgeData {
+        CartridgeData {
+            prg_banks,
+            chr_banks,
+            mirror_mode,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_prg_addr() {
+        let mut mapper = Mapper011::new(&mk_info(8, 16, MirrorMode::Horizontal));
+        assert_eq!(mapper.prg_addr(0x0000), 0x0000);
+        assert_eq!(mapper.prg_addr(0x7FFF), 0x7FFF);
+        mapper.configure(0, 0b0000_0010);
+        assert_eq!(mapper.prg_addr(0x0000), 0x10000);
+        assert_eq!(mapper.prg_addr(0x7FFF), 0x17FFF);
+    }
+
+    #[test]
+    fn test_chr_addr() {
+        let mut mapper = Mapper011::new(&mk_info(8, 16, MirrorMode::Horizontal));
+        assert_eq!(mapper.chr_addr(0x0000), 0x0000);
+        assert_eq!(mapper.chr_addr(0x1FFF), 0x1FFF);
+        mapper.configure(0, 0b1111_0000);
+        assert_eq!(mapper.chr_addr(0x0000), 0x1E000);
+        assert_eq!(mapper.chr_addr(0x1FFF), 0x1FFFF);
+        assert_eq!(mapper.prg_addr(0x0000), 0x0000);
+    }
+
+    #[test]
+    fn test_mirror_mode() {
+        let mapper = Mapper011::new(&mk_info(2, 1, MirrorMode::Horizontal));
+        assert_eq!(mapper.mirror_mode(), MirrorMode::Horizontal);
+
+        let mapper = Mapper011::new(&mk_info(2, 1, MirrorMode::Vertical));
+        assert_eq!(mapper.mirror_mode(), MirrorMode::Vertical);
+    }
+}
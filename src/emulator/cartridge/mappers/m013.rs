@@ -0,0 +1,106 @@
+use super::*;
+
+// CPROM has 16KB of CHR RAM, the first 4KB are fixed and the last 4KB are switchable
+const CHR_RAM_SIZE: usize = 0x4000;
+
+#[derive(Clone)]
+pub struct Mapper013 {
+    chr_bank: Bank<0x1000>,
+    mirror_mode: MirrorMode,
+}
+
+state!(Mapper013 {
+    chr_bank,
+    mirror_mode,
+});
+
+impl Mapper013 {
+    pub fn new(info: &CartridgeData) -> Self {
+        Self {
+            chr_bank: Bank(0),
+            mirror_mode: info.mirror_mode,
+        }
+    }
+}
+
+impl Mappable for Mapper013 {
+    fn configure(&mut self, _addr: u16, val: u8) {
+        self.chr_bank.select(val as usize & 0b11)
+    }
+
+    fn prg_addr(&self, addr: u16) -> usize {
+        addr as usize
+    }
+
+    fn chr_addr(&self, addr: u16) -> usize {
+        if addr < 0x1000 {
+            addr as usize
+        } else {
+            self.chr_bank.resolve_address(addr)
+        }
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.mirror_mode
+    }
+
+    fn has_bus_conflicts(&self) -> bool {
+        true
+    }
+
+    fn chr_ram_size(&self) -> usize {
+        CHR_RAM_SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mk_info(mirror_mode: MirrorMode) -> CartridgeData {
+        CartridgeData {
+            prg_banks: 2,
+            mirror_mode,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_prg_addr() {
+        let mut mapper = Mapper013::new(&mk_info(MirrorMode::Horizontal));
+        assert_eq!(mapper.prg_addr(0x0000), 0x0000);
+        assert_eq!(mapper.prg_addr(0x7FFF), 0x7FFF);
+        mapper.configure(0, 3);
+        assert_eq!(mapper.prg_addr(0x0000), 0x0000);
+    }
+
+    #[test]
+    fn test_chr_addr() {
+        let mut mapper = Mapper013::new(&mk_info(MirrorMode::Horizontal));
+        assert_eq!(mapper.chr_addr(0x0000), 0x0000);
+        assert_eq!(mapper.chr_addr(0x1000), 0x0000);
+        assert_eq!(mapper.chr_addr(0x1FFF), 0x0FFF);
+        mapper.configure(0, 2);
+        assert_eq!(mapper.chr_addr(0x0000), 0x0000);
+        assert_eq!(mapper.chr_addr(0x0FFF), 0x0FFF);
+        assert_eq!(mapper.chr_addr(0x1000), 0x2000);
+        assert_eq!(mapper.chr_addr(0x1FFF), 0x2FFF);
+        mapper.configure(0, 0xFF);
+        assert_eq!(mapper.chr_addr(0x1000), 0x3000);
+    }
+
+    #[test]
+    fn test_chr_ram_size() {
+        let mapper = Mapper013::new(&mk_info(MirrorMode::Horizontal));
+        assert_eq!(mapper.chr_ram_size(), 0x4000);
+    }
+
+    #[test]
+    fn test_mirror_mode() {
+        let mapper = Mapper013::new(&mk_info(MirrorMode::Horizontal));
+        assert_eq!(mapper.mirror_mode(), MirrorMode::Horizontal);
+
+        let mapper = Mapper013::new(&mk_info(MirrorMode::Vertical));
+        assert_eq!(mapper.mirror_mode(), MirrorMode::Vertical);
+    }
+}
@@ -0,0 +1,161 @@
+use super::*;
+
+const NINA_PRG_BANK_ADDR: u16 = 0x1FFD;
+const NINA_CHR_BANK_LO_ADDR: u16 = 0x1FFE;
+const NINA_CHR_BANK_HI_ADDR: u16 = 0x1FFF;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Board {
+    Bnrom,
+    Nina001,
+}
+
+impl Board {
+    fn detect(info: &CartridgeData) -> Self {
+        match info.submapper {
+            1 => Self::Nina001,
+            2 => Self::Bnrom,
+            // BNROM boards only have CHR RAM, NINA-001 has 8KB+ of CHR ROM
+            _ if info.chr_banks > 0 => Self::Nina001,
+            _ => Self::Bnrom,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Mapper034 {
+    board: Board,
+    prg_bank: Bank<0x8000>,
+    chr_bank_lo: Bank<0x1000>,
+    chr_bank_hi: Bank<0x1000>,
+    prg_bank_count: usize,
+    chr_bank_count: usize,
+    mirror_mode: MirrorMode,
+}
+
+state!(Mapper034 {
+    prg_bank,
+    chr_bank_lo,
+    chr_bank_hi,
+    mirror_mode,
+});
+
+impl Mapper034 {
+    pub fn new(info: &CartridgeData) -> Self {
+        Self {
+            board: Board::detect(info),
+            prg_bank: Bank(0),
+            chr_bank_lo: Bank(0),
+            chr_bank_hi: Bank(1),
+            prg_bank_count: (info.prg_banks / 2).max(1),
+            chr_bank_count: (info.chr_banks * 2).max(2),
+            mirror_mode: info.mirror_mode,
+        }
+    }
+}
+
+impl Mappable for Mapper034 {
+    fn configure(&mut self, _addr: u16, val: u8) {
+        if self.board == Board::Bnrom {
+            self.prg_bank.select(val as usize % self.prg_bank_count);
+        }
+    }
+
+    fn configure_sram(&mut self, addr: u16, val: u8) {
+        if self.board != Board::Nina001 {
+            return;
+        }
+
+        let val = val as usize;
+        match addr {
+            NINA_PRG_BANK_ADDR => self.prg_bank.select((val & 0x01) % self.prg_bank_count),
+            NINA_CHR_BANK_LO_ADDR => self.chr_bank_lo.select((val & 0x0F) % self.chr_bank_count),
+            NINA_CHR_BANK_HI_ADDR => self.chr_bank_hi.select((val & 0x0F) % self.chr_bank_count),
+            _ => (),
+        }
+    }
+
+    fn prg_addr(&self, addr: u16) -> usize {
+        self.prg_bank.resolve_address(addr)
+    }
+
+    fn chr_addr(&self, addr: u16) -> usize {
+        match self.board {
+            Board::Bnrom => addr as usize,
+            Board::Nina001 if addr < 0x1000 => self.chr_bank_lo.resolve_address(addr),
+            Board::Nina001 => self.chr_bank_hi.resolve_address(addr),
+        }
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.mirror_mode
+    }
+
+    fn has_bus_conflicts(&self) -> bool {
+        self.board == Board::Bnrom
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mk_info(prg_banks: usize, chr_banks: usize, submapper: u8) -> CartridgeData {
+        CartridgeData {
+            prg_banks,
+            chr_banks,
+            submapper,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_board_detection() {
+        assert_eq!(Board::detect(&mk_info(8, 0, 0)), Board::Bnrom);
+        assert_eq!(Board::detect(&mk_info(4, 2, 0)), Board::Nina001);
+        assert_eq!(Board::detect(&mk_info(4, 0, 1)), Board::Nina001);
+        assert_eq!(Board::detect(&mk_info(4, 2, 2)), Board::Bnrom);
+    }
+
+    #[test]
+    fn test_bnrom() {
+        let mut mapper = Mapper034::new(&mk_info(8, 0, 0));
+        assert!(mapper.has_bus_conflicts());
+        assert_eq!(mapper.prg_addr(0x0000), 0x0000);
+        mapper.configure(0, 3);
+        assert_eq!(mapper.prg_addr(0x0000), 0x18000);
+        assert_eq!(mapper.prg_addr(0x7FFF), 0x1FFFF);
+        mapper.configure_sram(NINA_PRG_BANK_ADDR, 0);
+        assert_eq!(
+            mapper.prg_addr(0x0000),
+            0x18000,
+            "BNROM should ignore the NINA-001 registers"
+        );
+        assert_eq!(mapper.chr_addr(0x1FFF), 0x1FFF);
+    }
+
+    #[test]
+    fn test_nina001() {
+        let mut mapper = Mapper034::new(&mk_info(4, 4, 0));
+        assert!(!mapper.has_bus_conflicts());
+        assert_eq!(mapper.prg_addr(0x0000), 0x0000);
+        assert_eq!(mapper.chr_addr(0x0000), 0x0000);
+        assert_eq!(mapper.chr_addr(0x1000), 0x1000);
+
+        mapper.configure(0, 1);
+        assert_eq!(
+            mapper.prg_addr(0x0000),
+            0x0000,
+            "NINA-001 has no registers in the PRG area"
+        );
+
+        mapper.configure_sram(NINA_PRG_BANK_ADDR, 1);
+        mapper.configure_sram(NINA_CHR_BANK_LO_ADDR, 5);
+        mapper.configure_sram(NINA_CHR_BANK_HI_ADDR, 2);
+        assert_eq!(mapper.prg_addr(0x0000), 0x8000);
+        assert_eq!(mapper.chr_addr(0x0000), 0x5000);
+        assert_eq!(mapper.chr_addr(0x0FFF), 0x5FFF);
+        assert_eq!(mapper.chr_addr(0x1000), 0x2000);
+        assert_eq!(mapper.chr_addr(0x1FFF), 0x2FFF);
+    }
+}
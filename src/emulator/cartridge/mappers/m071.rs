@@ -0,0 +1,125 @@
+use super::*;
+
+#[derive(Clone)]
+pub struct Mapper071 {
+    lo_prg_bank: Bank<0x4000>,
+    hi_prg_bank: Bank<0x4000>,
+    prg_bank_count: usize,
+    mirror_mode: MirrorMode,
+    // the NES 2.0 submapper 1 of Fire Hawk, the other boards have hardwired mirroring
+    has_mirroring_register: bool,
+}
+
+state!(Mapper071 {
+    lo_prg_bank,
+    hi_prg_bank,
+    mirror_mode,
+});
+
+impl Mapper071 {
+    pub fn new(info: &CartridgeData) -> Self {
+        Self {
+            lo_prg_bank: Bank(0),
+            hi_prg_bank: Bank(info.prg_banks - 1),
+            prg_bank_count: info.prg_banks,
+            mirror_mode: info.mirror_mode,
+            has_mirroring_register: info.submapper == 1,
+        }
+    }
+}
+
+impl Mappable for Mapper071 {
+    fn configure(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x1000..=0x1FFF if self.has_mirroring_register => {
+                self.mirror_mode = if val & 0b0001_0000 == 0 {
+                    MirrorMode::SingleScreen0
+                } else {
+                    MirrorMode::SingleScreen1
+                }
+            }
+            0x4000..=0x7FFF => self
+                .lo_prg_bank
+                .select((val as usize & 0b1111) % self.prg_bank_count),
+            _ => (),
+        }
+    }
+
+    fn prg_addr(&self, addr: u16) -> usize {
+        if addr >= 0x4000 {
+            self.hi_prg_bank.resolve_address(addr)
+        } else {
+            self.lo_prg_bank.resolve_address(addr)
+        }
+    }
+
+    fn chr_addr(&self, addr: u16) -> usize {
+        addr as usize
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.mirror_mode
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mk_info(prg_banks: usize, mirror_mode: MirrorMode) -> CartridgeData {
+        CartridgeData {
+            prg_banks,
+            mirror_mode,
+            ..Default::default()
+        }
+    }
+
+    fn mk_fire_hawk_info(prg_banks: usize, mirror_mode: MirrorMode) -> CartridgeData {
+        CartridgeData {
+            submapper: 1,
+            ..mk_info(prg_banks, mirror_mode)
+        }
+    }
+
+    #[test]
+    fn test_prg_addr() {
+        let mut mapper = Mapper071::new(&mk_info(8, MirrorMode::Horizontal));
+        assert_eq!(mapper.prg_addr(0x0000), 0x0000);
+        assert_eq!(mapper.prg_addr(0x4000), 0x1C000);
+        assert_eq!(mapper.prg_addr(0x7FFF), 0x1FFFF);
+        mapper.configure(0x4000, 3);
+        assert_eq!(mapper.prg_addr(0x0000), 0xC000);
+        assert_eq!(mapper.prg_addr(0x3FFF), 0xFFFF);
+        mapper.configure(0x0000, 5);
+        assert_eq!(
+            mapper.prg_addr(0x0000),
+            0xC000,
+            "writes below $C000 should not switch banks"
+        );
+    }
+
+    #[test]
+    fn test_chr_addr() {
+        let mapper = Mapper071::new(&mk_info(8, MirrorMode::Horizontal));
+        assert_eq!(mapper.chr_addr(0x0000), 0x0000);
+        assert_eq!(mapper.chr_addr(0x1FFF), 0x1FFF);
+    }
+
+    #[test]
+    fn test_mirror_mode() {
+        let mut mapper = Mapper071::new(&mk_info(8, MirrorMode::Vertical));
+        mapper.configure(0x1000, 0b0001_0000);
+        assert_eq!(
+            mapper.mirror_mode(),
+            MirrorMode::Vertical,
+            "only Fire Hawk has the mirroring register"
+        );
+
+        let mut mapper = Mapper071::new(&mk_fire_hawk_info(8, MirrorMode::Vertical));
+        assert_eq!(mapper.mirror_mode(), MirrorMode::Vertical);
+        mapper.configure(0x1000, 0b0001_0000);
+        assert_eq!(mapper.mirror_mode(), MirrorMode::SingleScreen1);
+        mapper.configure(0x1000, 0b0000_0000);
+        assert_eq!(mapper.mirror_mode(), MirrorMode::SingleScreen0);
+    }
+}
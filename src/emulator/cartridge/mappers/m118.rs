@@ -0,0 +1,104 @@
+use super::*;
+
+const CHR_BANK_MASK: usize = 0b0111_1111;
+
+/*
+ * TxSROM: an MMC3 where the CIRAM A10 line is wired to CHR A17 instead of the mirroring
+ * register, so the bit 7 of the CHR bank selecting each 1KB slot of $0000-$0FFF
+ * decides which CIRAM page is used by the matching nametable.
+ */
+#[derive(Clone)]
+pub struct Mapper118(Mapper004);
+
+state!(Mapper118 { 0 });
+
+impl Mapper118 {
+    pub fn new(info: &CartridgeData) -> Self {
+        Self(Mapper004::new(info))
+    }
+}
+
+impl Mappable for Mapper118 {
+    fn configure(&mut self, addr: u16, val: u8) {
+        // the mirroring register is not connected
+        if matches!(addr, 0x2000..=0x3FFF) && addr & 1 == 0 {
+            return;
+        }
+        self.0.configure(addr, val)
+    }
+
+    fn prg_addr(&self, addr: u16) -> usize {
+        self.0.prg_addr(addr)
+    }
+
+    fn chr_addr(&self, addr: u16) -> usize {
+        self.0.clock_a12(addr);
+        self.peek_chr_addr(addr)
+    }
+
+    fn peek_chr_addr(&self, addr: u16) -> usize {
+        // the bit 7 selects the nametable, it is not a CHR address line
+        let bank = self.0.chr_bank(addr);
+        Bank::<0x0400>(bank.0 & CHR_BANK_MASK).resolve_address(addr)
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.0.mirror_mode()
+    }
+
+    fn nametable(&self, table: u16) -> Nametable {
+        let bank = self.0.chr_bank((table & 0b11) << 10);
+        Nametable::Ciram((bank.0 >> 7) & 1)
+    }
+
+    fn take_irq(&mut self) -> bool {
+        self.0.take_irq()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mk_info() -> CartridgeData {
+        CartridgeData {
+            chr_banks: 16,
+            prg_banks: 8,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_nametable() {
+        let mut mapper = Mapper118::new(&mk_info());
+        mapper.configure(0x0000, 0b0000_0000); // R0, invert off
+        mapper.configure(0x0001, 0b1000_0000);
+        mapper.configure(0x0000, 0b0000_0001); // R1, invert off
+        mapper.configure(0x0001, 0b0000_0010);
+        assert_eq!(mapper.nametable(0), Nametable::Ciram(1));
+        assert_eq!(mapper.nametable(1), Nametable::Ciram(1));
+        assert_eq!(mapper.nametable(2), Nametable::Ciram(0));
+        assert_eq!(mapper.nametable(3), Nametable::Ciram(0));
+
+        mapper.configure(0x0000, 0b1000_0010); // R2, invert on
+        mapper.configure(0x0001, 0b0000_0000);
+        mapper.configure(0x0000, 0b1000_0011); // R3, invert on
+        mapper.configure(0x0001, 0b1000_0000);
+        mapper.configure(0x0000, 0b1000_0100); // R4, invert on
+        mapper.configure(0x0001, 0b1000_0000);
+        mapper.configure(0x0000, 0b1000_0101); // R5, invert on
+        mapper.configure(0x0001, 0b0000_0000);
+        assert_eq!(mapper.nametable(0), Nametable::Ciram(0));
+        assert_eq!(mapper.nametable(1), Nametable::Ciram(1));
+        assert_eq!(mapper.nametable(2), Nametable::Ciram(1));
+        assert_eq!(mapper.nametable(3), Nametable::Ciram(0));
+    }
+
+    #[test]
+    fn test_ignore_mirroring_register() {
+        let mut mapper = Mapper118::new(&mk_info());
+        mapper.configure(0x2000, 0);
+        assert_eq!(mapper.nametable(0), Nametable::Ciram(0));
+        assert_eq!(mapper.nametable(3), Nametable::Ciram(0));
+    }
+}
@@ -0,0 +1,86 @@
+use super::*;
+
+const CHR_RAM_BANK_FLAG: usize = 0b0100_0000;
+const CHR_RAM_BANK_MASK: usize = 0b0000_0111;
+
+/*
+ * TQROM: an MMC3 with both CHR ROM and 8KB of CHR RAM.
+ * The bit 6 of the CHR bank number selects the RAM instead of the ROM.
+ */
+#[derive(Clone)]
+pub struct Mapper119(Mapper004);
+
+state!(Mapper119 { 0 });
+
+impl Mapper119 {
+    pub fn new(info: &CartridgeData) -> Self {
+        Self(Mapper004::new(info))
+    }
+}
+
+impl Mappable for Mapper119 {
+    fn configure(&mut self, addr: u16, val: u8) {
+        self.0.configure(addr, val)
+    }
+
+    fn prg_addr(&self, addr: u16) -> usize {
+        self.0.prg_addr(addr)
+    }
+
+    fn chr_addr(&self, addr: u16) -> usize {
+        self.0.clock_a12(addr);
+        self.peek_chr_addr(addr)
+    }
+
+    fn peek_chr_addr(&self, addr: u16) -> usize {
+        let bank = self.0.chr_bank(addr);
+        if bank.0 & CHR_RAM_BANK_FLAG != 0 {
+            Bank::<0x0400>(bank.0 & CHR_RAM_BANK_MASK).resolve_address(addr)
+        } else {
+            self.0.peek_chr_addr(addr)
+        }
+    }
+
+    fn is_chr_ram(&self, addr: u16) -> bool {
+        self.0.chr_bank(addr).0 & CHR_RAM_BANK_FLAG != 0
+    }
+
+    fn mirror_mode(&self) -> MirrorMode {
+        self.0.mirror_mode()
+    }
+
+    fn take_irq(&mut self) -> bool {
+        self.0.take_irq()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mk_info() -> CartridgeData {
+        CartridgeData {
+            chr_banks: 8,
+            prg_banks: 8,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_chr_addr() {
+        let mut mapper = Mapper119::new(&mk_info());
+        mapper.configure(0x0000, 0b0000_0010); // R2 => $1000
+        mapper.configure(0x0001, 0x05);
+        mapper.configure(0x0000, 0b0000_0011); // R3 => $1400
+        mapper.configure(0x0001, 0x45);
+
+        assert!(!mapper.is_chr_ram(0x1000));
+        assert_eq!(mapper.chr_addr(0x1000), 0x1400);
+        assert!(mapper.is_chr_ram(0x1400));
+        assert_eq!(mapper.chr_addr(0x1400), 0x1400);
+        assert_eq!(mapper.chr_addr(0x17FF), 0x17FF);
+
+        mapper.configure(0x0001, 0x7F);
+        assert_eq!(mapper.chr_addr(0x1400), 0x1C00);
+    }
+}
@@ -1,25 +1,85 @@
+mod fds;
 mod m000;
 mod m001;
 mod m002;
 mod m003;
 mod m004;
+mod m011;
+mod m013;
+mod m034;
+mod m066;
+mod m071;
+mod m118;
+mod m119;
 
 use super::*;
 
+pub use fds::{Fds, AUDIO_OUTPUT_MAX as FDS_AUDIO_OUTPUT_MAX};
 pub use m000::Mapper000;
 pub use m001::Mapper001;
 pub use m002::Mapper002;
 pub use m003::Mapper003;
 pub use m004::Mapper004;
+pub use m011::Mapper011;
+pub use m013::Mapper013;
+pub use m034::Mapper034;
+pub use m066::Mapper066;
+pub use m071::Mapper071;
+pub use m118::Mapper118;
+pub use m119::Mapper119;
 
 pub trait Mappable {
     fn prg_addr(&self, addr: u16) -> usize;
     fn chr_addr(&self, addr: u16) -> usize;
+    // mappers whose CHR reads have side effects must override it
+    fn peek_chr_addr(&self, addr: u16) -> usize {
+        self.chr_addr(addr)
+    }
     fn mirror_mode(&self) -> MirrorMode;
     fn configure(&mut self, addr: u16, val: u8);
+    fn configure_sram(&mut self, _addr: u16, _val: u8) {}
+    // Some(_) when the mapper answers reads to $8000-$FFFF with something other than PRG ROM
+    fn read_prg(&self, _addr: u16) -> Option<u8> {
+        None
+    }
+    // Some(_) when the mapper answers reads to $6000-$7FFF instead of the regular SRAM
+    fn read_sram(&self, _addr: u16) -> Option<u8> {
+        None
+    }
     fn take_irq(&mut self) -> bool {
         false
     }
+    // when true, the value written is ANDed with the PRG ROM byte at the same address
+    fn has_bus_conflicts(&self) -> bool {
+        false
+    }
+    fn chr_ram_size(&self) -> usize {
+        CHR_RAM_SIZE
+    }
+    // the PRG RAM provided by the mapper itself, filled at power on
+    fn fill_ram(&mut self, _pattern: crate::emulator::RamPattern) {}
+    // for boards mixing CHR ROM and CHR RAM
+    fn is_chr_ram(&self, _addr: u16) -> bool {
+        false
+    }
+    fn nametable(&self, table: u16) -> Nametable {
+        self.mirror_mode().nametable(table)
+    }
+    fn read_exram(&self, _addr: u16) -> u8 {
+        0
+    }
+    fn write_exram(&mut self, _addr: u16, _val: u8) {}
+    // $4020-$5FFF, relative to $4020
+    fn read_expansion(&self, _addr: u16) -> Option<u8> {
+        None
+    }
+    // mappers whose expansion reads have side effects must override it
+    fn peek_expansion(&self, addr: u16) -> Option<u8> {
+        self.read_expansion(addr)
+    }
+    fn write_expansion(&mut self, _addr: u16, _val: u8) {}
+    // called once per CPU cycle
+    fn clock(&mut self) {}
 }
 
 #[derive(Clone)]
@@ -29,6 +89,16 @@ pub enum Mapper {
     M002(Mapper002),
     M003(Mapper003),
     M004(Mapper004),
+    M011(Mapper011),
+    M013(Mapper013),
+    M034(Mapper034),
+    M066(Mapper066),
+    M071(Mapper071),
+    M118(Mapper118),
+    M119(Mapper119),
+    Fds(Fds),
+    #[cfg(test)]
+    Test(tests::NametableMapper),
 }
 
 /*
@@ -44,6 +114,16 @@ impl Mapper {
             2 => Self::M002(Mapper002::new(info)),
             3 => Self::M003(Mapper003::new(info)),
             4 => Self::M004(Mapper004::new(info)),
+            11 => Self::M011(Mapper011::new(info)),
+            13 => Self::M013(Mapper013::new(info)),
+            34 => Self::M034(Mapper034::new(info)),
+            66 => Self::M066(Mapper066::new(info)),
+            71 => Self::M071(Mapper071::new(info)),
+            118 => Self::M118(Mapper118::new(info)),
+            119 => Self::M119(Mapper119::new(info)),
+            super::fds::FDS_MAPPER_CODE => Self::Fds(Fds::new(info)),
+            #[cfg(test)]
+            tests::NAMETABLE_MAPPER_CODE => Self::Test(tests::NametableMapper::default()),
             _ => panic!("Unsupported mapper: {}", info.mapper_code),
         }
     }
@@ -55,6 +135,16 @@ impl Mapper {
             Self::M002(m) => m,
             Self::M003(m) => m,
             Self::M004(m) => m,
+            Self::M011(m) => m,
+            Self::M013(m) => m,
+            Self::M034(m) => m,
+            Self::M066(m) => m,
+            Self::M071(m) => m,
+            Self::M118(m) => m,
+            Self::M119(m) => m,
+            Self::Fds(m) => m,
+            #[cfg(test)]
+            Self::Test(m) => m,
         }
     }
 
@@ -65,6 +155,59 @@ impl Mapper {
             Self::M002(m) => m,
             Self::M003(m) => m,
             Self::M004(m) => m,
+            Self::M011(m) => m,
+            Self::M013(m) => m,
+            Self::M034(m) => m,
+            Self::M066(m) => m,
+            Self::M071(m) => m,
+            Self::M118(m) => m,
+            Self::M119(m) => m,
+            Self::Fds(m) => m,
+            #[cfg(test)]
+            Self::Test(m) => m,
+        }
+    }
+}
+
+// the mapper kind comes from the ROM, the state is loaded into the current mapper
+impl crate::emulator::state::State for Mapper {
+    fn save(&self, out: &mut Vec<u8>) {
+        match self {
+            Self::M000(m) => m.save(out),
+            Self::M001(m) => m.save(out),
+            Self::M002(m) => m.save(out),
+            Self::M003(m) => m.save(out),
+            Self::M004(m) => m.save(out),
+            Self::M011(m) => m.save(out),
+            Self::M013(m) => m.save(out),
+            Self::M034(m) => m.save(out),
+            Self::M066(m) => m.save(out),
+            Self::M071(m) => m.save(out),
+            Self::M118(m) => m.save(out),
+            Self::M119(m) => m.save(out),
+            Self::Fds(m) => m.save(out),
+            #[cfg(test)]
+            Self::Test(m) => m.save(out),
+        }
+    }
+
+    fn load(&mut self, data: &mut &[u8]) -> Result<(), String> {
+        match self {
+            Self::M000(m) => m.load(data),
+            Self::M001(m) => m.load(data),
+            Self::M002(m) => m.load(data),
+            Self::M003(m) => m.load(data),
+            Self::M004(m) => m.load(data),
+            Self::M011(m) => m.load(data),
+            Self::M013(m) => m.load(data),
+            Self::M034(m) => m.load(data),
+            Self::M066(m) => m.load(data),
+            Self::M071(m) => m.load(data),
+            Self::M118(m) => m.load(data),
+            Self::M119(m) => m.load(data),
+            Self::Fds(m) => m.load(data),
+            #[cfg(test)]
+            Self::Test(m) => m.load(data),
         }
     }
 }
@@ -72,6 +215,8 @@ impl Mapper {
 #[derive(Clone, Copy)]
 struct Bank<const SIZE: usize>(usize);
 
+state!(<const SIZE: usize> Bank<SIZE> { 0 });
+
 impl<const SIZE: usize> std::fmt::Debug for Bank<SIZE> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Bank")
@@ -92,9 +237,53 @@ impl<const SIZE: usize> Bank<SIZE> {
 }
 
 #[cfg(test)]
-mod tests {
+pub mod tests {
     use super::*;
 
+    pub const NAMETABLE_MAPPER_CODE: u8 = 255;
+
+    // $2000 from the CHR ROM page 1, $2400 from the ExRAM, the rest from the CIRAM
+    #[derive(Clone)]
+    pub struct NametableMapper {
+        exram: Vec<u8>,
+    }
+
+    state!(NametableMapper { exram });
+
+    impl Default for NametableMapper {
+        fn default() -> Self {
+            Self {
+                exram: vec![0; nametable::NAMETABLE_SIZE],
+            }
+        }
+    }
+
+    impl Mappable for NametableMapper {
+        fn prg_addr(&self, addr: u16) -> usize {
+            addr as usize
+        }
+        fn chr_addr(&self, addr: u16) -> usize {
+            addr as usize
+        }
+        fn mirror_mode(&self) -> MirrorMode {
+            MirrorMode::Vertical
+        }
+        fn configure(&mut self, _addr: u16, _val: u8) {}
+        fn nametable(&self, table: u16) -> Nametable {
+            match table & 0b11 {
+                0 => Nametable::ChrRom(1),
+                1 => Nametable::ExRam,
+                _ => self.mirror_mode().nametable(table),
+            }
+        }
+        fn read_exram(&self, addr: u16) -> u8 {
+            self.exram[addr as usize]
+        }
+        fn write_exram(&mut self, addr: u16, val: u8) {
+            self.exram[addr as usize] = val;
+        }
+    }
+
     #[test]
     fn test_bank() {
         let mut bank = Bank::<0x4000>(0);
@@ -1,7 +1,10 @@
+mod fds;
 mod i_nes;
 mod mappers;
+mod nametable;
 mod time_machine;
 
+pub use nametable::Nametable;
 pub use time_machine::TimeMachine;
 
 const CHR_RAM_SIZE: usize = 0x2000;
@@ -9,6 +12,7 @@ const CHR_RAM_SIZE: usize = 0x2000;
 #[derive(Debug, Default, Clone)]
 pub struct CartridgeData {
     pub mapper_code: u8,
+    pub submapper: u8,
     pub prg_banks: usize,
     pub chr_banks: usize,
     pub mirror_mode: MirrorMode,
@@ -16,12 +20,15 @@ pub struct CartridgeData {
     pub has_trainer: bool,
     pub prg_data: Vec<u8>,
     pub chr_data: Vec<u8>,
+    pub disk_sides: Vec<Vec<u8>>,
 }
 
 #[derive(Debug, Default, Clone)]
 pub struct RomInfo {
     pub name: String,
     pub cksum: u32,
+    // of the PRG and CHR ROM, or of the disk sides, as FCEUX computes it
+    pub md5: [u8; 16],
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
@@ -31,69 +38,493 @@ pub enum MirrorMode {
     Vertical,
     SingleScreen0,
     SingleScreen1,
+    FourScreen,
 }
 
+state_enum!(MirrorMode {
+    Horizontal = 0,
+    Vertical = 1,
+    SingleScreen0 = 2,
+    SingleScreen1 = 3,
+    FourScreen = 4,
+});
+
 pub struct Cartridge {
     rom_info: RomInfo,
     data: CartridgeData,
     chr_ram: Vec<u8>,
+    nametable_ram: Vec<u8>,
     mapper: mappers::Mapper,
+    prg_patches: Vec<super::cheats::Patch>,
 }
 
 impl Cartridge {
     pub fn new(rom_info: RomInfo, data: CartridgeData) -> Self {
         let mapper = mappers::Mapper::build(&data);
+        let nametable_ram_size = if data.mirror_mode == MirrorMode::FourScreen {
+            nametable::FOUR_SCREEN_RAM_SIZE
+        } else {
+            0
+        };
         Self {
             rom_info,
             data,
-            chr_ram: vec![0; CHR_RAM_SIZE],
+            chr_ram: vec![0; mapper.as_ref().chr_ram_size()],
+            nametable_ram: vec![0; nametable_ram_size],
             mapper,
+            prg_patches: Vec::new(),
         }
     }
 
+    // the CHR RAM, the four-screen nametable RAM and the PRG RAM of the mapper
+    pub fn fill_ram(&mut self, pattern: super::RamPattern) {
+        use super::ram_pattern::Ram;
+        pattern.fill(&mut self.chr_ram, Ram::Chr);
+        pattern.fill(&mut self.nametable_ram, Ram::Nametables);
+        self.mapper.as_mut().fill_ram(pattern);
+    }
+
     pub fn rom_info(&self) -> &RomInfo {
         &self.rom_info
     }
 
     pub fn read_prg(&self, addr: u16) -> u8 {
-        self.data.prg_data[self.mapper.as_ref().prg_addr(addr)]
+        let mapper = self.mapper.as_ref();
+        let val = mapper
+            .read_prg(addr)
+            .unwrap_or_else(|| self.data.prg_data[mapper.prg_addr(addr)]);
+        if self.prg_patches.is_empty() {
+            return val;
+        }
+        // the patches use CPU addresses, addr is relative to $8000
+        self.prg_patches
+            .iter()
+            .find(|patch| {
+                patch.addr == addr | 0x8000 && patch.compare.is_none_or(|compare| compare == val)
+            })
+            .map_or(val, |patch| patch.value)
+    }
+
+    // the Game Genie codes
+    pub fn set_prg_patches(&mut self, patches: Vec<super::cheats::Patch>) {
+        self.prg_patches = patches;
     }
 
     pub fn read_chr(&self, addr: u16) -> u8 {
-        if self.data.chr_banks == 0 {
-            self.chr_ram[addr as usize]
+        let is_ram = self.is_chr_ram(addr);
+        let addr = self.mapper.as_ref().chr_addr(addr);
+        self.chr_byte(is_ram, addr)
+    }
+
+    // the same as read_chr, without the side effects of the access on the mapper
+    pub fn peek_chr(&self, addr: u16) -> u8 {
+        let is_ram = self.is_chr_ram(addr);
+        let addr = self.mapper.as_ref().peek_chr_addr(addr);
+        self.chr_byte(is_ram, addr)
+    }
+
+    fn chr_byte(&self, is_ram: bool, addr: usize) -> u8 {
+        if is_ram {
+            self.chr_ram[addr % self.chr_ram.len()]
         } else {
-            let addr = self.mapper.as_ref().chr_addr(addr);
             self.data.chr_data[addr]
         }
     }
 
     pub fn write_prg(&mut self, addr: u16, val: u8) {
+        let val = if self.mapper.as_ref().has_bus_conflicts() {
+            val & self.read_prg(addr)
+        } else {
+            val
+        };
         self.mapper.as_mut().configure(addr, val);
     }
 
+    pub fn read_sram(&self, addr: u16) -> Option<u8> {
+        self.mapper.as_ref().read_sram(addr)
+    }
+
+    pub fn write_sram(&mut self, addr: u16, val: u8) {
+        self.mapper.as_mut().configure_sram(addr, val);
+    }
+
     pub fn write_chr(&mut self, addr: u16, val: u8) {
-        self.chr_ram[addr as usize] = val;
+        if self.is_chr_ram(addr) {
+            let addr = self.mapper.as_ref().chr_addr(addr);
+            let len = self.chr_ram.len();
+            self.chr_ram[addr % len] = val;
+        }
     }
 
-    pub fn mirror_mode(&self) -> MirrorMode {
-        self.mapper.as_ref().mirror_mode()
+    pub fn nametable(&self, table: u16) -> Nametable {
+        // boards wired for four-screen ignore the mapper's mirroring control
+        if self.data.mirror_mode == MirrorMode::FourScreen {
+            return MirrorMode::FourScreen.nametable(table);
+        }
+        self.mapper.as_ref().nametable(table)
+    }
+
+    pub fn read_nametable(&self, nametable: Nametable, addr: u16) -> u8 {
+        let offset = addr as usize & (nametable::NAMETABLE_SIZE - 1);
+        match nametable {
+            Nametable::Ram(page) => self.nametable_ram[page * nametable::NAMETABLE_SIZE + offset],
+            Nametable::ChrRom(page) => {
+                self.data.chr_data[page * nametable::NAMETABLE_SIZE + offset]
+            }
+            Nametable::ExRam => self.mapper.as_ref().read_exram(offset as u16),
+            Nametable::Ciram(_) => unreachable!("CIRAM belongs to the PPU"),
+        }
+    }
+
+    pub fn write_nametable(&mut self, nametable: Nametable, addr: u16, val: u8) {
+        let offset = addr as usize & (nametable::NAMETABLE_SIZE - 1);
+        match nametable {
+            Nametable::Ram(page) => {
+                self.nametable_ram[page * nametable::NAMETABLE_SIZE + offset] = val
+            }
+            Nametable::ChrRom(_) => (),
+            Nametable::ExRam => self.mapper.as_mut().write_exram(offset as u16, val),
+            Nametable::Ciram(_) => unreachable!("CIRAM belongs to the PPU"),
+        }
+    }
+
+    pub fn read_expansion(&self, addr: u16) -> Option<u8> {
+        self.mapper.as_ref().read_expansion(addr)
+    }
+
+    pub fn peek_expansion(&self, addr: u16) -> Option<u8> {
+        self.mapper.as_ref().peek_expansion(addr)
+    }
+
+    pub fn write_expansion(&mut self, addr: u16, val: u8) {
+        self.mapper.as_mut().write_expansion(addr, val);
     }
 
     pub fn take_irq(&mut self) -> bool {
         self.mapper.as_mut().take_irq()
     }
+
+    pub fn clock(&mut self) {
+        self.mapper.as_mut().clock();
+    }
+
+    // 0.0..=1.0
+    pub fn audio_output(&self) -> f32 {
+        match &self.mapper {
+            mappers::Mapper::Fds(fds) => {
+                fds.audio_output() as f32 / mappers::FDS_AUDIO_OUTPUT_MAX as f32
+            }
+            _ => 0.0,
+        }
+    }
+
+    pub fn disk_side(&self) -> Option<usize> {
+        match &self.mapper {
+            mappers::Mapper::Fds(fds) => fds.disk_side(),
+            _ => None,
+        }
+    }
+
+    pub fn insert_disk(&mut self, side: usize) {
+        if let mappers::Mapper::Fds(fds) = &mut self.mapper {
+            log!("Inserting disk side {}", side);
+            fds.insert_disk(side % fds.disk_sides());
+        }
+    }
+
+    pub fn eject_disk(&mut self) {
+        if let mappers::Mapper::Fds(fds) = &mut self.mapper {
+            log!("Ejecting disk");
+            fds.eject_disk();
+        }
+    }
+
+    pub fn switch_disk_side(&mut self) {
+        if let mappers::Mapper::Fds(fds) = &mut self.mapper {
+            log!("Switching disk side");
+            fds.switch_disk_side();
+        }
+    }
+
+    fn is_chr_ram(&self, addr: u16) -> bool {
+        self.data.chr_banks == 0 || self.mapper.as_ref().is_chr_ram(addr)
+    }
 }
 
-pub fn open_rom(path: &std::path::Path) -> Cartridge {
+pub fn open_rom(path: &std::path::Path) -> Result<Cartridge, String> {
     log!("Loading ROM file: {:?}", path);
-    let rom_data = std::fs::read(path).expect("Failed to read ROM file.");
+    let rom_data =
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    if fds::FdsImageBuilder::is_disk_image(&rom_data) {
+        return Err(format!(
+            "{}: FDS disk images require the BIOS, use the --fds-bios option",
+            path.display()
+        ));
+    }
+    // without the header, so fixing a bad header keeps the same checksum
+    let cartridge_data = i_nes::INesRomBuilder::build(&rom_data);
+    let rom_info = RomInfo {
+        name: path.file_name().unwrap().to_string_lossy().to_string(),
+        cksum: crc32(rom_data.get(i_nes::HEADER_SIZE..).unwrap_or_default()),
+        md5: md5(&[cartridge_data.prg_data.as_slice(), &cartridge_data.chr_data].concat()),
+    };
+
+    Ok(Cartridge::new(rom_info, cartridge_data))
+}
+
+pub fn open_disk(path: &std::path::Path, bios_path: &std::path::Path) -> Result<Cartridge, String> {
+    let read = |path: &std::path::Path| {
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+    };
+    log!("Loading FDS BIOS file: {:?}", bios_path);
+    let bios_data = read(bios_path)?;
+    log!("Loading FDS disk file: {:?}", path);
+    let disk_data = read(path)?;
 
     let rom_info = RomInfo {
         name: path.file_name().unwrap().to_string_lossy().to_string(),
-        cksum: rom_data.len() as u32, // @TODO: real checksum
+        cksum: crc32(&disk_data),
+        md5: md5(fds::FdsImageBuilder::without_header(&disk_data)),
     };
-    let cartridge_data = i_nes::INesRomBuilder::build(&rom_data);
+    let cartridge_data = fds::FdsImageBuilder::build(&bios_data, &disk_data)?;
+
+    Ok(Cartridge::new(rom_info, cartridge_data))
+}
+
+// CRC-32 (IEEE), the one used by the ROM databases
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+// MD5 (RFC 1321), the ROM checksum of the FCEUX movies
+pub fn md5(data: &[u8]) -> [u8; 16] {
+    const SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+    let table: Vec<u32> = (0..64)
+        .map(|i| ((i as f64 + 1.0).sin().abs() * 4_294_967_296.0) as u32)
+        .collect();
 
-    Cartridge::new(rom_info, cartridge_data)
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+    let mut state: [u32; 4] = [0x6745_2301, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476];
+    for chunk in message.chunks_exact(64) {
+        let words: Vec<u32> = chunk
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let sum = a
+                .wrapping_add(f)
+                .wrapping_add(table[i])
+                .wrapping_add(words[g]);
+            (a, d, c) = (d, c, b);
+            b = b.wrapping_add(sum.rotate_left(SHIFTS[i / 16 * 4 + i % 4]));
+        }
+        for (value, added) in state.iter_mut().zip([a, b, c, d]) {
+            *value = value.wrapping_add(added);
+        }
+    }
+
+    let mut digest = [0; 16];
+    for (bytes, value) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&value.to_le_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+impl Cartridge {
+    /*
+     * A 32KB NROM cartridge for the tests, filled with NOPs and starting at $8000. The code is a
+     * list of CPU addresses and the bytes placed there, which can overwrite the vectors too.
+     */
+    pub fn with_program(code: &[(u16, &[u8])]) -> Self {
+        let mut prg_data = vec![0xEA; 0x8000];
+        prg_data[0x7FFC..0x7FFE].copy_from_slice(&[0x00, 0x80]);
+        for &(addr, bytes) in code {
+            let start = (addr - 0x8000) as usize;
+            prg_data[start..start + bytes.len()].copy_from_slice(bytes);
+        }
+        Self::new(
+            RomInfo::default(),
+            CartridgeData {
+                prg_banks: 2,
+                prg_data,
+                ..Default::default()
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0x0000_0000);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_md5() {
+        let hex = |digest: [u8; 16]| {
+            digest
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>()
+        };
+        assert_eq!(hex(md5(b"")), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(
+            hex(md5(b"The quick brown fox jumps over the lazy dog")),
+            "9e107d9d372bb6826bd81d3542a419d6"
+        );
+        assert_eq!(hex(md5(&[b'a'; 100])), "36a92cc94a9e0fa21f625f8bfb007adf");
+    }
+
+    fn mk_cartridge(mapper_code: u8, prg_data: Vec<u8>) -> Cartridge {
+        let data = CartridgeData {
+            mapper_code,
+            prg_banks: prg_data.len() / 0x4000,
+            prg_data,
+            ..Default::default()
+        };
+        Cartridge::new(RomInfo::default(), data)
+    }
+
+    #[test]
+    fn test_bus_conflicts() {
+        // GxROM (66) has bus conflicts, so the written value is ANDed with the ROM
+        let mut prg_data = vec![0xFF; 0x10000];
+        prg_data[0x0000] = 0b0000_0000;
+        prg_data[0x0001] = 0b0001_0000;
+        let mut cartridge = mk_cartridge(66, prg_data);
+
+        cartridge.write_prg(0x0000, 0b0001_0000);
+        assert_eq!(cartridge.mapper.as_ref().prg_addr(0x0000), 0x0000);
+
+        cartridge.write_prg(0x0001, 0b0001_0000);
+        assert_eq!(cartridge.mapper.as_ref().prg_addr(0x0000), 0x8000);
+    }
+
+    #[test]
+    fn test_four_screen() {
+        let data = CartridgeData {
+            mapper_code: 4,
+            prg_banks: 2,
+            mirror_mode: MirrorMode::FourScreen,
+            prg_data: vec![0; 0x8000],
+            ..Default::default()
+        };
+        let mut cartridge = Cartridge::new(RomInfo::default(), data);
+        cartridge.write_prg(0x2000, 0); // MMC3 vertical mirroring
+        for table in 0..4 {
+            let nametable = cartridge.nametable(table);
+            assert_eq!(nametable, Nametable::Ram(table as usize));
+            cartridge.write_nametable(nametable, 0x3FF, table as u8 + 1);
+        }
+        for table in 0..4 {
+            let nametable = cartridge.nametable(table);
+            assert_eq!(cartridge.read_nametable(nametable, 0x3FF), table as u8 + 1);
+        }
+    }
+
+    #[test]
+    fn test_cartridge_nametables() {
+        let data = CartridgeData {
+            mapper_code: mappers::tests::NAMETABLE_MAPPER_CODE,
+            prg_banks: 2,
+            chr_banks: 1,
+            prg_data: vec![0; 0x8000],
+            chr_data: (0..0x2000).map(|addr| (addr / 0x400) as u8).collect(),
+            ..Default::default()
+        };
+        let mut cartridge = Cartridge::new(RomInfo::default(), data);
+
+        let chr_rom = cartridge.nametable(0);
+        assert_eq!(chr_rom, Nametable::ChrRom(1));
+        assert_eq!(cartridge.read_nametable(chr_rom, 0x3FF), 0x01);
+        cartridge.write_nametable(chr_rom, 0x3FF, 0x42);
+        assert_eq!(
+            cartridge.read_nametable(chr_rom, 0x3FF),
+            0x01,
+            "CHR ROM is not writable"
+        );
+
+        let exram = cartridge.nametable(1);
+        assert_eq!(exram, Nametable::ExRam);
+        cartridge.write_nametable(exram, 0x7FF, 0x42);
+        assert_eq!(cartridge.read_nametable(exram, 0x3FF), 0x42);
+
+        assert_eq!(cartridge.nametable(2), Nametable::Ciram(0));
+    }
+
+    #[test]
+    fn test_mixed_chr() {
+        // TQROM (119) maps CHR RAM when the bank number has the bit 6 set
+        let data = CartridgeData {
+            mapper_code: 119,
+            prg_banks: 2,
+            chr_banks: 1,
+            prg_data: vec![0; 0x8000],
+            chr_data: vec![0x11; 0x2000],
+            ..Default::default()
+        };
+        let mut cartridge = Cartridge::new(RomInfo::default(), data);
+        cartridge.write_prg(0x0000, 0x02); // R2 => $1000
+        cartridge.write_prg(0x0001, 0x40);
+        cartridge.write_chr(0x1000, 0x42);
+        cartridge.write_chr(0x0000, 0x42);
+        assert_eq!(cartridge.read_chr(0x1000), 0x42);
+        assert_eq!(cartridge.read_chr(0x0000), 0x11, "CHR ROM is not writable");
+    }
+
+    #[test]
+    fn test_txsrom_chr_banks() {
+        // TxSROM (118) uses the bit 7 of the CHR banks for the nametables
+        let data = CartridgeData {
+            mapper_code: 118,
+            prg_banks: 2,
+            chr_banks: 16,
+            prg_data: vec![0; 0x8000],
+            chr_data: (0..0x20000).map(|addr| (addr / 0x400) as u8).collect(),
+            ..Default::default()
+        };
+        let mut cartridge = Cartridge::new(RomInfo::default(), data);
+        cartridge.write_prg(0x0000, 0x00); // R0 => $0000
+        cartridge.write_prg(0x0001, 0x80);
+        assert_eq!(cartridge.read_chr(0x0000), 0x00);
+        assert_eq!(cartridge.nametable(0), Nametable::Ciram(1));
+        cartridge.write_prg(0x0001, 0x82);
+        assert_eq!(cartridge.read_chr(0x0400), 0x03);
+    }
+
+    #[test]
+    fn test_chr_ram_banking() {
+        // CPROM (13) has 16KB of CHR RAM with the upper 4KB window switchable
+        let mut cartridge = mk_cartridge(13, vec![0xFF; 0x8000]);
+        cartridge.write_chr(0x1000, 0x42);
+        cartridge.write_prg(0x0000, 0x01);
+        assert_eq!(cartridge.read_chr(0x1000), 0x00);
+        cartridge.write_chr(0x1000, 0x24);
+        cartridge.write_prg(0x0000, 0x00);
+        assert_eq!(cartridge.read_chr(0x1000), 0x42);
+        assert_eq!(cartridge.read_chr(0x0000), 0x42);
+    }
 }
@@ -0,0 +1,63 @@
+use super::*;
+
+pub const NAMETABLE_SIZE: usize = 0x0400;
+pub const FOUR_SCREEN_RAM_SIZE: usize = NAMETABLE_SIZE * 4;
+
+/*
+ * Where each of the four PPU nametables ($2000, $2400, $2800 and $2C00) is fetched from.
+ * CIRAM is the console's internal 2KB VRAM, the other sources are provided by the cartridge.
+ */
+#[allow(dead_code)] // ChrRom and ExRam are only used by mappers not implemented yet (Sunsoft-4, MMC5...)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Nametable {
+    Ciram(usize),
+    Ram(usize),
+    ChrRom(usize),
+    ExRam,
+}
+
+impl MirrorMode {
+    pub fn nametable(&self, table: u16) -> Nametable {
+        let table = table as usize & 0b11;
+        match self {
+            MirrorMode::Horizontal => Nametable::Ciram(table >> 1),
+            MirrorMode::Vertical => Nametable::Ciram(table & 1),
+            MirrorMode::SingleScreen0 => Nametable::Ciram(0),
+            MirrorMode::SingleScreen1 => Nametable::Ciram(1),
+            MirrorMode::FourScreen => Nametable::Ram(table),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mirroring() {
+        assert_eq!(MirrorMode::Horizontal.nametable(0), Nametable::Ciram(0));
+        assert_eq!(MirrorMode::Horizontal.nametable(1), Nametable::Ciram(0));
+        assert_eq!(MirrorMode::Horizontal.nametable(2), Nametable::Ciram(1));
+        assert_eq!(MirrorMode::Horizontal.nametable(3), Nametable::Ciram(1));
+
+        assert_eq!(MirrorMode::Vertical.nametable(0), Nametable::Ciram(0));
+        assert_eq!(MirrorMode::Vertical.nametable(1), Nametable::Ciram(1));
+        assert_eq!(MirrorMode::Vertical.nametable(2), Nametable::Ciram(0));
+        assert_eq!(MirrorMode::Vertical.nametable(3), Nametable::Ciram(1));
+
+        assert_eq!(MirrorMode::SingleScreen0.nametable(0), Nametable::Ciram(0));
+        assert_eq!(MirrorMode::SingleScreen0.nametable(1), Nametable::Ciram(0));
+        assert_eq!(MirrorMode::SingleScreen0.nametable(2), Nametable::Ciram(0));
+        assert_eq!(MirrorMode::SingleScreen0.nametable(3), Nametable::Ciram(0));
+
+        assert_eq!(MirrorMode::SingleScreen1.nametable(0), Nametable::Ciram(1));
+        assert_eq!(MirrorMode::SingleScreen1.nametable(1), Nametable::Ciram(1));
+        assert_eq!(MirrorMode::SingleScreen1.nametable(2), Nametable::Ciram(1));
+        assert_eq!(MirrorMode::SingleScreen1.nametable(3), Nametable::Ciram(1));
+
+        assert_eq!(MirrorMode::FourScreen.nametable(0), Nametable::Ram(0));
+        assert_eq!(MirrorMode::FourScreen.nametable(1), Nametable::Ram(1));
+        assert_eq!(MirrorMode::FourScreen.nametable(2), Nametable::Ram(2));
+        assert_eq!(MirrorMode::FourScreen.nametable(3), Nametable::Ram(3));
+    }
+}
@@ -3,19 +3,28 @@ use super::*;
 #[derive(Clone)]
 pub struct TimeMachine {
     chr_ram: Vec<u8>,
+    nametable_ram: Vec<u8>,
     mapper: mappers::Mapper,
 }
 
+state!(TimeMachine {
+    chr_ram,
+    nametable_ram,
+    mapper,
+});
+
 impl TimeMachine {
     pub fn save(cartridge: &Cartridge) -> Self {
         Self {
             chr_ram: cartridge.chr_ram.clone(),
+            nametable_ram: cartridge.nametable_ram.clone(),
             mapper: cartridge.mapper.clone(),
         }
     }
 
     pub fn load(self, cartridge: &mut Cartridge) {
         cartridge.chr_ram = self.chr_ram;
+        cartridge.nametable_ram = self.nametable_ram;
         cartridge.mapper = self.mapper;
     }
 }
@@ -0,0 +1,243 @@
+use std::path::Path;
+
+// the value of each letter is its position
+const GAME_GENIE_LETTERS: &[u8; 16] = b"APZLGITYEOXUKSVN";
+
+// replaces a PRG ROM byte, only while it holds `compare` when given
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Patch {
+    pub addr: u16,
+    pub value: u8,
+    pub compare: Option<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    // Game Genie codes
+    Patch(Patch),
+    // Pro Action Replay codes, the RAM byte is written at the start of every vblank
+    Freeze { addr: u16, value: u8 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    pub code: String,
+    pub description: String,
+    pub enabled: bool,
+    pub effect: Effect,
+}
+
+/*
+ * The cheats of a game, as in the .cht files: one code per line, optionally followed by a
+ * description. A line starting with ! is a disabled cheat and one starting with # a comment.
+ * The codes are 6 or 8 Game Genie letters or Pro Action Replay RAM writes (AAAA:VV or AAAAVV).
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheats {
+    pub cheats: Vec<Cheat>,
+    // switches all the cheats at once, without losing which ones are enabled
+    pub enabled: bool,
+}
+
+impl Default for Cheats {
+    fn default() -> Self {
+        Self {
+            cheats: Vec::new(),
+            enabled: true,
+        }
+    }
+}
+
+impl Cheats {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut cheats = Self::default();
+        for (num, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (enabled, line) = match line.strip_prefix('!') {
+                Some(line) => (false, line.trim_start()),
+                None => (true, line),
+            };
+            let (code, description) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            cheats
+                .add(code, description.trim(), enabled)
+                .map_err(|e| format!("line {}: {}", num + 1, e))?;
+        }
+        Ok(cheats)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn add(&mut self, code: &str, description: &str, enabled: bool) -> Result<(), String> {
+        self.cheats.push(Cheat {
+            code: code.to_uppercase(),
+            description: description.to_string(),
+            enabled,
+            effect: decode(code)?,
+        });
+        Ok(())
+    }
+
+    pub fn patches(&self) -> Vec<Patch> {
+        self.active()
+            .filter_map(|effect| match effect {
+                Effect::Patch(patch) => Some(patch),
+                Effect::Freeze { .. } => None,
+            })
+            .collect()
+    }
+
+    pub fn freezes(&self) -> Vec<(u16, u8)> {
+        self.active()
+            .filter_map(|effect| match effect {
+                Effect::Freeze { addr, value } => Some((addr, value)),
+                Effect::Patch(_) => None,
+            })
+            .collect()
+    }
+
+    fn active(&self) -> impl Iterator<Item = Effect> + '_ {
+        self.cheats
+            .iter()
+            .filter(|cheat| self.enabled && cheat.enabled)
+            .map(|cheat| cheat.effect)
+    }
+}
+
+pub fn decode(code: &str) -> Result<Effect, String> {
+    let code = code.trim().to_uppercase();
+    if let Some((addr, value)) = code.split_once(':') {
+        return decode_action_replay(addr, value);
+    }
+    let letters: Option<Vec<u8>> = code
+        .bytes()
+        .map(|letter| {
+            GAME_GENIE_LETTERS
+                .iter()
+                .position(|&l| l == letter)
+                .map(|pos| pos as u8)
+        })
+        .collect();
+    match (letters, code.len()) {
+        (Some(letters), 6 | 8) => Ok(Effect::Patch(decode_game_genie(&letters))),
+        // the length is in bytes, only ASCII codes can be split there
+        (_, 6) if code.is_ascii() => decode_action_replay(&code[..4], &code[4..]),
+        _ => Err(format!("invalid cheat code: {}", code)),
+    }
+}
+
+// https://www.nesdev.org/wiki/Game_Genie
+fn decode_game_genie(n: &[u8]) -> Patch {
+    let addr = 0x8000
+        | (n[3] as u16 & 7) << 12
+        | (n[5] as u16 & 7) << 8
+        | (n[4] as u16 & 8) << 8
+        | (n[2] as u16 & 7) << 4
+        | (n[1] as u16 & 8) << 4
+        | (n[4] as u16 & 7)
+        | (n[3] as u16 & 8);
+    let value = |lo: u8, hi: u8, last: u8| (hi & 7) << 4 | (lo & 8) << 4 | (lo & 7) | (last & 8);
+    match n.len() {
+        8 => Patch {
+            addr,
+            value: value(n[0], n[1], n[7]),
+            compare: Some(value(n[6], n[7], n[5])),
+        },
+        _ => Patch {
+            addr,
+            value: value(n[0], n[1], n[5]),
+            compare: None,
+        },
+    }
+}
+
+fn decode_action_replay(addr: &str, value: &str) -> Result<Effect, String> {
+    let addr = u16::from_str_radix(addr, 16).map_err(|_| format!("invalid address: {}", addr))?;
+    let value = u8::from_str_radix(value, 16).map_err(|_| format!("invalid value: {}", value))?;
+    match addr {
+        // the RAM and the SRAM
+        0x0000..=0x1FFF | 0x6000..=0x7FFF => Ok(Effect::Freeze { addr, value }),
+        _ => Err(format!("not a RAM address: {:04X}", addr)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_game_genie() {
+        assert_eq!(
+            decode("GOSSIP"),
+            Ok(Effect::Patch(Patch {
+                addr: 0xD1DD,
+                value: 0x14,
+                compare: None
+            }))
+        );
+        assert_eq!(
+            decode("zexpygla"),
+            Ok(Effect::Patch(Patch {
+                addr: 0x94A7,
+                value: 0x02,
+                compare: Some(0x03)
+            }))
+        );
+    }
+
+    #[test]
+    fn test_decode_action_replay() {
+        let freeze = Ok(Effect::Freeze {
+            addr: 0x0075,
+            value: 0x09,
+        });
+        assert_eq!(decode("0075:09"), freeze);
+        assert_eq!(decode("007509"), freeze);
+        assert_eq!(
+            decode("6001:FF"),
+            Ok(Effect::Freeze {
+                addr: 0x6001,
+                value: 0xFF
+            })
+        );
+        assert!(decode("8000:01").is_err());
+        assert!(decode("GOSSI").is_err());
+        assert!(decode("00G5:09").is_err());
+        assert!(decode("€€").is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        let text = "\
+# Super Game
+GOSSIP Infinite lives
+!0075:09   Start on world 8
+
+ZEXPYGLA
+";
+        let mut cheats = Cheats::parse(text).unwrap();
+        assert_eq!(cheats.cheats.len(), 3);
+        assert_eq!(cheats.cheats[0].description, "Infinite lives");
+        assert!(!cheats.cheats[1].enabled);
+        assert_eq!(cheats.cheats[1].description, "Start on world 8");
+        assert_eq!(cheats.cheats[2].code, "ZEXPYGLA");
+
+        assert_eq!(cheats.patches().len(), 2);
+        assert!(cheats.freezes().is_empty());
+        cheats.cheats[1].enabled = true;
+        assert_eq!(cheats.freezes(), vec![(0x0075, 0x09)]);
+        cheats.enabled = false;
+        assert!(cheats.patches().is_empty() && cheats.freezes().is_empty());
+
+        assert_eq!(
+            Cheats::parse("GOSSIP\nNOPE").unwrap_err(),
+            "line 2: invalid cheat code: NOPE"
+        );
+    }
+}
@@ -5,6 +5,7 @@ pub mod disasm;
 mod opcodes;
 mod status;
 mod time_machine;
+pub mod trace;
 
 pub use status::Status;
 pub use time_machine::TimeMachine;
@@ -20,20 +21,30 @@ const BREAK_VECTOR: u16 = 0xFFFE;
 pub trait Memory {
     fn read(&self, addr: u16) -> u8;
     fn write(&mut self, addr: u16, val: u8);
+    // memories whose reads have side effects must override it
+    fn peek(&self, addr: u16) -> u8 {
+        self.read(addr)
+    }
 }
 
 impl<M: Memory> disasm::DisasmMemory for M {
     fn read(&self, addr: u16) -> u8 {
-        M::read(self, addr)
+        M::peek(self, addr)
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+// called before each instruction is executed
+pub type TraceHook<M> = Box<dyn FnMut(&Cpu<M>)>;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum Signal {
+    #[default]
     Irq,
     Nmi,
 }
 
+state_enum!(Signal { Irq = 0, Nmi = 1 });
+
 pub struct Cpu<M: Memory> {
     pub mem: M,
 
@@ -47,6 +58,7 @@ pub struct Cpu<M: Memory> {
 
     pub cycle: usize,
     pub busy_cycles: usize,
+    pub trace_hook: Option<TraceHook<M>>,
 }
 
 impl<M: Memory> std::fmt::Debug for Cpu<M> {
@@ -79,6 +91,7 @@ impl<M: Memory> Cpu<M> {
 
             cycle: 0,
             busy_cycles: 0,
+            trace_hook: None,
         }
     }
 
@@ -87,10 +100,25 @@ impl<M: Memory> Cpu<M> {
         disasm::Disasm::new(&self.mem, self.pc).disasm_next()
     }
 
-    pub fn reset(&mut self) {
-        self.detour(RESET_VECTOR);
-        self.sp = INITIAL_SP;
+    // the power-on state is a reset with the registers cleared
+    pub fn power_on(&mut self) {
+        self.a = 0;
+        self.x = 0;
+        self.y = 0;
+        self.sp = 0;
         self.p = (Status::U | Status::I).into();
+        self.reset();
+    }
+
+    // the reset sequence pushes PC and P with the writes disabled, so only SP changes
+    pub fn reset(&mut self) {
+        self.sp = self.sp.wrapping_sub(3);
+        self.p.set(Status::I, true);
+        self.signal = None;
+        self.busy_cycles = 0;
+        let lo = self.mem.read(RESET_VECTOR);
+        let hi = self.mem.read(RESET_VECTOR.wrapping_add(1));
+        self.pc = u16::from_le_bytes([lo, hi]);
     }
 
     pub fn detour(&mut self, vector: u16) {
@@ -120,6 +148,10 @@ impl<M: Memory> Cpu<M> {
                     self.busy_cycles += 7;
                 }
                 None => {
+                    if let Some(mut hook) = self.trace_hook.take() {
+                        hook(self);
+                        self.trace_hook = Some(hook);
+                    }
                     self.run_instruction();
                 }
             }
@@ -136,6 +168,7 @@ impl<M: Memory> Cpu<M> {
 
         self.detour(vector);
         self.push(self.p.raw | Status::B | Status::U);
+        self.p.set(Status::I, true);
     }
 
     fn run_instruction(&mut self) {
@@ -3,6 +3,8 @@ pub struct Status {
     pub raw: u8,
 }
 
+state!(Status { raw });
+
 impl Status {
     pub const C: u8 = 0b0000_0001; // carry
     pub const Z: u8 = 0b0000_0010; // zero
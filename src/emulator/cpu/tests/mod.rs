@@ -58,6 +58,7 @@ mod util {
 mod disasm;
 mod instructions;
 mod nestest;
+mod trace;
 
 use super::{AddressingMode as AM, Instruction as IN, Status as S};
 
@@ -83,10 +84,27 @@ fn test_detour() {
 
 #[test]
 fn test_reset() {
-    let mut cpu = mk_cpu!(mk_io!(0xFFFC: 0xBB, 0xFFFD: 0xAA), pc: 0x8000);
+    let mut cpu =
+        mk_cpu!(mk_io!(0xFFFC: 0xBB, 0xFFFD: 0xAA), pc: 0x8000, sp: 0x40, a: 0x12, p: S::U | S::C);
     cpu.reset();
     assert_eq!(cpu.pc, 0xAABB);
+    assert_eq!(cpu.sp, 0x3D);
+    assert_eq!(cpu.a, 0x12);
+    assert_eq!(cpu.p, (S::U | S::C | S::I).into());
+    // nothing is pushed, the stack still holds its initial values
+    assert_eq!(cpu.mem.read(0x0140), 0x40);
+    assert_eq!(cpu.mem.read(0x013F), 0x3F);
+    assert_eq!(cpu.mem.read(0x013E), 0x3E);
+}
+
+#[test]
+fn test_power_on() {
+    let mut cpu =
+        mk_cpu!(mk_io!(0xFFFC: 0xBB, 0xFFFD: 0xAA), pc: 0x8000, sp: 0x40, a: 0x12, p: S::U | S::C);
+    cpu.power_on();
+    assert_eq!(cpu.pc, 0xAABB);
     assert_eq!(cpu.sp, 0xFD);
+    assert_eq!(cpu.a, 0x00);
     assert_eq!(cpu.p, (S::U | S::I).into());
 }
 
@@ -95,8 +113,8 @@ fn test_irq_signal() {
     let nop = util::opcode_lookup(IN::Nop, AM::Imp);
     let mut cpu = mk_cpu!(mk_io!(0xAABB: nop, 0xFFFE: 0xBB, 0xFFFF: 0xAA), pc: 0x8000, sp: 0x40, p: S::U | S::Z | S::N);
     cpu.set_signal(Signal::Irq);
-    assert_cpu!(cpu, {pc: 0xAABB, sp: 0x3D, p: S::U | S::Z | S::N, cycle: 6});
-    assert_cpu!(cpu, {pc: 0xAABC, sp: 0x3D, p: S::U | S::Z | S::N, cycle: 8});
+    assert_cpu!(cpu, {pc: 0xAABB, sp: 0x3D, p: S::U | S::Z | S::N | S::I, cycle: 6});
+    assert_cpu!(cpu, {pc: 0xAABC, sp: 0x3D, p: S::U | S::Z | S::N | S::I, cycle: 8});
 
     let mut cpu = mk_cpu!(mk_io!(0x8000: nop, 0xFFFE: 0xBB, 0xFFFF: 0xAA), pc: 0x8000, sp: 0x40, p: S::U | S::Z | S::N | S::I);
     cpu.set_signal(Signal::Irq);
@@ -108,8 +126,8 @@ fn test_nmi_signal() {
     let nop = util::opcode_lookup(IN::Nop, AM::Imp);
     let mut cpu = mk_cpu!(mk_io!(0xAABB: nop, 0xFFFA: 0xBB, 0xFFFB: 0xAA), pc: 0x8000, sp: 0x40, p: S::U | S::Z | S::N);
     cpu.set_signal(Signal::Nmi);
-    assert_cpu!(cpu, {pc: 0xAABB, sp: 0x3D, p: S::U | S::Z | S::N, cycle: 7});
-    assert_cpu!(cpu, {pc: 0xAABC, sp: 0x3D, p: S::U | S::Z | S::N, cycle: 9});
+    assert_cpu!(cpu, {pc: 0xAABB, sp: 0x3D, p: S::U | S::Z | S::N | S::I, cycle: 7});
+    assert_cpu!(cpu, {pc: 0xAABC, sp: 0x3D, p: S::U | S::Z | S::N | S::I, cycle: 9});
 
     let mut cpu = mk_cpu!(mk_io!(0xAABB: nop, 0xFFFA: 0xBB, 0xFFFB: 0xAA), pc: 0x8000, sp: 0x40, p: S::U | S::Z | S::N | S::I);
     cpu.set_signal(Signal::Nmi);
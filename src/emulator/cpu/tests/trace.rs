@@ -0,0 +1,51 @@
+use crate::emulator::cpu::trace::nestest_line;
+
+use super::*;
+
+#[test]
+fn test_nestest_line() {
+    let cpu =
+        mk_cpu!(mk_io!(0xC000: 0x4C, 0xC001: 0xF5, 0xC002: 0xC5), pc: 0xC000, p: 0x24, cycle: 7);
+    assert_eq!(
+        nestest_line(&cpu, &cpu.mem, 0, 21),
+        "C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7"
+    );
+
+    let cpu = mk_cpu!(mk_io!(0xC5F7: 0x86, 0xC5F8: 0x00), pc: 0xC5F7, p: 0x26, cycle: 12);
+    assert_eq!(
+        nestest_line(&cpu, &cpu.mem, 0, 36),
+        "C5F7  86 00     STX $00 = 00                    A:00 X:00 Y:00 P:26 SP:FD PPU:  0, 36 CYC:12"
+    );
+
+    let cpu = mk_cpu!(mk_io!(0x8000: 0x4A), pc: 0x8000, a: 0x01, p: 0x24, cycle: 100);
+    assert_eq!(
+        nestest_line(&cpu, &cpu.mem, 241, 340),
+        "8000  4A        LSR A                           A:01 X:00 Y:00 P:24 SP:FD PPU:241,340 CYC:100"
+    );
+}
+
+#[test]
+fn test_nestest_operands() {
+    let asm = |cpu: &Cpu<TestIO>| nestest_line(cpu, &cpu.mem, 0, 0)[16..48].to_string();
+
+    let cpu = mk_cpu!(mk_io!(0x8000: 0xB5, 0x8001: 0xF0, 0x0010: 0x42), pc: 0x8000, x: 0x20);
+    assert_eq!(asm(&cpu).trim_end(), "LDA $F0,X @ 10 = 42");
+
+    let cpu = mk_cpu!(mk_io!(0x8000: 0xB9, 0x8001: 0xFF, 0x8002: 0x02, 0x0300: 0x89), pc: 0x8000, y: 0x01);
+    assert_eq!(asm(&cpu).trim_end(), "LDA $02FF,Y @ 0300 = 89");
+
+    let cpu = mk_cpu!(mk_io!(0x8000: 0xA1, 0x8001: 0x80, 0x0080: 0x00, 0x0081: 0x02, 0x0200: 0x5A), pc: 0x8000);
+    assert_eq!(asm(&cpu).trim_end(), "LDA ($80,X) @ 80 = 0200 = 5A");
+
+    let cpu = mk_cpu!(mk_io!(0x8000: 0xB1, 0x8001: 0x89, 0x0089: 0x00, 0x008A: 0x03, 0x0301: 0x11), pc: 0x8000, y: 0x01);
+    assert_eq!(asm(&cpu).trim_end(), "LDA ($89),Y = 0300 @ 0301 = 11");
+
+    let cpu = mk_cpu!(mk_io!(0x8000: 0x6C, 0x8001: 0xFF, 0x8002: 0x02, 0x02FF: 0x7E, 0x0200: 0xDB), pc: 0x8000);
+    assert_eq!(asm(&cpu).trim_end(), "JMP ($02FF) = DB7E");
+
+    let cpu = mk_cpu!(mk_io!(0x8000: 0xD0, 0x8001: 0xFC), pc: 0x8000);
+    assert_eq!(asm(&cpu).trim_end(), "BNE $7FFE");
+
+    let cpu = mk_cpu!(mk_io!(0x8000: 0xAD, 0x8001: 0x78, 0x8002: 0x06), pc: 0x8000);
+    assert_eq!(asm(&cpu).trim_end(), "LDA $0678 = 00");
+}
@@ -14,6 +14,18 @@ pub struct TimeMachine {
     busy_cycles: usize,
 }
 
+state!(TimeMachine {
+    a,
+    x,
+    y,
+    pc,
+    sp,
+    p,
+    signal,
+    cycle,
+    busy_cycles,
+});
+
 impl TimeMachine {
     pub fn save<M: Memory>(cpu: &Cpu<M>) -> Self {
         Self {
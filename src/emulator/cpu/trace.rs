@@ -0,0 +1,147 @@
+use super::disasm::DisasmMemory;
+use super::opcodes::{AddressingMode, Instruction, OPCODES};
+use super::*;
+
+/*
+ * Formats the instruction at PC as a line of the nestest.log:
+ * C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+ * The operands are annotated with the effective address and the value in memory, so the memory
+ * is read through DisasmMemory.
+ */
+pub fn nestest_line<M: Memory>(
+    cpu: &Cpu<M>,
+    mem: &dyn DisasmMemory,
+    scanline: usize,
+    dot: usize,
+) -> String {
+    let opcode = mem.read(cpu.pc);
+    let (instruction, addr_mode) = OPCODES[opcode as usize];
+    let mut raw_data = vec![opcode];
+    for i in 0..addr_mode.len() {
+        raw_data.push(mem.read(cpu.pc.wrapping_add(1 + i as u16)));
+    }
+
+    let bytes = raw_data
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mnemonic = format!("{:?}", instruction).to_uppercase();
+    let asm = format!(
+        "{} {}",
+        mnemonic,
+        operand(cpu, mem, instruction, addr_mode, &raw_data)
+    );
+
+    format!(
+        "{:04X}  {:<9} {:<32}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+        cpu.pc,
+        bytes,
+        asm.trim_end(),
+        cpu.a,
+        cpu.x,
+        cpu.y,
+        cpu.p.raw,
+        cpu.sp,
+        scanline,
+        dot,
+        cpu.cycle
+    )
+}
+
+fn operand<M: Memory>(
+    cpu: &Cpu<M>,
+    mem: &dyn DisasmMemory,
+    instruction: Instruction,
+    addr_mode: AddressingMode,
+    raw_data: &[u8],
+) -> String {
+    let read_word_zp = |addr: u8| {
+        u16::from_le_bytes([mem.read(addr as u16), mem.read(addr.wrapping_add(1) as u16)])
+    };
+
+    match addr_mode {
+        AddressingMode::Imp => match instruction {
+            Instruction::Asl | Instruction::Lsr | Instruction::Rol | Instruction::Ror => {
+                "A".to_string()
+            }
+            _ => String::new(),
+        },
+        AddressingMode::Imm => format!("#${:02X}", raw_data[1]),
+        AddressingMode::Zp0 => {
+            let addr = raw_data[1] as u16;
+            format!("${:02X} = {:02X}", addr, mem.read(addr))
+        }
+        AddressingMode::Zpx | AddressingMode::Zpy => {
+            let (reg, name) = if addr_mode == AddressingMode::Zpx {
+                (cpu.x, "X")
+            } else {
+                (cpu.y, "Y")
+            };
+            let addr = raw_data[1].wrapping_add(reg) as u16;
+            format!(
+                "${:02X},{} @ {:02X} = {:02X}",
+                raw_data[1],
+                name,
+                addr,
+                mem.read(addr)
+            )
+        }
+        AddressingMode::Rel => {
+            let next = cpu.pc.wrapping_add(2);
+            format!("${:04X}", next.wrapping_add(raw_data[1] as i8 as u16))
+        }
+        AddressingMode::Abs => {
+            let addr = u16::from_le_bytes([raw_data[1], raw_data[2]]);
+            match instruction {
+                Instruction::Jmp | Instruction::Jsr => format!("${:04X}", addr),
+                _ => format!("${:04X} = {:02X}", addr, mem.read(addr)),
+            }
+        }
+        AddressingMode::Abx | AddressingMode::Aby => {
+            let (reg, name) = if addr_mode == AddressingMode::Abx {
+                (cpu.x, "X")
+            } else {
+                (cpu.y, "Y")
+            };
+            let base = u16::from_le_bytes([raw_data[1], raw_data[2]]);
+            let addr = base.wrapping_add(reg as u16);
+            format!(
+                "${:04X},{} @ {:04X} = {:02X}",
+                base,
+                name,
+                addr,
+                mem.read(addr)
+            )
+        }
+        AddressingMode::Ind => {
+            let ptr = u16::from_le_bytes([raw_data[1], raw_data[2]]);
+            // the high byte doesn't cross the page (6502 bug)
+            let hi_ptr = (ptr & 0xFF00) | (ptr.wrapping_add(1) & 0x00FF);
+            let addr = u16::from_le_bytes([mem.read(ptr), mem.read(hi_ptr)]);
+            format!("(${:04X}) = {:04X}", ptr, addr)
+        }
+        AddressingMode::Izx => {
+            let ptr = raw_data[1].wrapping_add(cpu.x);
+            let addr = read_word_zp(ptr);
+            format!(
+                "(${:02X},X) @ {:02X} = {:04X} = {:02X}",
+                raw_data[1],
+                ptr,
+                addr,
+                mem.read(addr)
+            )
+        }
+        AddressingMode::Izy => {
+            let base = read_word_zp(raw_data[1]);
+            let addr = base.wrapping_add(cpu.y as u16);
+            format!(
+                "(${:02X}),Y = {:04X} @ {:04X} = {:02X}",
+                raw_data[1],
+                base,
+                addr,
+                mem.read(addr)
+            )
+        }
+    }
+}
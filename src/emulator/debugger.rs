@@ -0,0 +1,378 @@
+use super::*;
+
+pub use bus::Access;
+
+use std::collections::BTreeSet;
+
+const OPCODE_JSR: u8 = 0x20;
+const OPCODE_RTS: u8 = 0x60;
+const OPCODE_RTI: u8 = 0x40;
+const JSR_SIZE: u16 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Registers {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub p: u8,
+    pub sp: u8,
+    pub pc: u16,
+}
+
+impl std::fmt::Display for Registers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "A:{:02X} X:{:02X} Y:{:02X} P:{:?} SP:{:02X} PC:{:04X}",
+            self.a,
+            self.x,
+            self.y,
+            cpu::Status::from(self.p),
+            self.sp,
+            self.pc
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Run {
+    Continue,
+    Step,
+    // runs a JSR until it returns, any other instruction is a regular step
+    StepOver,
+    // runs until a RTS/RTI leaves the current subroutine
+    StepOut,
+    Scanline(usize),
+    Frame,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Step,
+    Breakpoint(u16),
+    Watchpoint(Access, u16),
+    Irq,
+    Nmi,
+    Scanline(usize),
+    Frame(usize),
+}
+
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+    pub break_on_irq: bool,
+    pub break_on_nmi: bool,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_breakpoint(&mut self, addr: u16, enabled: bool) {
+        if enabled {
+            self.breakpoints.insert(addr);
+        } else {
+            self.breakpoints.remove(&addr);
+        }
+    }
+
+    pub fn breakpoints(&self) -> Vec<u16> {
+        self.breakpoints.iter().copied().collect()
+    }
+
+    /*
+     * The stops are checked only between instructions. When the execution starts in one of
+     * them it is not checked, so continuing from a breakpoint doesn't stop on it again.
+     * The watchpoints stop right after the instruction that triggered them.
+     */
+    pub fn run(&mut self, emu: &mut Emulator, run: Run) -> Stop {
+        emu.cpu.mem.watchpoints.take_hit();
+
+        let start = emu.registers();
+        let start_frame = emu.frame();
+        let mut checking = !emu.is_instruction_boundary();
+        let mut last_opcode = None;
+        let mut left_scanline = false;
+        loop {
+            if let Run::Scanline(scanline) = run {
+                left_scanline |= emu.scanline() != scanline;
+            }
+
+            if emu.is_instruction_boundary() {
+                if checking {
+                    if let Some(stop) = self.check(emu, run, start, start_frame, last_opcode) {
+                        return stop;
+                    }
+                    if let Run::Scanline(scanline) = run {
+                        if left_scanline && emu.scanline() == scanline {
+                            return Stop::Scanline(scanline);
+                        }
+                    }
+                }
+                checking = true;
+                // the next clock might handle an interrupt instead of running an instruction
+                last_opcode = match emu.cpu.signal {
+                    Some(_) => None,
+                    None => Some(emu.peek_memory(emu.cpu.pc)),
+                };
+            }
+
+            emu.clock();
+            if let Some((access, addr)) = emu.cpu.mem.watchpoints.take_hit() {
+                return Stop::Watchpoint(access, addr);
+            }
+        }
+    }
+
+    fn check(
+        &self,
+        emu: &Emulator,
+        run: Run,
+        start: Registers,
+        start_frame: usize,
+        last_opcode: Option<u8>,
+    ) -> Option<Stop> {
+        let pc = emu.cpu.pc;
+        if self.breakpoints.contains(&pc) {
+            return Some(Stop::Breakpoint(pc));
+        }
+
+        match emu.cpu.signal {
+            Some(cpu::Signal::Nmi) if self.break_on_nmi => return Some(Stop::Nmi),
+            Some(cpu::Signal::Irq) if self.break_on_irq => return Some(Stop::Irq),
+            _ => (),
+        }
+
+        let stop = match run {
+            Run::Step => true,
+            Run::StepOver if emu.peek_memory(start.pc) == OPCODE_JSR => {
+                pc == start.pc.wrapping_add(JSR_SIZE) && emu.cpu.sp == start.sp
+            }
+            Run::StepOver => true,
+            Run::StepOut => {
+                matches!(last_opcode, Some(OPCODE_RTS | OPCODE_RTI)) && emu.cpu.sp > start.sp
+            }
+            Run::Frame if emu.frame() > start_frame => {
+                return Some(Stop::Frame(emu.frame()));
+            }
+            Run::Continue | Run::Scanline(_) | Run::Frame => false,
+        };
+        stop.then_some(Stop::Step)
+    }
+}
+
+pub(super) struct BusMemory<'a>(pub(super) &'a bus::Bus);
+
+impl cpu::disasm::DisasmMemory for BusMemory<'_> {
+    fn read(&self, addr: u16) -> u8 {
+        self.0.peek(addr)
+    }
+}
+
+impl Emulator {
+    pub fn registers(&self) -> Registers {
+        Registers {
+            a: self.cpu.a,
+            x: self.cpu.x,
+            y: self.cpu.y,
+            p: self.cpu.p.raw,
+            sp: self.cpu.sp,
+            pc: self.cpu.pc,
+        }
+    }
+
+    pub fn set_registers(&mut self, regs: Registers) {
+        self.cpu.a = regs.a;
+        self.cpu.x = regs.x;
+        self.cpu.y = regs.y;
+        self.cpu.p = regs.p.into();
+        self.cpu.sp = regs.sp;
+        self.cpu.pc = regs.pc;
+    }
+
+    // reads the memory as the CPU sees it, without the side effects of a read
+    pub fn peek_memory(&self, addr: u16) -> u8 {
+        self.cpu.mem.peek(addr)
+    }
+
+    pub fn write_memory(&mut self, addr: u16, val: u8) {
+        self.cpu.mem.write(addr, val);
+    }
+
+    // returns the instruction at addr and the address of the next one
+    pub fn disasm(&self, addr: u16) -> (String, u16) {
+        let mem = BusMemory(&self.cpu.mem);
+        let mut disasm = cpu::disasm::Disasm::new(&mem, addr);
+        let text = disasm.disasm_next();
+        (text, disasm.current_addr())
+    }
+
+    pub fn set_watchpoint(&mut self, access: Access, addr: u16, enabled: bool) {
+        self.cpu.mem.watchpoints.set(access, addr, enabled);
+    }
+
+    pub fn watchpoints(&self, access: Access) -> Vec<u16> {
+        self.cpu.mem.watchpoints.list(access)
+    }
+
+    // every watchpoint hit since the last call, for the ones that don't stop the execution
+    pub fn take_watchpoint_hits(&mut self) -> Vec<(Access, u16)> {
+        self.cpu.mem.watchpoints.take_hits()
+    }
+
+    // the address of the instruction the next clock starts, if it isn't handling an interrupt
+    pub fn next_instruction(&self) -> Option<u16> {
+        (self.is_instruction_boundary() && self.cpu.signal.is_none()).then_some(self.cpu.pc)
+    }
+
+    pub fn cpu_cycle(&self) -> usize {
+        self.cpu.cycle
+    }
+
+    pub fn scanline(&self) -> usize {
+        self.ppu.as_ref().scanline
+    }
+
+    pub fn dot(&self) -> usize {
+        self.ppu.as_ref().dot
+    }
+
+    pub fn frame(&self) -> usize {
+        self.ppu.as_ref().frame
+    }
+
+    // true when the next CPU clock starts a new instruction (or handles an interrupt)
+    fn is_instruction_boundary(&self) -> bool {
+        self.cycle.is_multiple_of(12)
+            && self.cpu.busy_cycles == 0
+            && !self.oam_dma.is_active()
+            && !self.dmc_dma.is_active()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /*
+     * $8000 LDA #$01
+     * $8002 JSR $8010
+     * $8005 STA $0200
+     * $8008 JMP $8008
+     * $8010 LDX #$02
+     * $8012 INX
+     * $8013 RTS
+     */
+    fn mk_emulator() -> Emulator {
+        Emulator::new(cartridge::Cartridge::with_program(&[
+            (
+                0x8000,
+                &[
+                    0xA9, 0x01, 0x20, 0x10, 0x80, 0x8D, 0x00, 0x02, 0x4C, 0x08, 0x80,
+                ],
+            ),
+            (0x8010, &[0xA2, 0x02, 0xE8, 0x60]),
+            (0xFFFA, &[0x08, 0x80, 0x00, 0x80, 0x08, 0x80]),
+        ]))
+    }
+
+    #[test]
+    fn test_step() {
+        let mut emu = mk_emulator();
+        let mut debugger = Debugger::new();
+        assert_eq!(debugger.run(&mut emu, Run::Step), Stop::Step);
+        assert_eq!(emu.registers().pc, 0x8002);
+        assert_eq!(emu.registers().a, 0x01);
+        assert_eq!(debugger.run(&mut emu, Run::Step), Stop::Step);
+        assert_eq!(emu.registers().pc, 0x8010);
+        assert_eq!(debugger.run(&mut emu, Run::StepOut), Stop::Step);
+        assert_eq!(emu.registers().pc, 0x8005);
+        assert_eq!(emu.registers().x, 0x03);
+    }
+
+    #[test]
+    fn test_step_over() {
+        let mut emu = mk_emulator();
+        let mut debugger = Debugger::new();
+        debugger.run(&mut emu, Run::StepOver);
+        assert_eq!(emu.registers().pc, 0x8002);
+        debugger.run(&mut emu, Run::StepOver);
+        assert_eq!(emu.registers().pc, 0x8005);
+        assert_eq!(emu.registers().x, 0x03);
+    }
+
+    #[test]
+    fn test_breakpoint() {
+        let mut emu = mk_emulator();
+        let mut debugger = Debugger::new();
+        debugger.set_breakpoint(0x8012, true);
+        assert_eq!(
+            debugger.run(&mut emu, Run::Continue),
+            Stop::Breakpoint(0x8012)
+        );
+        assert_eq!(emu.registers().x, 0x02);
+        assert_eq!(debugger.run(&mut emu, Run::Step), Stop::Step);
+        assert_eq!(emu.registers().pc, 0x8013);
+    }
+
+    #[test]
+    fn test_watchpoint() {
+        let mut emu = mk_emulator();
+        let mut debugger = Debugger::new();
+        emu.set_watchpoint(Access::Write, 0x0200, true);
+        let stop = debugger.run(&mut emu, Run::Continue);
+        assert_eq!(stop, Stop::Watchpoint(Access::Write, 0x0200));
+        assert_eq!(emu.peek_memory(0x0200), 0x01);
+        assert_eq!(debugger.run(&mut emu, Run::Step), Stop::Step);
+        assert_eq!(emu.registers().pc, 0x8008);
+    }
+
+    #[test]
+    fn test_nmi() {
+        let mut emu = mk_emulator();
+        let mut debugger = Debugger::new();
+        debugger.break_on_nmi = true;
+        emu.ppu.as_mut().io_ports().write(0x00, 0x80); // enable NMI
+        assert_eq!(debugger.run(&mut emu, Run::Continue), Stop::Nmi);
+        assert_eq!(emu.scanline(), 241);
+    }
+
+    #[test]
+    fn test_peek_memory() {
+        let mut emu = mk_emulator();
+        let mut debugger = Debugger::new();
+        debugger.run(&mut emu, Run::Scanline(242));
+        assert_eq!(emu.peek_memory(0x2002) & 0x80, 0x80);
+        assert_eq!(emu.peek_memory(0x2002) & 0x80, 0x80);
+        assert_eq!(emu.cpu.mem.read(0x2002) & 0x80, 0x80);
+        assert_eq!(emu.peek_memory(0x2002) & 0x80, 0x00);
+    }
+
+    #[test]
+    fn test_run_to_scanline() {
+        let mut emu = mk_emulator();
+        let mut debugger = Debugger::new();
+        let stop = debugger.run(&mut emu, Run::Scanline(100));
+        assert_eq!(stop, Stop::Scanline(100));
+        assert_eq!(emu.scanline(), 100);
+        debugger.run(&mut emu, Run::Frame);
+        assert_eq!(emu.frame(), 1);
+    }
+
+    #[test]
+    fn test_set_registers() {
+        let mut emu = mk_emulator();
+        let mut regs = emu.registers();
+        regs.pc = 0x8010;
+        regs.x = 0x10;
+        emu.set_registers(regs);
+        Debugger::new().run(&mut emu, Run::Step);
+        Debugger::new().run(&mut emu, Run::Step);
+        assert_eq!(emu.registers().x, 0x03);
+        assert_eq!(
+            emu.disasm(0x8010),
+            ("8010 LDX #$02     [A2, 02]".to_string(), 0x8012)
+        );
+    }
+}
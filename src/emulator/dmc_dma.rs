@@ -8,6 +8,8 @@ enum DmaState {
     Running,
 }
 
+state_enum!(DmaState { Idle = 0, Ready = 1, Aligning = 2, Running = 3 });
+
 #[derive(Clone)]
 pub struct DmcDma {
     state: DmaState,
@@ -15,6 +17,12 @@ pub struct DmcDma {
     pub buffer: u8,
 }
 
+state!(DmcDma {
+    state,
+    address,
+    buffer,
+});
+
 impl DmcDma {
     pub fn new() -> Self {
         Self {
@@ -58,13 +58,20 @@ impl StandardJoypad {
     }
 
     pub fn serial_read(&mut self) -> u8 {
+        let state = self.peek();
+        if !self.strobe {
+            self.buffer = self.buffer.rotate_right(1);
+        }
+        state
+    }
+
+    // the value the next serial_read returns
+    pub fn peek(&self) -> u8 {
         if self.strobe {
             // while strobe is high, return the current state of the A button
             self.state.a as u8
         } else {
-            let state = self.buffer & 0x01;
-            self.buffer = self.buffer.rotate_right(1);
-            state
+            self.buffer & 0x01
         }
     }
 
@@ -110,4 +117,16 @@ mod tests {
         assert_eq!(joy.serial_read(), 0, "read Left");
         assert_eq!(joy.serial_read(), 1, "read Right");
     }
+
+    #[test]
+    fn test_peek() {
+        let mut joy = mk_joy!(b);
+        joy.set_strobe(true);
+        joy.set_strobe(false);
+        assert_eq!(joy.peek(), 0, "peek A");
+        assert_eq!(joy.peek(), 0, "peek doesn't shift");
+        assert_eq!(joy.serial_read(), 0, "read A");
+        assert_eq!(joy.peek(), 1, "peek B");
+        assert_eq!(joy.serial_read(), 1, "read B");
+    }
 }
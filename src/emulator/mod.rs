@@ -1,3 +1,6 @@
+#[macro_use]
+mod state;
+
 #[cfg(test)]
 mod tests;
 
@@ -12,11 +15,19 @@ mod time_machine;
 mod video;
 
 pub mod cartridge;
+pub mod cheats;
+pub mod debugger;
+pub mod tracer;
+pub mod viewer;
 pub use audio::Signal as AudioSignal;
 pub use bus::InputPort;
+pub use ppu::{OamEntry, LINES_PER_FRAME};
 pub use time_machine::TimeMachine;
-pub use video::{Color, Signal as VideoSignal};
+pub use video::{Color, Image, SCREEN_HEIGHT, SCREEN_WIDTH};
 pub mod input_devices;
+pub mod ram_pattern;
+pub mod ram_search;
+pub use ram_pattern::RamPattern;
 
 use std::{cell::RefCell, rc::Rc};
 
@@ -32,16 +43,26 @@ pub struct Emulator {
     cartridge: Rc<RefCell<cartridge::Cartridge>>,
 
     color_palette: [video::Color; 64],
+    framebuffer: Box<[u16; SCREEN_WIDTH * SCREEN_HEIGHT]>,
+    ram_pattern: RamPattern,
+    cheats: cheats::Cheats,
+    // the frames in which the game didn't read the joypads
+    lag_frames: usize,
+    lagged: bool,
     pub cycle: usize,
 }
 
 impl Emulator {
+    #[cfg(test)]
     pub fn new(cartridge: cartridge::Cartridge) -> Self {
+        Self::with_ram_pattern(cartridge, RamPattern::default())
+    }
+
+    // the pattern also fills the SRAM, a battery save must be loaded over it
+    pub fn with_ram_pattern(cartridge: cartridge::Cartridge, ram_pattern: RamPattern) -> Self {
         let cartridge = Rc::new(RefCell::new(cartridge));
 
-        let ppu_cartridge = PpuCartridge(cartridge.clone());
-        let ppu_bus = ppu::bus::Bus::new(Box::new(ppu_cartridge));
-        let ppu = Rc::new(RefCell::new(ppu::Ppu::new(ppu_bus)));
+        let ppu = Rc::new(RefCell::new(Self::build_ppu(&cartridge)));
 
         let apu = Rc::new(RefCell::new(apu::Apu::new()));
 
@@ -54,11 +75,9 @@ impl Emulator {
             Box::new(apu_regs),
         );
 
-        let mut cpu = cpu::Cpu::new(bus);
-
-        cpu.reset();
+        let cpu = cpu::Cpu::new(bus);
 
-        Self {
+        let mut emulator = Self {
             cpu,
             ppu: PpuWrapper(ppu),
             apu: ApuWrapper(apu),
@@ -67,10 +86,85 @@ impl Emulator {
             cartridge,
 
             color_palette: video::DEFAULT_PALETTE,
+            framebuffer: Box::new([0; SCREEN_WIDTH * SCREEN_HEIGHT]),
+            ram_pattern,
+            cheats: cheats::Cheats::default(),
+            lag_frames: 0,
+            lagged: false,
             cycle: 0,
+        };
+        emulator.fill_ram();
+        emulator.cpu.mem.fill_sram(ram_pattern);
+        emulator.cpu.power_on();
+        emulator
+    }
+
+    pub fn ram_pattern(&self) -> RamPattern {
+        self.ram_pattern
+    }
+
+    pub fn lag_frames(&self) -> usize {
+        self.lag_frames
+    }
+
+    // the last frame was a lag frame
+    pub fn lagged(&self) -> bool {
+        self.lagged
+    }
+
+    pub fn cheats(&self) -> &cheats::Cheats {
+        &self.cheats
+    }
+
+    pub fn set_cheats(&mut self, cheats: cheats::Cheats) {
+        self.cartridge
+            .borrow_mut()
+            .set_prg_patches(cheats.patches());
+        self.cheats = cheats;
+    }
+
+    fn apply_ram_freezes(&mut self) {
+        for (addr, value) in self.cheats.freezes() {
+            self.cpu.mem.write(addr, value);
         }
     }
 
+    // every volatile RAM, the SRAM is battery backed
+    fn fill_ram(&mut self) {
+        self.cpu.mem.fill_ram(self.ram_pattern);
+        self.ppu.as_mut().mem.fill_ram(self.ram_pattern);
+        self.cartridge.borrow_mut().fill_ram(self.ram_pattern);
+    }
+
+    fn build_ppu(cartridge: &Rc<RefCell<cartridge::Cartridge>>) -> Ppu {
+        let ppu_cartridge = PpuCartridge(cartridge.clone());
+        ppu::Ppu::new(ppu::bus::Bus::new(Box::new(ppu_cartridge)))
+    }
+
+    // the Reset button: the RAM and the cartridge are untouched, only the CPU restarts
+    pub fn reset(&mut self) {
+        self.cpu.reset();
+        self.ppu.as_mut().reset();
+        self.apu.as_mut().reset();
+        self.oam_dma = oam_dma::OamDma::new();
+        self.dmc_dma = dmc_dma::DmcDma::new();
+    }
+
+    /*
+     * Turns the console off and on, everything but the cartridge (and its battery backed RAM)
+     * starts over. The joypads, the tracer and the watchpoints stay connected.
+     */
+    pub fn power_cycle(&mut self) {
+        *self.ppu.as_mut() = Self::build_ppu(&self.cartridge);
+        *self.apu.as_mut() = apu::Apu::new();
+        self.cpu.mem.power_cycle();
+        self.fill_ram();
+        self.cpu.power_on();
+        self.oam_dma = oam_dma::OamDma::new();
+        self.dmc_dma = dmc_dma::DmcDma::new();
+        self.framebuffer.fill(0);
+    }
+
     pub fn connect_port1(&mut self, port: Option<Box<dyn InputPort>>) {
         self.cpu.mem.port1 = port;
     }
@@ -83,6 +177,22 @@ impl Emulator {
         self.cartridge.borrow().rom_info().clone()
     }
 
+    pub fn insert_disk(&mut self, side: usize) {
+        self.cartridge.borrow_mut().insert_disk(side);
+    }
+
+    pub fn eject_disk(&mut self) {
+        self.cartridge.borrow_mut().eject_disk();
+    }
+
+    pub fn switch_disk_side(&mut self) {
+        self.cartridge.borrow_mut().switch_disk_side();
+    }
+
+    pub fn disk_side(&self) -> Option<usize> {
+        self.cartridge.borrow().disk_side()
+    }
+
     pub fn save_state(&self) -> TimeMachine {
         TimeMachine::save(self)
     }
@@ -91,12 +201,45 @@ impl Emulator {
         state.load(self);
     }
 
-    pub fn video_signal(&self) -> video::Signal {
-        let ppu = self.ppu.as_ref();
-        video::Signal {
-            x: ppu.dot.wrapping_sub(1),
-            y: ppu.scanline,
-            color: self.color_palette[ppu.color_idx],
+    #[cfg(test)]
+    pub fn state_data(&self) -> Vec<u8> {
+        state::write(&self.save_state())
+    }
+
+    // a state saved with the same ROM, the cheats are kept
+    pub fn load_state_data(&mut self, data: &[u8]) -> Result<(), String> {
+        let mut state = self.save_state();
+        state::read(&mut state, data)?;
+        self.load_state(state);
+        Ok(())
+    }
+
+    /*
+     * Runs until the PPU starts the next frame, so the framebuffer holds a complete picture. The
+     * clock function is called in place of Emulator::clock, which it must call once, so it can
+     * wrap every cycle, for example to sample the audio.
+     */
+    pub fn run_frame(&mut self, mut clock: impl FnMut(&mut Self)) {
+        let frame = self.ppu.as_ref().frame;
+        while self.ppu.as_ref().frame == frame {
+            clock(self);
+        }
+    }
+
+    // the palette index in the bits 0-5 and the color emphasis in the bits 6-8, row by row
+    pub fn framebuffer(&self) -> &[u16] {
+        self.framebuffer.as_slice()
+    }
+
+    pub fn framebuffer_image(&self) -> Image {
+        Image {
+            width: SCREEN_WIDTH,
+            height: SCREEN_HEIGHT,
+            pixels: self
+                .framebuffer
+                .iter()
+                .map(|&pixel| Color::from_pixel(&self.color_palette, pixel))
+                .collect(),
         }
     }
 
@@ -108,6 +251,7 @@ impl Emulator {
             triangle: apu.triangle.output(),
             noise: apu.noise.output(),
             dmc: apu.dmc.output(),
+            expansion: self.cartridge.borrow().audio_output(),
         }
     }
 
@@ -127,16 +271,32 @@ impl Emulator {
 
             self.apu.as_mut().clock_timer();
             self.check_dmc_dma();
+            self.cartridge.borrow_mut().clock();
         }
 
         // ~53.69mhz
         if self.cycle % 4 == 0 {
+            let vblank_started;
+            let frame_started;
             {
                 let mut ppu = self.ppu.as_mut();
+                let frame = ppu.frame;
                 ppu.clock();
+                frame_started = ppu.frame != frame;
+                if (1..=SCREEN_WIDTH).contains(&ppu.dot) && ppu.scanline < SCREEN_HEIGHT {
+                    self.framebuffer[ppu.scanline * SCREEN_WIDTH + ppu.dot - 1] = ppu.output();
+                }
                 if ppu.take_nmi() {
                     self.cpu.set_signal(cpu::Signal::Nmi);
                 }
+                vblank_started = ppu.scanline == 241 && ppu.dot == 1;
+            }
+            if vblank_started {
+                self.apply_ram_freezes();
+            }
+            if frame_started {
+                self.lagged = !self.cpu.mem.take_input_polled();
+                self.lag_frames += self.lagged as usize;
             }
             if self.cartridge.borrow_mut().take_irq() {
                 self.cpu.set_signal(cpu::Signal::Irq);
@@ -206,6 +366,10 @@ impl bus::Addressable for ApuWrapper {
         self.0.borrow_mut().read(addr)
     }
 
+    fn peek(&self, addr: u16) -> u8 {
+        self.0.borrow().read(addr)
+    }
+
     fn write(&mut self, addr: u16, val: u8) {
         self.0.borrow_mut().write(addr, val);
     }
@@ -228,6 +392,10 @@ impl bus::Addressable for PpuWrapper {
         self.0.borrow_mut().io_ports().read(addr)
     }
 
+    fn peek(&self, addr: u16) -> u8 {
+        self.0.borrow().peek_io_port(addr)
+    }
+
     fn write(&mut self, addr: u16, val: u8) {
         self.0.borrow_mut().io_ports().write(addr, val);
     }
@@ -239,12 +407,24 @@ impl ppu::bus::CartridgeIO for PpuCartridge {
         self.0.borrow().read_chr(addr)
     }
 
+    fn peek(&self, addr: u16) -> u8 {
+        self.0.borrow().peek_chr(addr)
+    }
+
     fn write(&self, addr: u16, val: u8) {
         self.0.borrow_mut().write_chr(addr, val)
     }
 
-    fn mirror_mode(&self) -> cartridge::MirrorMode {
-        self.0.borrow().mirror_mode()
+    fn nametable(&self, table: u16) -> cartridge::Nametable {
+        self.0.borrow().nametable(table)
+    }
+
+    fn read_nametable(&self, nametable: cartridge::Nametable, addr: u16) -> u8 {
+        self.0.borrow().read_nametable(nametable, addr)
+    }
+
+    fn write_nametable(&self, nametable: cartridge::Nametable, addr: u16, val: u8) {
+        self.0.borrow_mut().write_nametable(nametable, addr, val)
     }
 }
 
@@ -254,7 +434,33 @@ impl bus::Addressable for CpuCartridge {
         self.0.borrow().read_prg(addr)
     }
 
+    fn peek(&self, addr: u16) -> u8 {
+        self.0.borrow().read_prg(addr)
+    }
+
     fn write(&mut self, addr: u16, val: u8) {
         self.0.borrow_mut().write_prg(addr, val);
     }
 }
+
+impl bus::CartridgeIO for CpuCartridge {
+    fn read_sram(&self, addr: u16) -> Option<u8> {
+        self.0.borrow().read_sram(addr)
+    }
+
+    fn write_sram(&mut self, addr: u16, val: u8) {
+        self.0.borrow_mut().write_sram(addr, val);
+    }
+
+    fn read_expansion(&self, addr: u16) -> Option<u8> {
+        self.0.borrow().read_expansion(addr)
+    }
+
+    fn peek_expansion(&self, addr: u16) -> Option<u8> {
+        self.0.borrow().peek_expansion(addr)
+    }
+
+    fn write_expansion(&mut self, addr: u16, val: u8) {
+        self.0.borrow_mut().write_expansion(addr, val);
+    }
+}
@@ -11,6 +11,8 @@ enum DmaState {
     Running,
 }
 
+state_enum!(DmaState { Idle = 0, Ready = 1, Aligning = 2, Running = 3 });
+
 #[derive(Clone)]
 pub struct OamDma {
     page: u8,
@@ -19,6 +21,13 @@ pub struct OamDma {
     state: DmaState,
 }
 
+state!(OamDma {
+    page,
+    index,
+    buffer,
+    state,
+});
+
 impl OamDma {
     pub fn new() -> Self {
         Self {
@@ -13,6 +13,17 @@ pub struct Background {
     pub tmp_pattern_lo: u8,
 }
 
+state!(Background {
+    pattern_hi,
+    pattern_lo,
+    palette_hi,
+    palette_lo,
+    tmp_tile_idx,
+    tmp_palette,
+    tmp_pattern_hi,
+    tmp_pattern_lo,
+});
+
 impl std::fmt::Debug for Background {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if f.alternate() {
@@ -1,7 +1,10 @@
-use crate::emulator::cartridge::MirrorMode;
+use crate::emulator::cartridge::Nametable;
 
 pub trait CartridgeIO {
     fn read(&self, addr: u16) -> u8;
+    fn peek(&self, addr: u16) -> u8;
     fn write(&self, addr: u16, val: u8);
-    fn mirror_mode(&self) -> MirrorMode;
+    fn nametable(&self, table: u16) -> Nametable;
+    fn read_nametable(&self, nametable: Nametable, addr: u16) -> u8;
+    fn write_nametable(&self, nametable: Nametable, addr: u16, val: u8);
 }
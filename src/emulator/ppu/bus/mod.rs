@@ -2,13 +2,12 @@ mod cartridge_io;
 mod palette_ram;
 mod time_machine;
 mod vram;
-mod vram_mirroring;
 
 pub use cartridge_io::*;
 pub use time_machine::TimeMachine;
 
 use super::*;
-use vram_mirroring::Mirroring;
+use crate::emulator::cartridge::Nametable;
 
 const CARTRIDGE_START: u16 = 0x0000;
 const CARTRIDGE_END: u16 = 0x1FFF;
@@ -16,6 +15,8 @@ const CARTRIDGE_END: u16 = 0x1FFF;
 const VRAM_START: u16 = 0x2000;
 const VRAM_END: u16 = 0x3EFF;
 
+const NAMETABLE_MASK: u16 = 0x03FF;
+
 const PALLETE_START: u16 = 0x3F00;
 const PALLETE_END: u16 = 0x3FFF;
 
@@ -34,10 +35,15 @@ impl Bus {
         }
     }
 
+    pub fn fill_ram(&mut self, pattern: crate::emulator::RamPattern) {
+        self.vram.fill(pattern);
+        self.palette_ram.fill(pattern);
+    }
+
     pub fn read(&self, addr: u16) -> u8 {
         match addr {
             CARTRIDGE_START..=CARTRIDGE_END => self.cartridge_io.read(addr - CARTRIDGE_START),
-            VRAM_START..=VRAM_END => self.vram.read(self.vram_addr(addr)),
+            VRAM_START..=VRAM_END => self.read_nametable(addr - VRAM_START),
             PALLETE_START..=PALLETE_END => self.palette_ram.read(addr - PALLETE_START),
             _ => {
                 log!("Attempted to read from unmapped PPU address: {addr:04X}");
@@ -46,10 +52,19 @@ impl Bus {
         }
     }
 
+    pub fn peek(&self, addr: u16) -> u8 {
+        match addr {
+            CARTRIDGE_START..=CARTRIDGE_END => self.cartridge_io.peek(addr - CARTRIDGE_START),
+            VRAM_START..=VRAM_END => self.read_nametable(addr - VRAM_START),
+            PALLETE_START..=PALLETE_END => self.palette_ram.read(addr - PALLETE_START),
+            _ => 0,
+        }
+    }
+
     pub fn write(&mut self, addr: u16, val: u8) {
         match addr {
             CARTRIDGE_START..=CARTRIDGE_END => self.cartridge_io.write(addr - CARTRIDGE_START, val),
-            VRAM_START..=VRAM_END => self.vram.write(self.vram_addr(addr), val),
+            VRAM_START..=VRAM_END => self.write_nametable(addr - VRAM_START, val),
             PALLETE_START..=PALLETE_END => self.palette_ram.write(addr - PALLETE_START, val),
             _ => {
                 log!("Attempted to write to unmapped PPU address: {addr:04X}");
@@ -57,8 +72,22 @@ impl Bus {
         }
     }
 
-    fn vram_addr(&self, addr: u16) -> u16 {
-        self.cartridge_io.mirror_mode().mirror(addr - VRAM_START)
+    fn read_nametable(&self, addr: u16) -> u8 {
+        match self.cartridge_io.nametable(addr >> 10) {
+            Nametable::Ciram(page) => self.vram.read(Self::ciram_addr(page, addr)),
+            nametable => self.cartridge_io.read_nametable(nametable, addr),
+        }
+    }
+
+    fn write_nametable(&mut self, addr: u16, val: u8) {
+        match self.cartridge_io.nametable(addr >> 10) {
+            Nametable::Ciram(page) => self.vram.write(Self::ciram_addr(page, addr), val),
+            nametable => self.cartridge_io.write_nametable(nametable, addr, val),
+        }
+    }
+
+    fn ciram_addr(page: usize, addr: u16) -> u16 {
+        ((page as u16) << 10) | (addr & NAMETABLE_MASK)
     }
 }
 
@@ -67,6 +96,10 @@ impl Memory for Bus {
         self.read(addr)
     }
 
+    fn peek(&self, addr: u16) -> u8 {
+        self.peek(addr)
+    }
+
     fn write(&mut self, addr: u16, val: u8) {
         self.write(addr, val)
     }
@@ -1,9 +1,13 @@
+use crate::emulator::ram_pattern::{Ram, RamPattern};
+
 const PALETTE_RAM_SIZE: usize = 0x20;
 const PALETE_RAM_MASK: u16 = 0x001F;
 
 #[derive(Clone)]
 pub struct PaletteRam(Box<[u8; PALETTE_RAM_SIZE]>);
 
+state!(PaletteRam { 0 });
+
 impl Default for PaletteRam {
     fn default() -> Self {
         Self(Box::new(PALETTE_POWER_UP_STATE))
@@ -15,6 +19,16 @@ impl PaletteRam {
         Default::default()
     }
 
+    pub fn fill(&mut self, pattern: RamPattern) {
+        match pattern {
+            RamPattern::Zeros => *self.0 = PALETTE_POWER_UP_STATE,
+            _ => {
+                pattern.fill(self.0.as_mut_slice(), Ram::Palette);
+                self.0.iter_mut().for_each(|val| *val &= 0x3F);
+            }
+        }
+    }
+
     pub fn write(&mut self, addr: u16, val: u8) {
         self.0[self.resolve_address(addr)] = val & 0x3F;
     }
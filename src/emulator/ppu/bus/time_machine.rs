@@ -6,6 +6,8 @@ pub struct TimeMachine {
     palette_ram: palette_ram::PaletteRam,
 }
 
+state!(TimeMachine { vram, palette_ram });
+
 impl TimeMachine {
     pub fn save(ppu: &Bus) -> Self {
         Self {
@@ -1,14 +1,22 @@
+use crate::emulator::ram_pattern::{Ram, RamPattern};
+
 const VRAM_SIZE: usize = 0x0800;
-const VRAM_BIT_MASK: u16 = 0x07FF; // @TODO: temporary, fix when implementing mirroring
+const VRAM_BIT_MASK: u16 = 0x07FF;
 
 #[derive(Clone)]
 pub struct Vram(Box<[u8; VRAM_SIZE]>);
 
+state!(Vram { 0 });
+
 impl Vram {
     pub fn new() -> Self {
         Self(Box::new([0; VRAM_SIZE]))
     }
 
+    pub fn fill(&mut self, pattern: RamPattern) {
+        pattern.fill(self.0.as_mut_slice(), Ram::Vram);
+    }
+
     pub fn write(&mut self, addr: u16, val: u8) {
         self.0[self.resolve_address(addr) as usize] = val;
     }
@@ -1,86 +1,172 @@
 use super::*;
 
+pub const NAMETABLES_WIDTH: usize = 512;
+pub const NAMETABLES_HEIGHT: usize = 480;
+pub const PATTERN_TABLE_SIZE: usize = 128;
+
+const NAMETABLE_WIDTH: usize = 256;
+const NAMETABLE_HEIGHT: usize = 240;
+const TILE_SIZE: usize = 8;
+const TILE_BYTES: u16 = 16;
+const PALETTE_SIZE: u16 = 0x20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OamEntry {
+    pub x: u8,
+    pub y: u8,
+    pub tile: u8,
+    pub palette: u8,
+    pub behind: bool,
+    pub flip_h: bool,
+    pub flip_v: bool,
+}
+
+/*
+ * Renders the PPU memory for the viewers. The pixels are palette indices, the same values the
+ * PPU outputs, and the memory is only peeked so the emulation is not disturbed.
+ */
 pub struct Debugger<'a, M: Memory>(pub &'a Ppu<M>);
 
-#[allow(dead_code)]
 impl<M: Memory> Debugger<'_, M> {
-    pub fn print_nametable(&self, table_num: u8) {
-        println!("Name table {}:", table_num);
-        for row in 0..30 {
-            for col in 0..32 {
-                let tile = self.0.mem.read_nametable(table_num, col, row);
-                print!("{:02X} ", tile);
+    // the four nametables as they are laid out in the address space, NAMETABLES_WIDTH x NAMETABLES_HEIGHT
+    pub fn nametables(&self) -> Vec<u8> {
+        let mut pixels = vec![0; NAMETABLES_WIDTH * NAMETABLES_HEIGHT];
+        let pattern_base = self.0.regs.bg_pattern_table as u16 * 0x1000;
+        for table in 0..4u16 {
+            let base = 0x2000 | table << 10;
+            let (origin_x, origin_y) = (
+                (table as usize & 1) * NAMETABLE_WIDTH,
+                (table as usize >> 1) * NAMETABLE_HEIGHT,
+            );
+            for row in 0..(NAMETABLE_HEIGHT / TILE_SIZE) as u16 {
+                for col in 0..(NAMETABLE_WIDTH / TILE_SIZE) as u16 {
+                    let tile = self.0.mem.peek(base | row << 5 | col);
+                    let attr = self.0.mem.peek(base | 0x03C0 | (row >> 2) << 3 | col >> 2);
+                    let palette = (attr >> (((row & 0x02) << 1) | (col & 0x02))) & 0x03;
+                    self.draw_tile(
+                        &mut pixels,
+                        NAMETABLES_WIDTH,
+                        (
+                            origin_x + col as usize * TILE_SIZE,
+                            origin_y + row as usize * TILE_SIZE,
+                        ),
+                        pattern_base + tile as u16 * TILE_BYTES,
+                        palette,
+                    );
+                }
             }
-            println!();
         }
-        println!();
+        pixels
     }
 
-    pub fn print_attribute_table(&self, table_num: u8) {
-        println!("Attribute table {}:", table_num);
-        for row in 0..8 {
-            for col in 0..8 {
-                let attr = self.0.mem.read_attribute(table_num, col, row);
-                print!("{:02X} ", attr);
-            }
-            println!();
-        }
-        println!();
+    // the top left corner of the screen inside the nametables
+    pub fn scroll(&self) -> (usize, usize) {
+        let regs = &self.0.regs;
+        (
+            regs.nametable.h() as usize * NAMETABLE_WIDTH + regs.scroll.x.raw as usize,
+            regs.nametable.v() as usize * NAMETABLE_HEIGHT + regs.scroll.y.raw as usize,
+        )
     }
 
-    pub fn print_palette_table(&self) {
-        println!("Palette table:");
-        for row in 0..2 {
-            for col in 0..16 {
-                let color = self.0.mem.read(0x3F00 + row * 16 + col);
-                print!("{:02X} ", color);
-                if col % 4 == 3 {
-                    print!("  ");
-                }
-            }
-            println!();
+    // PATTERN_TABLE_SIZE squared, the palettes 0-3 are the background ones and 4-7 the sprite ones
+    pub fn pattern_table(&self, table: u16, palette: u8) -> Vec<u8> {
+        let mut pixels = vec![0; PATTERN_TABLE_SIZE * PATTERN_TABLE_SIZE];
+        let tiles_per_row = PATTERN_TABLE_SIZE / TILE_SIZE;
+        for tile in 0..(tiles_per_row * tiles_per_row) {
+            self.draw_tile(
+                &mut pixels,
+                PATTERN_TABLE_SIZE,
+                (
+                    (tile % tiles_per_row) * TILE_SIZE,
+                    (tile / tiles_per_row) * TILE_SIZE,
+                ),
+                table * 0x1000 + tile as u16 * TILE_BYTES,
+                palette,
+            );
         }
-        println!();
+        pixels
+    }
+
+    pub fn palettes(&self) -> Vec<u8> {
+        (0..PALETTE_SIZE)
+            .map(|idx| self.0.mem.peek(0x3F00 + idx))
+            .collect()
     }
 
-    pub fn print_oam(&self) {
-        println!("OAM:");
-        for row in 0..8 {
-            for col in 0..32 {
-                let sprite = self.0.oam.read(row * 32 + col);
-                print!("{:02X} ", sprite);
+    pub fn oam(&self) -> Vec<OamEntry> {
+        self.0
+            .oam
+            .sprites_iter()
+            .map(|sprite| OamEntry {
+                x: sprite.x,
+                y: sprite.y,
+                tile: sprite.tile,
+                palette: sprite.attr.palette,
+                behind: sprite.attr.behind,
+                flip_h: sprite.attr.flip_h,
+                flip_v: sprite.attr.flip_v,
+            })
+            .collect()
+    }
+
+    pub fn sprite_height(&self) -> usize {
+        self.0.regs.spr_height as usize
+    }
+
+    // 8 x sprite_height pixels, flipped as they are shown on the screen
+    pub fn sprite(&self, entry: &OamEntry) -> Vec<u8> {
+        let height = self.sprite_height();
+        let tile_addr = match self.0.regs.spr_height {
+            registers::SprHeight::Eight => {
+                self.0.regs.spr_pattern_table as u16 * 0x1000 + entry.tile as u16 * TILE_BYTES
+            }
+            registers::SprHeight::Sixteen => {
+                (entry.tile as u16 & 0x01) * 0x1000 + (entry.tile as u16 & 0xFE) * TILE_BYTES
+            }
+        };
+
+        let mut pixels = vec![0; TILE_SIZE * height];
+        for y in 0..height {
+            let src_y = if entry.flip_v { height - 1 - y } else { y };
+            // the second half of a 8x16 sprite is the next tile
+            let row_addr = tile_addr + (src_y / TILE_SIZE) as u16 * TILE_BYTES;
+            let colors = self.tile_row(row_addr, src_y % TILE_SIZE);
+            for x in 0..TILE_SIZE {
+                let src_x = if entry.flip_h { TILE_SIZE - 1 - x } else { x };
+                pixels[y * TILE_SIZE + x] = self.color(entry.palette + 4, colors[src_x]);
             }
-            println!();
         }
-        println!();
+        pixels
     }
 
-    pub fn print_pattern_row_by_tile_addr(&self, tile_addr: u16, row: usize) {
-        let row = row as u16;
-        let mut pattern_hi = self.0.mem.read(tile_addr + row);
-        let mut pattern_lo = self.0.mem.read(tile_addr + row + 8);
-
-        print!("|");
-        for _ in 0..8 {
-            let color = ((pattern_hi & 0x80) >> 7) | ((pattern_lo & 0x80) >> 6);
-            pattern_hi <<= 1;
-            pattern_lo <<= 1;
-            match color {
-                0 => print!("  "),
-                1 => print!("░░"),
-                2 => print!("▒▒"),
-                3 => print!("▓▓"),
-                _ => unreachable!(),
+    fn draw_tile(
+        &self,
+        pixels: &mut [u8],
+        width: usize,
+        (x, y): (usize, usize),
+        tile_addr: u16,
+        palette: u8,
+    ) {
+        for row in 0..TILE_SIZE {
+            let colors = self.tile_row(tile_addr, row);
+            for (col, color) in colors.into_iter().enumerate() {
+                pixels[(y + row) * width + x + col] = self.color(palette, color);
             }
         }
-        print!("|");
-        println!();
     }
 
-    pub fn print_pattern_row(&self, table: usize, pattern: (usize, usize), row: usize) {
-        let mut pattern_addr = 0x1000 * table;
-        pattern_addr += pattern.1 * 16;
-        pattern_addr += pattern.0 * 16 * 16;
-        self.print_pattern_row_by_tile_addr(pattern_addr as u16, row);
+    // the color numbers (0-3) of one row of a tile, from left to right
+    fn tile_row(&self, tile_addr: u16, row: usize) -> [u8; TILE_SIZE] {
+        let lo = self.0.mem.peek(tile_addr + row as u16);
+        let hi = self.0.mem.peek(tile_addr + row as u16 + 8);
+        std::array::from_fn(|col| ((lo >> (7 - col)) & 0x01) | ((hi >> (7 - col)) & 0x01) << 1)
+    }
+
+    fn color(&self, palette: u8, color: u8) -> u8 {
+        match color {
+            // the transparent color shows the backdrop
+            0 => self.0.mem.peek(0x3F00),
+            _ => self.0.mem.peek(0x3F00 + palette as u16 * 4 + color as u16),
+        }
     }
 }
@@ -1,6 +1,6 @@
 use super::*;
 
-#[derive(Clone)]
+#[derive(Default, Clone)]
 struct SpritePixels {
     hi: u8,
     lo: u8,
@@ -9,6 +9,14 @@ struct SpritePixels {
     behind: bool,
 }
 
+state!(SpritePixels {
+    hi,
+    lo,
+    palette,
+    x,
+    behind,
+});
+
 impl SpritePixels {
     fn color(&self, offset: usize) -> u8 {
         if offset >= 8 {
@@ -24,6 +32,11 @@ pub struct Foreground {
     pub zero_fetch: bool,
 }
 
+state!(Foreground {
+    spr_pixels,
+    zero_fetch,
+});
+
 impl Foreground {
     pub fn new() -> Self {
         Self {
@@ -2,6 +2,25 @@ use super::*;
 
 pub struct IOPorts<'a, M: Memory>(&'a mut Ppu<M>);
 
+impl<M: Memory> Ppu<M> {
+    // what IOPorts::read would return, without touching the flags or the address
+    pub fn peek_io_port(&self, addr: u16) -> u8 {
+        match addr {
+            0x02 => {
+                (self.regs.vblank_occurred.is_some() as u8) << 7
+                    | (self.regs.spr0_hit as u8) << 6
+                    | (self.regs.spr_overflow as u8) << 5
+            }
+            0x04 => self.oam.read(self.regs.oam_addr),
+            0x07 => match self.regs.vram_addr.get() {
+                addr @ 0x3F00..=0x3FFF => self.mem.peek(addr),
+                _ => self.regs.vram_data,
+            },
+            _ => 0,
+        }
+    }
+}
+
 impl<M: Memory> IOPorts<'_, M> {
     pub fn new(ppu: &mut Ppu<M>) -> IOPorts<M> {
         IOPorts(ppu)
@@ -119,6 +138,7 @@ impl<M: Memory> IOPorts<'_, M> {
         self.0.regs.clip_spr = val & 0b0000_0100 == 0;
         self.0.regs.show_bg = val & 0b0000_1000 != 0;
         self.0.regs.show_spr = val & 0b0001_0000 != 0;
+        self.0.regs.emphasis = val >> 5;
     }
 
     fn write_oam_address(&mut self, val: u8) {
@@ -5,6 +5,10 @@ const PALETTE_BASE_ADDR: u16 = 0x3F00;
 pub trait Memory {
     fn read(&self, addr: u16) -> u8;
     fn write(&mut self, addr: u16, val: u8);
+    // memories whose reads have side effects must override it
+    fn peek(&self, addr: u16) -> u8 {
+        self.read(addr)
+    }
 
     fn read_nametable(&self, table: u8, row: u8, col: u8) -> u8 {
         let addr =
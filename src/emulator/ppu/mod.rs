@@ -1,5 +1,5 @@
 mod background;
-mod debugger;
+pub mod debugger;
 mod foreground;
 mod io_ports;
 mod memory;
@@ -12,13 +12,14 @@ mod time_machine;
 
 pub mod bus;
 
+pub use debugger::OamEntry;
 pub use memory::*;
 use pixel::{Kind as PixelKind, Pixel};
 use sprite::RawSprite;
 pub use time_machine::TimeMachine;
 
 const DOTS_PER_LINE: usize = 341;
-const LINES_PER_FRAME: usize = 262;
+pub const LINES_PER_FRAME: usize = 262;
 const MAX_VISIBLE_SPRITES: usize = 8;
 
 pub struct Ppu<M: Memory> {
@@ -68,11 +69,26 @@ impl<M: Memory> Ppu<M> {
         }
     }
 
-    #[allow(dead_code)]
+    // the Reset button clears the control, mask and scroll registers, the memory is kept
+    pub fn reset(&mut self) {
+        let mut io_ports = self.io_ports();
+        io_ports.write_ctrl(0);
+        io_ports.write_mask(0);
+        self.regs.latch = None;
+        self.regs.set_scroll(0, 0);
+        self.regs.vram_data = 0;
+        self.odd_frame = false;
+    }
+
     pub fn debugger(&self) -> debugger::Debugger<M> {
         debugger::Debugger(self)
     }
 
+    // the palette index in the bits 0-5 and the color emphasis in the bits 6-8
+    pub fn output(&self) -> u16 {
+        self.color_idx as u16 | (self.regs.emphasis as u16) << 6
+    }
+
     pub fn take_nmi(&mut self) -> bool {
         self.nmi.take()
     }
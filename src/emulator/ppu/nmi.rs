@@ -4,6 +4,11 @@ pub struct Nmi {
     countdown: usize,
 }
 
+state!(Nmi {
+    occurred,
+    countdown,
+});
+
 /*
  * According to the documentation, the NMI is triggered at scanline 241 dot 1, but if the signal
  * is sent to the CPU at this moment, the emulation will fail in all nmi timing tests.
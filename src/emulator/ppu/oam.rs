@@ -7,6 +7,8 @@ pub struct Oam {
     mem: Box<[u8; OAM_SIZE]>,
 }
 
+state!(Oam { mem });
+
 impl Oam {
     pub fn new() -> Self {
         Self {
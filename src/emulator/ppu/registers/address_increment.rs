@@ -4,3 +4,5 @@ pub enum AddressIncrement {
     Increment1 = 1,
     Increment32 = 32,
 }
+
+state_enum!(AddressIncrement { Increment1 = 0, Increment32 = 1 });
@@ -30,6 +30,8 @@ pub struct Registers {
     pub clip_spr: bool,
     pub show_bg: bool,
     pub show_spr: bool,
+    // red, green and blue in the bits 0, 1 and 2
+    pub emphasis: u8,
 
     pub spr0_hit: bool,
     pub spr0_found: bool,
@@ -39,6 +41,30 @@ pub struct Registers {
     pub nmi_suppressed: bool,
 }
 
+state!(Registers {
+    latch,
+    vram_addr,
+    vram_data,
+    addres_increment,
+    scroll,
+    spr_pattern_table,
+    bg_pattern_table,
+    spr_height,
+    nametable,
+    oam_addr,
+    clip_bg,
+    clip_spr,
+    show_bg,
+    show_spr,
+    emphasis,
+    spr0_hit,
+    spr0_found,
+    spr_overflow,
+    vblank_occurred,
+    nmi_enabled,
+    nmi_suppressed,
+});
+
 impl Registers {
     pub fn set_scroll(&mut self, x: u8, y: u8) {
         self.scroll.set_x(x);
@@ -7,6 +7,8 @@ pub enum Nametable {
     Three = 3,
 }
 
+state_enum!(Nametable { Zero = 0, One = 1, Two = 2, Three = 3 });
+
 impl Nametable {
     pub fn h(&self) -> u8 {
         match self {
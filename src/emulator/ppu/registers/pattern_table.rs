@@ -5,6 +5,8 @@ pub enum PatternTable {
     One,
 }
 
+state_enum!(PatternTable { Zero = 0, One = 1 });
+
 impl From<u8> for PatternTable {
     fn from(val: u8) -> Self {
         match val {
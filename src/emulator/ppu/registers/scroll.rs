@@ -4,6 +4,8 @@ pub struct Scroll {
     pub y: Axis,
 }
 
+state!(Scroll { x, y });
+
 impl Scroll {
     pub fn set_x(&mut self, val: impl Into<Axis>) {
         self.x = val.into();
@@ -19,6 +21,8 @@ pub struct Axis {
     pub raw: u8,
 }
 
+state!(Axis { raw });
+
 impl std::fmt::Debug for Axis {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if f.alternate() {
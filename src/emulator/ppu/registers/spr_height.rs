@@ -5,6 +5,8 @@ pub enum SprHeight {
     Sixteen = 16,
 }
 
+state_enum!(SprHeight { Eight = 0, Sixteen = 1 });
+
 impl From<u8> for SprHeight {
     fn from(val: u8) -> Self {
         match val {
@@ -1,6 +1,8 @@
 #[derive(Default, Clone, Copy)]
 pub struct VramAddress(pub u16);
 
+state!(VramAddress { 0 });
+
 impl std::fmt::Debug for VramAddress {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if f.alternate() {
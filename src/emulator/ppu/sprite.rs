@@ -6,6 +6,8 @@ pub struct RawSprite {
     pub tile: u8,
 }
 
+state!(RawSprite { attr, x, y, tile });
+
 impl Default for RawSprite {
     fn default() -> Self {
         Self {
@@ -36,6 +38,13 @@ pub struct Attributes {
     pub flip_v: bool,
 }
 
+state!(Attributes {
+    palette,
+    behind,
+    flip_h,
+    flip_v,
+});
+
 impl From<u8> for Attributes {
     fn from(val: u8) -> Self {
         Self {
@@ -16,6 +16,21 @@ pub struct TimeMachine {
     cycle: usize,
 }
 
+state!(TimeMachine {
+    nmi,
+    oam,
+    sprites,
+    regs,
+    background,
+    foreground,
+    odd_frame,
+    color_idx,
+    dot,
+    scanline,
+    frame,
+    cycle,
+});
+
 impl TimeMachine {
     pub fn save<M: Memory>(ppu: &Ppu<M>) -> Self {
         Self {
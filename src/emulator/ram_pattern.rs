@@ -0,0 +1,139 @@
+// every RAM gets its own random stream, so they don't start with the same contents
+#[derive(Debug, Clone, Copy)]
+pub enum Ram {
+    Wram = 1,
+    Sram,
+    Vram,
+    Palette,
+    Chr,
+    Nametables,
+    Prg,
+}
+
+/*
+ * The contents of the RAMs when the console is turned on. Real consoles start with mostly random
+ * values, so some games only work (or only break) when the RAM is not zeroed.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RamPattern {
+    // the palette RAM starts with the values of a real console instead
+    #[default]
+    Zeros,
+    Ones,
+    Random(u64),
+    // 4 bytes $00 followed by 4 bytes $FF, as seen on many consoles
+    Alternating,
+}
+
+impl RamPattern {
+    pub fn fill(&self, ram: &mut [u8], kind: Ram) {
+        match self {
+            RamPattern::Zeros => ram.fill(0x00),
+            RamPattern::Ones => ram.fill(0xFF),
+            RamPattern::Alternating => ram
+                .iter_mut()
+                .enumerate()
+                .for_each(|(idx, val)| *val = if idx & 0x04 == 0 { 0x00 } else { 0xFF }),
+            RamPattern::Random(seed) => {
+                let mut state = seed ^ (kind as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+                for chunk in ram.chunks_mut(8) {
+                    let bytes = splitmix64(&mut state).to_le_bytes();
+                    chunk.copy_from_slice(&bytes[..chunk.len()]);
+                }
+            }
+        }
+    }
+}
+
+// https://prng.di.unimi.it/splitmix64.c
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+impl std::fmt::Display for RamPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RamPattern::Zeros => write!(f, "zeros"),
+            RamPattern::Ones => write!(f, "ones"),
+            RamPattern::Random(seed) => write!(f, "random:{}", seed),
+            RamPattern::Alternating => write!(f, "alternating"),
+        }
+    }
+}
+
+// "random" without a seed picks one from the clock, the Display output reproduces it
+impl std::str::FromStr for RamPattern {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.split_once(':') {
+            None if text == "zeros" => Ok(RamPattern::Zeros),
+            None if text == "ones" => Ok(RamPattern::Ones),
+            None if text == "alternating" => Ok(RamPattern::Alternating),
+            None if text == "random" => {
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default();
+                Ok(RamPattern::Random(now.as_nanos() as u64))
+            }
+            Some(("random", seed)) => seed
+                .parse()
+                .map(RamPattern::Random)
+                .map_err(|_| format!("invalid seed: {}", seed)),
+            _ => Err(format!(
+                "invalid RAM pattern: {} (expected zeros, ones, alternating, random or random:<seed>)",
+                text
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill() {
+        let mut ram = [0x12; 10];
+        RamPattern::Ones.fill(&mut ram, Ram::Wram);
+        assert_eq!(ram, [0xFF; 10]);
+        RamPattern::Zeros.fill(&mut ram, Ram::Wram);
+        assert_eq!(ram, [0x00; 10]);
+        RamPattern::Alternating.fill(&mut ram, Ram::Wram);
+        assert_eq!(
+            ram,
+            [0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn test_fill_random() {
+        let fill = |seed, kind| {
+            let mut ram = [0; 0x13];
+            RamPattern::Random(seed).fill(&mut ram, kind);
+            ram
+        };
+        assert_eq!(fill(42, Ram::Wram), fill(42, Ram::Wram));
+        assert_ne!(fill(42, Ram::Wram), fill(43, Ram::Wram));
+        assert_ne!(fill(42, Ram::Wram), fill(42, Ram::Vram));
+        assert!(fill(42, Ram::Wram).iter().any(|&val| val != 0));
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("zeros".parse(), Ok(RamPattern::Zeros));
+        assert_eq!("ones".parse(), Ok(RamPattern::Ones));
+        assert_eq!("alternating".parse(), Ok(RamPattern::Alternating));
+        assert_eq!("random:1234".parse(), Ok(RamPattern::Random(1234)));
+        assert!(matches!("random".parse(), Ok(RamPattern::Random(_))));
+        assert!("random:x".parse::<RamPattern>().is_err());
+        assert!("twos".parse::<RamPattern>().is_err());
+
+        let pattern = RamPattern::Random(99);
+        assert_eq!(pattern.to_string().parse(), Ok(pattern));
+    }
+}
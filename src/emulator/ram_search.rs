@@ -0,0 +1,226 @@
+use super::Emulator;
+
+// the CPU RAM and the SRAM, the memory a game keeps its variables in
+const REGIONS: [std::ops::Range<u16>; 2] = [0x0000..0x0800, 0x6000..0x8000];
+
+// the 2 bytes values are little-endian
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Size {
+    Byte,
+    Word,
+}
+
+impl Size {
+    fn bytes(&self) -> u16 {
+        match self {
+            Size::Byte => 1,
+            Size::Word => 2,
+        }
+    }
+}
+
+// compares the current values with the ones of the previous snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Equal,
+    Changed,
+    Increased,
+    Decreased,
+    Value(u16),
+}
+
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub addr: u16,
+    // the value the last filter compared with
+    pub previous: u16,
+    pub current: u16,
+}
+
+/*
+ * Narrows down the address of a game variable: every filter keeps the addresses whose value
+ * passes it and takes a new snapshot, so the emulator can run between the filters.
+ */
+#[derive(Debug, Clone)]
+pub struct RamSearch {
+    size: Size,
+    // (address, value before the last snapshot, value in the last snapshot)
+    candidates: Vec<(u16, u16, u16)>,
+}
+
+impl RamSearch {
+    pub fn new(emulator: &Emulator, size: Size) -> Self {
+        let candidates = REGIONS
+            .iter()
+            .flat_map(|region| region.start..=region.end - size.bytes())
+            .map(|addr| {
+                let value = read(emulator, addr, size);
+                (addr, value, value)
+            })
+            .collect();
+        Self { size, candidates }
+    }
+
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    // returns the number of addresses left
+    pub fn filter(&mut self, emulator: &Emulator, filter: Filter) -> usize {
+        let size = self.size;
+        self.candidates.retain_mut(|(addr, previous, snapshot)| {
+            let current = read(emulator, *addr, size);
+            let keep = match filter {
+                Filter::Equal => current == *snapshot,
+                Filter::Changed => current != *snapshot,
+                Filter::Increased => current > *snapshot,
+                Filter::Decreased => current < *snapshot,
+                Filter::Value(value) => current == value,
+            };
+            *previous = *snapshot;
+            *snapshot = current;
+            keep
+        });
+        self.candidates.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    pub fn candidates(&self, emulator: &Emulator) -> Vec<Candidate> {
+        self.candidates
+            .iter()
+            .map(|&(addr, previous, _)| Candidate {
+                addr,
+                previous,
+                current: read(emulator, addr, self.size),
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watch {
+    pub addr: u16,
+    pub size: Size,
+    pub label: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WatchList {
+    pub watches: Vec<Watch>,
+}
+
+impl WatchList {
+    // adds the watch, or removes it when the address is already watched; returns if it was added
+    pub fn toggle(&mut self, watch: Watch) -> bool {
+        match self.watches.iter().position(|w| w.addr == watch.addr) {
+            Some(idx) => {
+                self.watches.remove(idx);
+                false
+            }
+            None => {
+                self.watches.push(watch);
+                true
+            }
+        }
+    }
+
+    // one "ADDR label = value" line per watch
+    pub fn render(&self, emulator: &Emulator) -> Vec<String> {
+        self.watches
+            .iter()
+            .map(|watch| {
+                let value = read(emulator, watch.addr, watch.size);
+                let label = match watch.label.as_str() {
+                    "" => String::new(),
+                    label => format!(" {}", label),
+                };
+                match watch.size {
+                    Size::Byte => {
+                        format!("{:04X}{} = {:02X} ({})", watch.addr, label, value, value)
+                    }
+                    Size::Word => {
+                        format!("{:04X}{} = {:04X} ({})", watch.addr, label, value, value)
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+fn read(emulator: &Emulator, addr: u16, size: Size) -> u16 {
+    let lo = emulator.peek_memory(addr) as u16;
+    match size {
+        Size::Byte => lo,
+        Size::Word => lo | (emulator.peek_memory(addr.wrapping_add(1)) as u16) << 8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::cartridge;
+
+    fn mk_emulator() -> Emulator {
+        Emulator::new(cartridge::Cartridge::with_program(&[]))
+    }
+
+    #[test]
+    fn test_search_byte() {
+        let mut emu = mk_emulator();
+        let mut search = RamSearch::new(&emu, Size::Byte);
+        assert_eq!(search.len(), 0x0800 + 0x2000);
+
+        emu.write_memory(0x0010, 0x03);
+        emu.write_memory(0x6100, 0x05);
+        assert_eq!(search.filter(&emu, Filter::Changed), 2);
+        emu.write_memory(0x0010, 0x02);
+        emu.write_memory(0x6100, 0x06);
+        assert_eq!(search.filter(&emu, Filter::Decreased), 1);
+        assert_eq!(search.filter(&emu, Filter::Equal), 1);
+
+        let candidates = search.candidates(&emu);
+        assert_eq!(candidates[0].addr, 0x0010);
+        assert_eq!(candidates[0].current, 0x02);
+        assert_eq!(search.filter(&emu, Filter::Value(0x01)), 0);
+    }
+
+    #[test]
+    fn test_search_word() {
+        let mut emu = mk_emulator();
+        let mut search = RamSearch::new(&emu, Size::Word);
+        assert_eq!(search.len(), 0x07FF + 0x1FFF);
+
+        emu.write_memory(0x0020, 0xFF);
+        emu.write_memory(0x0021, 0x01);
+        assert_eq!(search.filter(&emu, Filter::Value(0x01FF)), 1);
+        emu.write_memory(0x0020, 0x00);
+        emu.write_memory(0x0021, 0x02);
+        assert_eq!(search.filter(&emu, Filter::Increased), 1);
+        assert_eq!(search.candidates(&emu)[0].previous, 0x01FF);
+    }
+
+    #[test]
+    fn test_watch_list() {
+        let mut emu = mk_emulator();
+        let mut watches = WatchList::default();
+        let watch = |addr, size, label: &str| Watch {
+            addr,
+            size,
+            label: label.to_string(),
+        };
+        assert!(watches.toggle(watch(0x0075, Size::Byte, "lives")));
+        assert!(watches.toggle(watch(0x0100, Size::Word, "")));
+        emu.write_memory(0x0075, 0x0A);
+        emu.write_memory(0x0100, 0x34);
+        emu.write_memory(0x0101, 0x12);
+        assert_eq!(
+            watches.render(&emu),
+            vec!["0075 lives = 0A (10)", "0100 = 1234 (4660)"]
+        );
+        assert!(!watches.toggle(watch(0x0075, Size::Byte, "")));
+        assert_eq!(watches.watches.len(), 1);
+    }
+}
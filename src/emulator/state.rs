@@ -0,0 +1,300 @@
+use std::cell::RefCell;
+
+const MAGIC: &[u8; 4] = b"SRST";
+const VERSION: u8 = 1;
+
+/*
+ * The save states as bytes, for the movies starting from one. A state is loaded in place, over
+ * the emulator of the same ROM, so the fields fixed by the ROM and the mapper kind are left out.
+ * The integers are little-endian, usize as 64 bits and the vectors start with their u32 length.
+ */
+pub trait State {
+    #[allow(dead_code)] // the movies are only played from a state, the tests save them
+    fn save(&self, out: &mut Vec<u8>);
+    fn load(&mut self, data: &mut &[u8]) -> Result<(), String>;
+}
+
+// "SRST", version and the state
+#[cfg(test)]
+pub fn write(state: &impl State) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    out.push(VERSION);
+    state.save(&mut out);
+    out
+}
+
+pub fn read(state: &mut impl State, data: &[u8]) -> Result<(), String> {
+    let Some(data) = data.strip_prefix(MAGIC) else {
+        return Err("not a save state".to_string());
+    };
+    let (&version, mut data) = data.split_first().ok_or("truncated save state")?;
+    if version != VERSION {
+        return Err(format!("unsupported save state version {}", version));
+    }
+    state.load(&mut data)?;
+    if !data.is_empty() {
+        return Err("trailing data after the save state".to_string());
+    }
+    Ok(())
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8], String> {
+    if data.len() < len {
+        return Err("truncated save state".to_string());
+    }
+    let (head, tail) = data.split_at(len);
+    *data = tail;
+    Ok(head)
+}
+
+// the fields in the given order
+macro_rules! state {
+    (<const $param:ident: $param_type:ty> $type:ty { $($field:tt),* $(,)? }) => {
+        state!(@impl [const $param: $param_type] $type { $($field),* });
+    };
+    (@impl [$($generics:tt)*] $type:ty { $($field:tt),* }) => {
+        impl<$($generics)*> $crate::emulator::state::State for $type {
+            fn save(&self, out: &mut Vec<u8>) {
+                $($crate::emulator::state::State::save(&self.$field, out);)*
+            }
+
+            fn load(&mut self, data: &mut &[u8]) -> Result<(), String> {
+                $($crate::emulator::state::State::load(&mut self.$field, data)?;)*
+                Ok(())
+            }
+        }
+    };
+    ($type:ty { $($field:tt),* $(,)? }) => {
+        state!(@impl [] $type { $($field),* });
+    };
+}
+
+// the variants as a byte
+macro_rules! state_enum {
+    ($type:ty { $($variant:ident = $val:literal),* $(,)? }) => {
+        impl $crate::emulator::state::State for $type {
+            fn save(&self, out: &mut Vec<u8>) {
+                let val: u8 = match self {
+                    $(Self::$variant => $val,)*
+                };
+                out.push(val);
+            }
+
+            fn load(&mut self, data: &mut &[u8]) -> Result<(), String> {
+                let mut val = 0u8;
+                $crate::emulator::state::State::load(&mut val, data)?;
+                *self = match val {
+                    $($val => Self::$variant,)*
+                    _ => return Err(format!("invalid {} {}", stringify!($type), val)),
+                };
+                Ok(())
+            }
+        }
+    };
+}
+
+macro_rules! state_int {
+    ($($type:ty),*) => {
+        $(impl State for $type {
+            fn save(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_le_bytes());
+            }
+
+            fn load(&mut self, data: &mut &[u8]) -> Result<(), String> {
+                let bytes = take(data, std::mem::size_of::<$type>())?;
+                *self = <$type>::from_le_bytes(bytes.try_into().unwrap());
+                Ok(())
+            }
+        })*
+    };
+}
+
+state_int!(u8, u16, u32, u64, i8);
+
+impl State for usize {
+    fn save(&self, out: &mut Vec<u8>) {
+        (*self as u64).save(out);
+    }
+
+    fn load(&mut self, data: &mut &[u8]) -> Result<(), String> {
+        let mut val = 0u64;
+        val.load(data)?;
+        *self = usize::try_from(val).map_err(|_| format!("invalid size {}", val))?;
+        Ok(())
+    }
+}
+
+impl State for bool {
+    fn save(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+
+    fn load(&mut self, data: &mut &[u8]) -> Result<(), String> {
+        *self = match take(data, 1)?[0] {
+            0 => false,
+            1 => true,
+            val => return Err(format!("invalid bool {}", val)),
+        };
+        Ok(())
+    }
+}
+
+impl State for () {
+    fn save(&self, _out: &mut Vec<u8>) {}
+
+    fn load(&mut self, _data: &mut &[u8]) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl<T: State + Default> State for Option<T> {
+    fn save(&self, out: &mut Vec<u8>) {
+        self.is_some().save(out);
+        if let Some(val) = self {
+            val.save(out);
+        }
+    }
+
+    fn load(&mut self, data: &mut &[u8]) -> Result<(), String> {
+        let mut some = false;
+        some.load(data)?;
+        *self = if some {
+            let mut val = self.take().unwrap_or_default();
+            val.load(data)?;
+            Some(val)
+        } else {
+            None
+        };
+        Ok(())
+    }
+}
+
+impl<A: State, B: State> State for (A, B) {
+    fn save(&self, out: &mut Vec<u8>) {
+        self.0.save(out);
+        self.1.save(out);
+    }
+
+    fn load(&mut self, data: &mut &[u8]) -> Result<(), String> {
+        self.0.load(data)?;
+        self.1.load(data)
+    }
+}
+
+impl<T: State, const N: usize> State for [T; N] {
+    fn save(&self, out: &mut Vec<u8>) {
+        self.iter().for_each(|val| val.save(out));
+    }
+
+    fn load(&mut self, data: &mut &[u8]) -> Result<(), String> {
+        self.iter_mut().try_for_each(|val| val.load(data))
+    }
+}
+
+impl<T: State + Default> State for Vec<T> {
+    fn save(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).save(out);
+        self.iter().for_each(|val| val.save(out));
+    }
+
+    fn load(&mut self, data: &mut &[u8]) -> Result<(), String> {
+        let mut len = 0u32;
+        len.load(data)?;
+        // every element takes a byte at least, a broken length fails before the allocation
+        if len as usize > data.len() {
+            return Err("truncated save state".to_string());
+        }
+        self.resize_with(len as usize, T::default);
+        self.iter_mut().try_for_each(|val| val.load(data))
+    }
+}
+
+impl<T: State + ?Sized> State for Box<T> {
+    fn save(&self, out: &mut Vec<u8>) {
+        self.as_ref().save(out);
+    }
+
+    fn load(&mut self, data: &mut &[u8]) -> Result<(), String> {
+        self.as_mut().load(data)
+    }
+}
+
+impl<T: State> State for RefCell<T> {
+    fn save(&self, out: &mut Vec<u8>) {
+        self.borrow().save(out);
+    }
+
+    fn load(&mut self, data: &mut &[u8]) -> Result<(), String> {
+        self.get_mut().load(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default, Debug, PartialEq)]
+    struct Sample {
+        a: u16,
+        b: Option<usize>,
+        c: Vec<(bool, i8)>,
+        d: [u8; 3],
+    }
+
+    state!(Sample { a, b, c, d });
+
+    #[derive(Default, Debug, PartialEq)]
+    enum Kind {
+        #[default]
+        One,
+        Two,
+    }
+
+    state_enum!(Kind { One = 0, Two = 1 });
+
+    #[test]
+    fn test_round_trip() {
+        let sample = Sample {
+            a: 0x1234,
+            b: Some(7),
+            c: vec![(true, -1), (false, 2)],
+            d: [1, 2, 3],
+        };
+        let data = write(&sample);
+        assert_eq!(&data[..7], b"SRST\x01\x34\x12");
+
+        let mut loaded = Sample::default();
+        read(&mut loaded, &data).unwrap();
+        assert_eq!(loaded, sample);
+
+        let mut kind = Kind::One;
+        read(&mut kind, &write(&Kind::Two)).unwrap();
+        assert_eq!(kind, Kind::Two);
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut sample = Sample::default();
+        assert_eq!(
+            read(&mut sample, b"SRMV"),
+            Err("not a save state".to_string())
+        );
+        assert_eq!(
+            read(&mut sample, b"SRST\x02"),
+            Err("unsupported save state version 2".to_string())
+        );
+        let data = write(&Sample::default());
+        assert_eq!(
+            read(&mut sample, &data[..data.len() - 1]),
+            Err("truncated save state".to_string())
+        );
+        assert_eq!(
+            read(&mut sample, &[data.as_slice(), &[0]].concat()),
+            Err("trailing data after the save state".to_string())
+        );
+        assert_eq!(
+            read(&mut Kind::One, b"SRST\x01\x02"),
+            Err("invalid Kind 2".to_string())
+        );
+    }
+}
@@ -88,3 +88,190 @@ fn test_frame_timing() {
         );
     }
 }
+
+#[test]
+fn test_run_frame() {
+    let mut emulator = Emulator::new(cartridge::Cartridge::with_program(&[]));
+    {
+        let mut ppu = emulator.ppu.as_mut();
+        ppu.mem.write(0x3F00, 0x21); // backdrop
+        ppu.io_ports().write(0x01, 0b1000_0000); // blue emphasis, rendering disabled
+    }
+
+    emulator.run_frame(Emulator::clock);
+    assert_eq!(emulator.frame(), 1);
+    assert_eq!(emulator.ppu.as_ref().scanline, 0);
+    assert_eq!(emulator.framebuffer().len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+    assert!(emulator
+        .framebuffer()
+        .iter()
+        .all(|&pixel| pixel == 0x21 | 0b100 << 6));
+
+    let image = emulator.framebuffer_image();
+    let color = Color::from_pixel(&video::DEFAULT_PALETTE, 0x21 | 0b100 << 6);
+    assert_eq!(image.get(0, 0), color);
+    assert_eq!(image.get(SCREEN_WIDTH - 1, SCREEN_HEIGHT - 1), color);
+}
+
+fn mk_reset_test_emulator() -> Emulator {
+    let mut emulator = Emulator::new(cartridge::Cartridge::with_program(&[]));
+    emulator.run_frame(Emulator::clock);
+    emulator.write_memory(0x0010, 0x55);
+    emulator.write_memory(0x6000, 0x66);
+    emulator.write_memory(0x2000, 0x81); // enable NMI, second nametable
+    emulator.write_memory(0x2005, 0x10);
+    emulator.write_memory(0x2005, 0x08);
+    emulator.write_memory(0x4015, 0x01); // enable pulse 1
+    emulator.write_memory(0x4003, 0x08); // load its length counter
+    emulator.cpu.a = 0x12;
+    emulator
+}
+
+#[test]
+fn test_reset() {
+    let mut emulator = mk_reset_test_emulator();
+    assert_eq!(emulator.peek_memory(0x4015), 0x01);
+    assert_eq!(emulator.ppu.as_ref().debugger().scroll(), (0x110, 0x08));
+    let sp = emulator.cpu.sp;
+
+    emulator.reset();
+    assert_eq!(emulator.cpu.pc, 0x8000);
+    assert_eq!(emulator.cpu.sp, sp.wrapping_sub(3));
+    assert!(emulator.cpu.p.get(cpu::Status::I));
+    assert_eq!(emulator.cpu.a, 0x12);
+    assert_eq!(emulator.peek_memory(0x4015), 0x00, "the APU is silenced");
+    assert_eq!(emulator.ppu.as_ref().debugger().scroll(), (0, 0));
+    assert_eq!(emulator.peek_memory(0x0010), 0x55);
+    assert_eq!(emulator.peek_memory(0x6000), 0x66);
+    assert_eq!(emulator.frame(), 1);
+}
+
+#[test]
+fn test_power_cycle() {
+    let mut emulator = mk_reset_test_emulator();
+    emulator.power_cycle();
+    assert_eq!(emulator.cpu.pc, 0x8000);
+    assert_eq!(emulator.cpu.sp, 0xFD);
+    assert_eq!(emulator.cpu.a, 0x00);
+    assert_eq!(emulator.peek_memory(0x4015), 0x00);
+    assert_eq!(emulator.ppu.as_ref().debugger().scroll(), (0, 0));
+    assert_eq!(emulator.peek_memory(0x0010), 0x00);
+    assert_eq!(
+        emulator.peek_memory(0x6000),
+        0x66,
+        "the battery RAM is kept"
+    );
+    assert_eq!(emulator.frame(), 0);
+
+    emulator.run_frame(Emulator::clock);
+    assert_eq!(emulator.frame(), 1);
+}
+
+#[test]
+fn test_ram_pattern() {
+    let cartridge = cartridge::Cartridge::with_program(&[]);
+    let mut emulator = Emulator::with_ram_pattern(cartridge, RamPattern::Ones);
+    assert_eq!(emulator.ram_pattern(), RamPattern::Ones);
+    assert_eq!(emulator.cpu.pc, 0x8000);
+    assert_eq!(emulator.peek_memory(0x0000), 0xFF);
+    assert_eq!(emulator.peek_memory(0x07FF), 0xFF);
+    assert_eq!(emulator.peek_memory(0x6000), 0xFF);
+    {
+        let ppu = emulator.ppu.as_ref();
+        assert_eq!(ppu.mem.peek(0x0000), 0xFF, "CHR RAM");
+        assert_eq!(ppu.mem.peek(0x2000), 0xFF);
+        assert_eq!(ppu.mem.peek(0x3F00), 0x3F);
+    }
+
+    emulator.write_memory(0x0000, 0x12);
+    emulator.write_memory(0x6000, 0x34);
+    emulator.power_cycle();
+    assert_eq!(emulator.peek_memory(0x0000), 0xFF);
+    assert_eq!(emulator.peek_memory(0x6000), 0x34);
+}
+
+#[test]
+fn test_cheats() {
+    let cartridge = cartridge::Cartridge::with_program(&[
+        (0x8000, &[0x4C, 0x00, 0x80]), // JMP $8000
+        (0xD1DD, &[0x20]),
+    ]);
+    let mut emulator = Emulator::new(cartridge);
+    let state = emulator.save_state();
+
+    // GOSSIP writes $14 at $D1DD, ZEXPYGLA writes $02 at $94A7 only when it holds $03
+    let mut cheats = cheats::Cheats::parse("GOSSIP\nZEXPYGLA\n0010:55").unwrap();
+    emulator.set_cheats(cheats.clone());
+    assert_eq!(emulator.peek_memory(0xD1DD), 0x14);
+    assert_eq!(emulator.peek_memory(0x94A7), 0xEA);
+    assert_eq!(emulator.peek_memory(0x0010), 0x00);
+    emulator.run_frame(Emulator::clock);
+    assert_eq!(emulator.peek_memory(0x0010), 0x55);
+    emulator.write_memory(0x0010, 0x00);
+    emulator.run_frame(Emulator::clock);
+    assert_eq!(emulator.peek_memory(0x0010), 0x55, "frozen every frame");
+
+    let with_cheats = emulator.save_state();
+    cheats.enabled = false;
+    emulator.set_cheats(cheats);
+    assert_eq!(emulator.peek_memory(0xD1DD), 0x20);
+
+    emulator.load_state(with_cheats);
+    assert!(emulator.cheats().enabled);
+    assert_eq!(emulator.peek_memory(0xD1DD), 0x14);
+    emulator.load_state(state);
+    assert!(emulator.cheats().cheats.is_empty());
+    assert_eq!(emulator.peek_memory(0xD1DD), 0x20);
+}
+
+#[test]
+fn test_lag_frames() {
+    // loop: LDA $10; BEQ loop_end; LDA $4016; loop_end: JMP loop
+    let cartridge = cartridge::Cartridge::with_program(&[(
+        0x8000,
+        &[0xA5, 0x10, 0xF0, 0x03, 0xAD, 0x16, 0x40, 0x4C, 0x00, 0x80],
+    )]);
+    let mut emulator = Emulator::new(cartridge);
+    emulator.run_frame(Emulator::clock);
+    emulator.run_frame(Emulator::clock);
+    assert!(emulator.lagged());
+    assert_eq!(emulator.lag_frames(), 2);
+
+    emulator.write_memory(0x0010, 0x01);
+    emulator.run_frame(Emulator::clock);
+    assert!(!emulator.lagged());
+    let state = emulator.save_state();
+    emulator.write_memory(0x0010, 0x00);
+    emulator.run_frame(Emulator::clock);
+    emulator.run_frame(Emulator::clock);
+    assert_eq!(emulator.lag_frames(), 4);
+
+    emulator.load_state(state);
+    assert_eq!(emulator.lag_frames(), 2);
+    assert!(!emulator.lagged());
+}
+
+#[test]
+fn test_state_data() {
+    let mut emulator = mk_reset_test_emulator();
+    let data = emulator.state_data();
+
+    let mut loaded = Emulator::new(cartridge::Cartridge::with_program(&[]));
+    loaded.load_state_data(&data).unwrap();
+    assert_eq!(loaded.frame(), 1);
+    assert_eq!(loaded.peek_memory(0x0010), 0x55);
+    assert_eq!(loaded.peek_memory(0x6000), 0x66);
+    assert_eq!(loaded.cpu.a, 0x12);
+    assert_eq!(loaded.state_data(), data);
+
+    emulator.run_frame(Emulator::clock);
+    loaded.run_frame(Emulator::clock);
+    assert_eq!(loaded.state_data(), emulator.state_data());
+    assert_eq!(loaded.framebuffer(), emulator.framebuffer());
+
+    assert_eq!(
+        loaded.load_state_data(&data[..data.len() - 1]),
+        Err("truncated save state".to_string())
+    );
+    assert_eq!(loaded.frame(), 2, "a broken state is not loaded");
+}
@@ -0,0 +1,76 @@
+use super::*;
+
+const STATUS_ADDR: u16 = 0x6000;
+const MAGIC_ADDR: u16 = 0x6001;
+const TEXT_ADDR: u16 = 0x6004;
+const MAGIC: [u8; 3] = [0xDE, 0xB0, 0x61];
+
+const STATUS_RUNNING: u8 = 0x80;
+const STATUS_RESET_REQUIRED: u8 = 0x81;
+// the ROM asks to wait at least 100ms before pressing reset
+const RESET_DELAY_FRAMES: usize = 8;
+const MAX_FRAMES: usize = 60 * 60;
+
+/*
+ * Runs a ROM reporting through the $6000 protocol: the status at $6000 is valid once the magic
+ * bytes DE B0 61 are written at $6001-$6003, it is $80 while the test runs, $81 when it needs
+ * the reset button and the result code when it's done (0 is success). The text at $6004 is
+ * printed, and is the panic message when the test fails.
+ */
+pub fn run_blargg_test(rom_path: &str) {
+    let mut emulator = build_emulator(rom_path);
+    for _ in 0..MAX_FRAMES {
+        emulator.run_frame(Emulator::clock);
+        if !has_magic(&emulator) {
+            continue;
+        }
+        match emulator.peek_memory(STATUS_ADDR) {
+            STATUS_RUNNING => {}
+            STATUS_RESET_REQUIRED => {
+                for _ in 0..RESET_DELAY_FRAMES {
+                    emulator.run_frame(Emulator::clock);
+                }
+                emulator.reset();
+            }
+            status => {
+                let text = text_output(&emulator);
+                println!("{}", text);
+                if status != 0 {
+                    panic!("Failed with status {:02X}: {}", status, text.trim());
+                }
+                return;
+            }
+        }
+    }
+    panic!(
+        "Exceeded {} frames, text so far: {}",
+        MAX_FRAMES,
+        text_output(&emulator).trim()
+    );
+}
+
+fn has_magic(emulator: &Emulator) -> bool {
+    (0..MAGIC.len()).all(|idx| emulator.peek_memory(MAGIC_ADDR + idx as u16) == MAGIC[idx])
+}
+
+fn text_output(emulator: &Emulator) -> String {
+    (TEXT_ADDR..)
+        .map_while(|addr| match emulator.peek_memory(addr) {
+            0 => None,
+            val => Some(char::from(val)),
+        })
+        .collect()
+}
+
+// one ignored test per ROM: `test_name: "path/to/rom.nes",`
+macro_rules! blargg_tests {
+    ($($name:ident: $rom_path:literal,)*) => {
+        $(
+            #[test]
+            #[ignore]
+            fn $name() {
+                blargg::run_blargg_test($rom_path);
+            }
+        )*
+    };
+}
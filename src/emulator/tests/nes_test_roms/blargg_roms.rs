@@ -0,0 +1,59 @@
+use super::*;
+
+blargg_tests! {
+    instr_basics: "instr_test-v5/rom_singles/01-basics.nes",
+    instr_implied: "instr_test-v5/rom_singles/02-implied.nes",
+    instr_immediate: "instr_test-v5/rom_singles/03-immediate.nes",
+    instr_zero_page: "instr_test-v5/rom_singles/04-zero_page.nes",
+    instr_zp_xy: "instr_test-v5/rom_singles/05-zp_xy.nes",
+    instr_absolute: "instr_test-v5/rom_singles/06-absolute.nes",
+    instr_abs_xy: "instr_test-v5/rom_singles/07-abs_xy.nes",
+    instr_ind_x: "instr_test-v5/rom_singles/08-ind_x.nes",
+    instr_ind_y: "instr_test-v5/rom_singles/09-ind_y.nes",
+    instr_branches: "instr_test-v5/rom_singles/10-branches.nes",
+    instr_stack: "instr_test-v5/rom_singles/11-stack.nes",
+    instr_jmp_jsr: "instr_test-v5/rom_singles/12-jmp_jsr.nes",
+    instr_rts: "instr_test-v5/rom_singles/13-rts.nes",
+    instr_rti: "instr_test-v5/rom_singles/14-rti.nes",
+    instr_brk: "instr_test-v5/rom_singles/15-brk.nes",
+    instr_special: "instr_test-v5/rom_singles/16-special.nes",
+
+    instr_misc_abs_x_wrap: "instr_misc/rom_singles/01-abs_x_wrap.nes",
+    instr_misc_branch_wrap: "instr_misc/rom_singles/02-branch_wrap.nes",
+    instr_misc_dummy_reads: "instr_misc/rom_singles/03-dummy_reads.nes",
+    instr_misc_dummy_reads_apu: "instr_misc/rom_singles/04-dummy_reads_apu.nes",
+
+    instr_timing: "instr_timing/rom_singles/1-instr_timing.nes",
+    branch_timing: "instr_timing/rom_singles/2-branch_timing.nes",
+
+    cpu_interrupts_cli_latency: "cpu_interrupts_v2/rom_singles/1-cli_latency.nes",
+    cpu_interrupts_nmi_and_brk: "cpu_interrupts_v2/rom_singles/2-nmi_and_brk.nes",
+    cpu_interrupts_nmi_and_irq: "cpu_interrupts_v2/rom_singles/3-nmi_and_irq.nes",
+    cpu_interrupts_irq_and_dma: "cpu_interrupts_v2/rom_singles/4-irq_and_dma.nes",
+    cpu_interrupts_branch_delays_irq: "cpu_interrupts_v2/rom_singles/5-branch_delays_irq.nes",
+
+    cpu_reset_registers: "cpu_reset/registers.nes",
+    cpu_reset_ram_after_reset: "cpu_reset/ram_after_reset.nes",
+
+    apu_len_ctr: "apu_test/rom_singles/1-len_ctr.nes",
+    apu_len_table: "apu_test/rom_singles/2-len_table.nes",
+    apu_irq_flag: "apu_test/rom_singles/3-irq_flag.nes",
+    apu_jitter: "apu_test/rom_singles/4-jitter.nes",
+    apu_len_timing: "apu_test/rom_singles/5-len_timing.nes",
+    apu_irq_flag_timing: "apu_test/rom_singles/6-irq_flag_timing.nes",
+    apu_dmc_basics: "apu_test/rom_singles/7-dmc_basics.nes",
+    apu_dmc_rates: "apu_test/rom_singles/8-dmc_rates.nes",
+
+    ppu_open_bus: "ppu_open_bus/ppu_open_bus.nes",
+    ppu_read_buffer: "ppu_read_buffer/test_ppuread.nes",
+    oam_read: "oam_read/oam_read.nes",
+    oam_stress: "oam_stress/oam_stress.nes",
+    sprdma_and_dmc_dma: "sprdma_and_dmc_dma/sprdma_and_dmc_dma.nes",
+
+    mmc3_clocking: "mmc3_test_2/rom_singles/1-clocking.nes",
+    mmc3_details: "mmc3_test_2/rom_singles/2-details.nes",
+    mmc3_a12_clocking: "mmc3_test_2/rom_singles/3-A12_clocking.nes",
+    mmc3_scanline_timing: "mmc3_test_2/rom_singles/4-scanline_timing.nes",
+    mmc3_mmc3: "mmc3_test_2/rom_singles/5-MMC3.nes",
+    mmc3_mmc3_alt: "mmc3_test_2/rom_singles/6-MMC3_alt.nes",
+}
@@ -1,4 +1,7 @@
+#[macro_use]
+mod blargg;
 mod blargg_ppu_tests;
+mod blargg_roms;
 mod ppu_vbl_nmi;
 mod sprite_hit_tests;
 
@@ -10,7 +13,7 @@ const MAX_CYCLES: u64 = 100_000_000;
 
 fn build_emulator(rom_path: &str) -> Emulator {
     println!("Building console for {}", rom_path);
-    let cartridge = cartridge::open_rom(&test_roms_path(rom_path));
+    let cartridge = cartridge::open_rom(&test_roms_path(rom_path)).unwrap();
     Emulator::new(cartridge)
 }
 
@@ -10,9 +10,27 @@ pub struct TimeMachine {
     cartridge: cartridge::TimeMachine,
     oam_dma: oam_dma::OamDma,
     dmc_dma: dmc_dma::DmcDma,
+    cheats: cheats::Cheats,
+    lag_frames: usize,
+    lagged: bool,
     cycle: usize,
 }
 
+// the cheats come from the command line, a loaded state keeps the current ones
+state!(TimeMachine {
+    cpu_mem,
+    cpu,
+    ppu_mem,
+    ppu,
+    apu,
+    cartridge,
+    oam_dma,
+    dmc_dma,
+    lag_frames,
+    lagged,
+    cycle,
+});
+
 impl TimeMachine {
     pub fn save(emu: &Emulator) -> Self {
         Self {
@@ -24,6 +42,9 @@ impl TimeMachine {
             cartridge: cartridge::TimeMachine::save(&emu.cartridge.borrow()),
             oam_dma: emu.oam_dma.clone(),
             dmc_dma: emu.dmc_dma.clone(),
+            cheats: emu.cheats.clone(),
+            lag_frames: emu.lag_frames,
+            lagged: emu.lagged,
             cycle: emu.cycle,
         }
     }
@@ -37,6 +58,9 @@ impl TimeMachine {
         self.cartridge.load(&mut emu.cartridge.borrow_mut());
         emu.oam_dma = self.oam_dma;
         emu.dmc_dma = self.dmc_dma;
+        emu.set_cheats(self.cheats);
+        emu.lag_frames = self.lag_frames;
+        emu.lagged = self.lagged;
         emu.cycle = self.cycle;
     }
 }
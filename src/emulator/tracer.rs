@@ -0,0 +1,144 @@
+use super::*;
+
+use std::io::Write;
+use std::ops::RangeInclusive;
+
+// when set, only the instructions matching all the conditions are logged
+#[derive(Debug, Default, Clone)]
+pub struct TraceCondition {
+    pub pc: Option<RangeInclusive<u16>>,
+    pub frames: Option<RangeInclusive<usize>>,
+}
+
+impl TraceCondition {
+    fn matches(&self, pc: u16, frame: usize) -> bool {
+        self.pc.as_ref().is_none_or(|range| range.contains(&pc))
+            && self
+                .frames
+                .as_ref()
+                .is_none_or(|range| range.contains(&frame))
+    }
+}
+
+/*
+ * Writes one line per executed instruction in the nestest.log format, so the output can be
+ * compared against the logs of other emulators.
+ */
+pub struct Tracer {
+    output: Box<dyn Write>,
+    condition: TraceCondition,
+}
+
+impl Tracer {
+    pub fn new(output: Box<dyn Write>, condition: TraceCondition) -> Self {
+        Self { output, condition }
+    }
+
+    pub fn create(path: &std::path::Path, condition: TraceCondition) -> std::io::Result<Self> {
+        log!("Writing trace to: {:?}", path);
+        let file = std::fs::File::create(path)?;
+        Ok(Self::new(
+            Box::new(std::io::BufWriter::new(file)),
+            condition,
+        ))
+    }
+
+    fn trace(&mut self, cpu: &Cpu, ppu: &Rc<RefCell<Ppu>>) {
+        // the PPU must not be borrowed while formatting, the annotations read from the bus
+        let (scanline, dot, frame) = {
+            let ppu = ppu.borrow();
+            (ppu.scanline, ppu.dot, ppu.frame)
+        };
+        if !self.condition.matches(cpu.pc, frame) {
+            return;
+        }
+
+        let mem = debugger::BusMemory(&cpu.mem);
+        let line = cpu::trace::nestest_line(cpu, &mem, scanline, dot);
+        writeln!(self.output, "{}", line).expect("Failed to write trace.");
+    }
+}
+
+impl Emulator {
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        self.cpu.trace_hook = tracer.map(|mut tracer| {
+            let ppu = self.ppu.0.clone();
+            Box::new(move |cpu: &Cpu| tracer.trace(cpu, &ppu)) as cpu::TraceHook<bus::Bus>
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn mk_emulator() -> Emulator {
+        // $8000 LDX #$02; $8002 INX; $8003 JMP $8002
+        Emulator::new(cartridge::Cartridge::with_program(&[(
+            0x8000,
+            &[0xA2, 0x02, 0xE8, 0x4C, 0x02, 0x80],
+        )]))
+    }
+
+    #[test]
+    fn test_trace() {
+        let mut emu = mk_emulator();
+        let output = SharedOutput::default();
+        emu.set_tracer(Some(Tracer::new(
+            Box::new(output.clone()),
+            TraceCondition::default(),
+        )));
+        for _ in 0..12 * 7 {
+            emu.clock();
+        }
+
+        let output = String::from_utf8(output.0.borrow().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("8000  A2 02     LDX #$02 "));
+        assert!(lines[0].ends_with("PPU:  0,  0 CYC:0"));
+        assert!(lines[1].starts_with("8002  E8        INX "));
+        assert!(lines[1].contains("X:02"));
+        assert!(lines[2].starts_with("8003  4C 02 80  JMP $8002 "));
+    }
+
+    #[test]
+    fn test_trace_condition() {
+        let mut emu = mk_emulator();
+        let output = SharedOutput::default();
+        let condition = TraceCondition {
+            pc: Some(0x8002..=0x8002),
+            frames: None,
+        };
+        emu.set_tracer(Some(Tracer::new(Box::new(output.clone()), condition)));
+        for _ in 0..12 * 20 {
+            emu.clock();
+        }
+        emu.set_tracer(None);
+
+        let output = String::from_utf8(output.0.borrow().clone()).unwrap();
+        assert!(output.lines().count() > 1);
+        assert!(output.lines().all(|line| line.starts_with("8002 ")));
+
+        let condition = TraceCondition {
+            pc: None,
+            frames: Some(1..=1),
+        };
+        assert!(!condition.matches(0x8000, 0));
+        assert!(condition.matches(0x8000, 1));
+        assert!(!condition.matches(0x8000, 2));
+    }
+}
@@ -1,13 +1,55 @@
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub struct Signal {
-    pub x: usize,
-    pub y: usize,
-    pub color: Color,
-}
+pub const SCREEN_WIDTH: usize = 256;
+pub const SCREEN_HEIGHT: usize = 240;
+
+// how much each emphasis bit darkens the other two channels
+const EMPHASIS_ATTENUATION: f32 = 0.816;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Color(pub u8, pub u8, pub u8);
 
+impl Color {
+    // pixel is a PPU output: palette index in the bits 0-5 and the emphasis in the bits 6-8
+    pub fn from_pixel(palette: &[Color; 64], pixel: u16) -> Self {
+        let Color(r, g, b) = palette[pixel as usize & 0x3F];
+        let mut channels = [r as f32, g as f32, b as f32];
+        for emphasized in 0..3 {
+            if pixel & (1 << (6 + emphasized)) != 0 {
+                channels
+                    .iter_mut()
+                    .enumerate()
+                    .filter(|(channel, _)| *channel != emphasized)
+                    .for_each(|(_, val)| *val *= EMPHASIS_ATTENUATION);
+            }
+        }
+        Color(channels[0] as u8, channels[1] as u8, channels[2] as u8)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color(0, 0, 0); width * height],
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Color) {
+        self.pixels[y * self.width + x] = color;
+    }
+}
+
 pub static DEFAULT_PALETTE: [Color; 64] = [
     Color(0x62, 0x62, 0x62),
     Color(0x0D, 0x22, 0x6B),
@@ -74,3 +116,26 @@ pub static DEFAULT_PALETTE: [Color; 64] = [
     Color(0x00, 0x00, 0x00),
     Color(0x00, 0x00, 0x00),
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_pixel() {
+        assert_eq!(
+            Color::from_pixel(&DEFAULT_PALETTE, 0x20),
+            Color(0xFF, 0xFF, 0xFF)
+        );
+        assert_eq!(
+            Color::from_pixel(&DEFAULT_PALETTE, 0x20 | 0b001 << 6),
+            Color(0xFF, 0xD0, 0xD0),
+            "red emphasis"
+        );
+        assert_eq!(
+            Color::from_pixel(&DEFAULT_PALETTE, 0x20 | 0b110 << 6),
+            Color(0xA9, 0xD0, 0xD0),
+            "green and blue emphasis"
+        );
+    }
+}
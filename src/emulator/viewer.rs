@@ -0,0 +1,247 @@
+use super::*;
+
+use ppu::debugger::{NAMETABLES_HEIGHT, NAMETABLES_WIDTH, PATTERN_TABLE_SIZE};
+
+const SCROLL_OVERLAY_COLOR: Color = Color(0xFF, 0x00, 0xFF);
+const PALETTE_SWATCH_SIZE: usize = 16;
+const PALETTE_COLUMNS: usize = 16;
+
+/*
+ * Images of the PPU memory for the debugging viewers.
+ */
+impl Emulator {
+    // all four nametables, optionally outlining the area shown on the screen
+    pub fn nametables_image(&self, scroll_overlay: bool) -> Image {
+        let ppu = self.ppu.as_ref();
+        let debugger = ppu.debugger();
+        let mut image = self.to_image(NAMETABLES_WIDTH, NAMETABLES_HEIGHT, &debugger.nametables());
+
+        if scroll_overlay {
+            let (scroll_x, scroll_y) = debugger.scroll();
+            let mut plot = |x: usize, y: usize| {
+                image.set(
+                    (scroll_x + x) % NAMETABLES_WIDTH,
+                    (scroll_y + y) % NAMETABLES_HEIGHT,
+                    SCROLL_OVERLAY_COLOR,
+                )
+            };
+            for x in 0..SCREEN_WIDTH {
+                plot(x, 0);
+                plot(x, SCREEN_HEIGHT - 1);
+            }
+            for y in 0..SCREEN_HEIGHT {
+                plot(0, y);
+                plot(SCREEN_WIDTH - 1, y);
+            }
+        }
+        image
+    }
+
+    // both pattern tables side by side, the palettes 0-3 are the background ones and 4-7 the sprite ones
+    pub fn pattern_tables_image(&self, palette: u8) -> Image {
+        let ppu = self.ppu.as_ref();
+        let debugger = ppu.debugger();
+        let mut image = Image::new(PATTERN_TABLE_SIZE * 2, PATTERN_TABLE_SIZE);
+        for table in 0..2 {
+            let pixels = debugger.pattern_table(table as u16, palette & 0x07);
+            for (idx, color_idx) in pixels.into_iter().enumerate() {
+                image.set(
+                    table * PATTERN_TABLE_SIZE + idx % PATTERN_TABLE_SIZE,
+                    idx / PATTERN_TABLE_SIZE,
+                    self.color(color_idx),
+                );
+            }
+        }
+        image
+    }
+
+    // the background palettes in the first row and the sprite palettes in the second one
+    pub fn palettes_image(&self) -> Image {
+        let palettes = self.ppu.as_ref().debugger().palettes();
+        let rows = palettes.len() / PALETTE_COLUMNS;
+        let mut image = Image::new(
+            PALETTE_COLUMNS * PALETTE_SWATCH_SIZE,
+            rows * PALETTE_SWATCH_SIZE,
+        );
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let idx = (y / PALETTE_SWATCH_SIZE) * PALETTE_COLUMNS + x / PALETTE_SWATCH_SIZE;
+                image.set(x, y, self.color(palettes[idx]));
+            }
+        }
+        image
+    }
+
+    // the 64 sprites in OAM order, with their attributes
+    pub fn oam(&self) -> Vec<OamEntry> {
+        self.ppu.as_ref().debugger().oam()
+    }
+
+    // a sprite as it is shown on the screen, 8x8 or 8x16
+    pub fn sprite_image(&self, entry: &OamEntry) -> Image {
+        let ppu = self.ppu.as_ref();
+        let debugger = ppu.debugger();
+        self.to_image(8, debugger.sprite_height(), &debugger.sprite(entry))
+    }
+
+    fn to_image(&self, width: usize, height: usize, color_indices: &[u8]) -> Image {
+        Image {
+            width,
+            height,
+            pixels: color_indices.iter().map(|&idx| self.color(idx)).collect(),
+        }
+    }
+
+    fn color(&self, color_idx: u8) -> Color {
+        self.color_palette[color_idx as usize & 0x3F]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mk_emulator() -> Emulator {
+        let mut prg_data = vec![0xEA; 0x8000];
+        prg_data[0x7FFC..0x7FFE].copy_from_slice(&[0x00, 0x80]);
+        // tile 1 is filled with the color 1, tile 2 has the color 3 in its top left pixel
+        let mut chr_data = vec![0x00; 0x2000];
+        chr_data[0x0010..0x0018].fill(0xFF);
+        chr_data[0x0020] = 0x80;
+        chr_data[0x0028] = 0x80;
+        let cartridge = cartridge::Cartridge::new(
+            cartridge::RomInfo::default(),
+            cartridge::CartridgeData {
+                prg_banks: 2,
+                prg_data,
+                chr_banks: 1,
+                chr_data,
+                ..Default::default()
+            },
+        );
+        let emu = Emulator::new(cartridge);
+        {
+            let mut ppu = emu.ppu.0.borrow_mut();
+            for (idx, color) in [0x0F, 0x01, 0x02, 0x03, 0x0F, 0x11, 0x12, 0x13]
+                .into_iter()
+                .enumerate()
+            {
+                ppu.mem.write(0x3F00 + idx as u16, color);
+            }
+            for (idx, color) in [0x21, 0x22, 0x23].into_iter().enumerate() {
+                ppu.mem.write(0x3F11 + idx as u16, color);
+            }
+        }
+        emu
+    }
+
+    #[test]
+    fn test_nametables() {
+        let mut emu = mk_emulator();
+        {
+            let mut ppu = emu.ppu.as_mut();
+            ppu.mem.write(0x2000, 0x01);
+            ppu.mem.write(0x2021, 0x02);
+            ppu.mem.write(0x23C0, 0x01); // top left quadrant uses the palette 1
+        }
+        let image = emu.nametables_image(false);
+        assert_eq!((image.width, image.height), (512, 480));
+        assert_eq!(image.get(0, 0), video::DEFAULT_PALETTE[0x11]);
+        assert_eq!(image.get(8, 8), video::DEFAULT_PALETTE[0x13]);
+        assert_eq!(image.get(9, 8), video::DEFAULT_PALETTE[0x0F]);
+
+        emu.ppu.as_mut().io_ports().write(0x05, 0x10);
+        emu.ppu.as_mut().io_ports().write(0x05, 0x08);
+        let image = emu.nametables_image(true);
+        assert_eq!(image.get(0x10, 0x08), SCROLL_OVERLAY_COLOR);
+        assert_eq!(image.get(0x10 + 255, 0x08 + 239), SCROLL_OVERLAY_COLOR);
+        assert_ne!(image.get(0x11, 0x09), SCROLL_OVERLAY_COLOR);
+    }
+
+    #[test]
+    fn test_pattern_tables() {
+        let emu = mk_emulator();
+        let image = emu.pattern_tables_image(0);
+        assert_eq!((image.width, image.height), (256, 128));
+        assert_eq!(image.get(8, 0), video::DEFAULT_PALETTE[0x01]);
+        assert_eq!(image.get(16, 0), video::DEFAULT_PALETTE[0x03]);
+        assert_eq!(image.get(17, 0), video::DEFAULT_PALETTE[0x0F]);
+        let image = emu.pattern_tables_image(4);
+        assert_eq!(image.get(8, 0), video::DEFAULT_PALETTE[0x21]);
+    }
+
+    #[test]
+    fn test_palettes() {
+        let emu = mk_emulator();
+        let image = emu.palettes_image();
+        assert_eq!((image.width, image.height), (256, 32));
+        assert_eq!(image.get(16, 0), video::DEFAULT_PALETTE[0x01]);
+        assert_eq!(image.get(16, 16), video::DEFAULT_PALETTE[0x21]);
+    }
+
+    #[test]
+    fn test_sprites() {
+        let mut emu = mk_emulator();
+        {
+            let mut ppu = emu.ppu.as_mut();
+            let mut io_ports = ppu.io_ports();
+            io_ports.write(0x03, 0x04); // second sprite
+            for val in [0x20, 0x02, 0b1100_0000, 0x30] {
+                io_ports.write(0x04, val);
+            }
+        }
+        assert_eq!(
+            emu.oam()[1],
+            OamEntry {
+                x: 0x30,
+                y: 0x20,
+                tile: 0x02,
+                palette: 0,
+                behind: false,
+                flip_h: true,
+                flip_v: true,
+            }
+        );
+
+        let image = emu.sprite_image(&emu.oam()[1]);
+        assert_eq!((image.width, image.height), (8, 8));
+        // the top left pixel of the tile is flipped to the bottom right
+        assert_eq!(image.get(7, 7), video::DEFAULT_PALETTE[0x23]);
+        assert_eq!(image.get(0, 0), video::DEFAULT_PALETTE[0x0F]);
+    }
+
+    #[test]
+    fn test_mmc3_irq_untouched() {
+        let mut prg_data = vec![0xEA; 0x8000];
+        prg_data[0x7FFC..0x7FFE].copy_from_slice(&[0x00, 0x80]);
+        let cartridge = cartridge::Cartridge::new(
+            cartridge::RomInfo::default(),
+            cartridge::CartridgeData {
+                mapper_code: 4,
+                prg_banks: 2,
+                prg_data,
+                chr_banks: 1,
+                chr_data: vec![0x00; 0x2000],
+                ..Default::default()
+            },
+        );
+        let mut emu = Emulator::new(cartridge);
+        emu.write_memory(0xC000, 2); // latch
+        emu.write_memory(0xC001, 0); // reload the counter
+        emu.write_memory(0xE001, 0); // enable the IRQ
+
+        emu.nametables_image(true);
+        emu.pattern_tables_image(0);
+        for entry in emu.oam() {
+            emu.sprite_image(&entry);
+        }
+
+        // the counter is reloaded from the latch on the first A12 rise and fires on the third
+        let mut cartridge = emu.cartridge.borrow_mut();
+        for rise in 1..=6 {
+            cartridge.read_chr(0x0000);
+            cartridge.read_chr(0x1000);
+            assert_eq!(cartridge.take_irq(), rise % 3 == 0, "A12 rise {}", rise);
+        }
+    }
+}
@@ -0,0 +1,509 @@
+use std::io::{Read, Write};
+
+use crate::emulator::{
+    debugger::{Access, Debugger, Registers, Run, Stop},
+    Emulator,
+};
+
+const INTERRUPT: u8 = 0x03;
+const PACKET_SIZE: usize = 0x1000;
+// the bytes of an `m` reply, two hex digits each, fitting in a packet with its $ and #xx
+const MAX_MEMORY_READ: usize = (PACKET_SIZE - 4) / 2;
+const REGISTER_COUNT: usize = 6;
+const PC_REGISTER: usize = 5;
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.sunrest.6502">
+    <reg name="a" bitsize="8" type="uint8"/>
+    <reg name="x" bitsize="8" type="uint8"/>
+    <reg name="y" bitsize="8" type="uint8"/>
+    <reg name="p" bitsize="8" type="uint8"/>
+    <reg name="sp" bitsize="8" type="uint8"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+pub trait Connection: Read + Write {
+    fn set_nonblocking(&self, nonblocking: bool) -> std::io::Result<()>;
+}
+
+impl Connection for std::net::TcpStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> std::io::Result<()> {
+        std::net::TcpStream::set_nonblocking(self, nonblocking)
+    }
+}
+
+#[cfg(unix)]
+impl Connection for std::os::unix::net::UnixStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> std::io::Result<()> {
+        std::os::unix::net::UnixStream::set_nonblocking(self, nonblocking)
+    }
+}
+
+/*
+ * Waits for a single GDB connection on a TCP address (host:port) or on a Unix socket
+ * (unix:path) and serves it until the client detaches.
+ */
+pub fn serve(emulator: Emulator, address: &str) -> Result<(), String> {
+    let bind_error = |e| format!("Failed to listen on {}: {}", address, e);
+    let accept_error = |e| format!("Failed to accept the GDB connection: {}", e);
+    let write_error = |e| format!("Failed to write to GDB: {}", e);
+    if let Some(path) = address.strip_prefix("unix:") {
+        #[cfg(unix)]
+        {
+            let _ = std::fs::remove_file(path);
+            let listener = std::os::unix::net::UnixListener::bind(path).map_err(bind_error)?;
+            println!("Waiting for GDB on {}", path);
+            let result = listener
+                .accept()
+                .map_err(accept_error)
+                .and_then(|(conn, _)| GdbStub::new(emulator, conn).run().map_err(write_error));
+            let _ = std::fs::remove_file(path);
+            result
+        }
+        #[cfg(not(unix))]
+        Err(format!("Unix sockets are not supported: {}", path))
+    } else {
+        let listener = std::net::TcpListener::bind(address).map_err(bind_error)?;
+        println!("Waiting for GDB on {}", address);
+        let (conn, _) = listener.accept().map_err(accept_error)?;
+        conn.set_nodelay(true).ok();
+        GdbStub::new(emulator, conn).run().map_err(write_error)
+    }
+}
+
+// what the client sent while the emulation was running
+enum Poll {
+    Nothing,
+    Interrupt,
+    Disconnected,
+}
+
+/*
+ * GDB remote serial protocol server. The registers are, in order, A, X, Y, P, SP (8 bits) and
+ * PC (16 bits, little endian). The execution only advances when GDB asks for a step/continue.
+ */
+pub struct GdbStub<C: Connection> {
+    emulator: Emulator,
+    debugger: Debugger,
+    conn: C,
+    // the client went away while the emulation was running
+    disconnected: bool,
+}
+
+impl<C: Connection> GdbStub<C> {
+    pub fn new(emulator: Emulator, conn: C) -> Self {
+        Self {
+            emulator,
+            debugger: Debugger::new(),
+            conn,
+            disconnected: false,
+        }
+    }
+
+    // until the client kills, detaches or disconnects
+    pub fn run(&mut self) -> std::io::Result<()> {
+        while let Some(packet) = self.read_packet() {
+            let reply = match packet.as_str() {
+                "k" => break,
+                "D" => return self.write_packet("OK"),
+                _ => self.handle(&packet),
+            };
+            if self.disconnected {
+                break;
+            }
+            self.write_packet(&reply)?;
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, packet: &str) -> String {
+        let (command, args) = packet.split_at(1.min(packet.len()));
+        let reply = match command {
+            "?" => Some("S05".to_string()),
+            "g" => Some(self.read_registers()),
+            "G" => self.write_registers(args),
+            "p" => self.read_register(args),
+            "P" => self.write_register(args),
+            "m" => self.peek_memory(args),
+            "M" => self.write_memory(args),
+            "Z" => self.set_breakpoint(args, true),
+            "z" => self.set_breakpoint(args, false),
+            "c" => self.resume(Run::Continue, args),
+            "s" => self.resume(Run::Step, args),
+            "H" => Some("OK".to_string()),
+            "q" => Some(self.query(args)),
+            _ => Some(String::new()),
+        };
+        reply.unwrap_or_else(|| "E01".to_string())
+    }
+
+    fn query(&self, query: &str) -> String {
+        if query.starts_with("Supported") {
+            format!("PacketSize={:x};qXfer:features:read+", PACKET_SIZE)
+        } else if let Some(range) = query.strip_prefix("Xfer:features:read:target.xml:") {
+            let Some((offset, len)) = parse_addr_len(range) else {
+                return "E01".to_string();
+            };
+            let offset = (offset as usize).min(TARGET_XML.len());
+            let end = offset.saturating_add(len).min(TARGET_XML.len());
+            let marker = if end == TARGET_XML.len() { 'l' } else { 'm' };
+            format!("{}{}", marker, &TARGET_XML[offset..end])
+        } else {
+            match query {
+                "Attached" => "1".to_string(),
+                "C" => "QC1".to_string(),
+                "fThreadInfo" => "m1".to_string(),
+                "sThreadInfo" => "l".to_string(),
+                _ => String::new(),
+            }
+        }
+    }
+
+    fn read_registers(&self) -> String {
+        let regs = self.emulator.registers();
+        (0..REGISTER_COUNT)
+            .map(|i| register_hex(&regs, i))
+            .collect()
+    }
+
+    fn write_registers(&mut self, hex: &str) -> Option<String> {
+        let bytes = decode_hex(hex)?;
+        let [a, x, y, p, sp, pc_lo, pc_hi] = bytes[..] else {
+            return None;
+        };
+        self.emulator.set_registers(Registers {
+            a,
+            x,
+            y,
+            p,
+            sp,
+            pc: u16::from_le_bytes([pc_lo, pc_hi]),
+        });
+        Some("OK".to_string())
+    }
+
+    fn read_register(&self, args: &str) -> Option<String> {
+        let reg = usize::from_str_radix(args, 16).ok()?;
+        (reg < REGISTER_COUNT).then(|| register_hex(&self.emulator.registers(), reg))
+    }
+
+    fn write_register(&mut self, args: &str) -> Option<String> {
+        let (reg, val) = args.split_once('=')?;
+        let reg = usize::from_str_radix(reg, 16).ok()?;
+        let bytes = decode_hex(val)?;
+        let mut regs = self.emulator.registers();
+        match (reg, bytes.as_slice()) {
+            (0, &[val]) => regs.a = val,
+            (1, &[val]) => regs.x = val,
+            (2, &[val]) => regs.y = val,
+            (3, &[val]) => regs.p = val,
+            (4, &[val]) => regs.sp = val,
+            (PC_REGISTER, &[lo, hi]) => regs.pc = u16::from_le_bytes([lo, hi]),
+            _ => return None,
+        }
+        self.emulator.set_registers(regs);
+        Some("OK".to_string())
+    }
+
+    fn peek_memory(&self, args: &str) -> Option<String> {
+        let (addr, len) = parse_addr_len(args)?;
+        // a shorter reply is allowed, GDB asks for the rest
+        Some(
+            (0..len.min(MAX_MEMORY_READ))
+                .map(|i| {
+                    format!(
+                        "{:02x}",
+                        self.emulator.peek_memory(addr.wrapping_add(i as u16))
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    fn write_memory(&mut self, args: &str) -> Option<String> {
+        let (range, data) = args.split_once(':')?;
+        let (addr, len) = parse_addr_len(range)?;
+        let bytes = decode_hex(data)?;
+        if bytes.len() != len {
+            return None;
+        }
+        for (i, val) in bytes.into_iter().enumerate() {
+            self.emulator.write_memory(addr.wrapping_add(i as u16), val);
+        }
+        Some("OK".to_string())
+    }
+
+    // Z<type>,<addr>,<kind>
+    fn set_breakpoint(&mut self, args: &str, enabled: bool) -> Option<String> {
+        let mut parts = args.split(',');
+        let kind = parts.next()?;
+        let addr = u16::from_str_radix(parts.next()?, 16).ok()?;
+        match kind {
+            "0" | "1" => self.debugger.set_breakpoint(addr, enabled),
+            "2" => self.emulator.set_watchpoint(Access::Write, addr, enabled),
+            "3" => self.emulator.set_watchpoint(Access::Read, addr, enabled),
+            "4" => {
+                self.emulator.set_watchpoint(Access::Write, addr, enabled);
+                self.emulator.set_watchpoint(Access::Read, addr, enabled);
+            }
+            _ => return Some(String::new()),
+        }
+        Some("OK".to_string())
+    }
+
+    fn resume(&mut self, run: Run, args: &str) -> Option<String> {
+        if !args.is_empty() {
+            let mut regs = self.emulator.registers();
+            regs.pc = u16::from_str_radix(args, 16).ok()?;
+            self.emulator.set_registers(regs);
+        }
+
+        // a continue runs one frame at a time, so GDB can interrupt it
+        let run = if run == Run::Continue {
+            Run::Frame
+        } else {
+            run
+        };
+        loop {
+            let stop = self.debugger.run(&mut self.emulator, run);
+            let reply = match stop {
+                Stop::Frame(_) => match self.poll() {
+                    Poll::Nothing => continue,
+                    Poll::Interrupt => "S02".to_string(),
+                    Poll::Disconnected => {
+                        self.disconnected = true;
+                        String::new()
+                    }
+                },
+                Stop::Watchpoint(Access::Write, addr) => format!("T05watch:{:x};", addr),
+                Stop::Watchpoint(Access::Read, addr) => format!("T05rwatch:{:x};", addr),
+                Stop::Breakpoint(_) => "T05swbreak:;".to_string(),
+                Stop::Step | Stop::Irq | Stop::Nmi | Stop::Scanline(_) => "S05".to_string(),
+            };
+            return Some(reply);
+        }
+    }
+
+    fn poll(&mut self) -> Poll {
+        self.conn.set_nonblocking(true).ok();
+        let mut byte = [0];
+        let poll = match self.conn.read(&mut byte) {
+            Ok(0) => Poll::Disconnected,
+            Ok(_) if byte[0] == INTERRUPT => Poll::Interrupt,
+            Ok(_) => Poll::Nothing,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Poll::Nothing,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => Poll::Nothing,
+            Err(_) => Poll::Disconnected,
+        };
+        self.conn.set_nonblocking(false).ok();
+        poll
+    }
+
+    fn read_byte(&mut self) -> Option<u8> {
+        let mut byte = [0];
+        match self.conn.read(&mut byte) {
+            Ok(1) => Some(byte[0]),
+            _ => None,
+        }
+    }
+
+    // $<data>#<checksum>, the acknowledgments from GDB and stray interrupts are ignored
+    fn read_packet(&mut self) -> Option<String> {
+        loop {
+            while self.read_byte()? != b'$' {}
+
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    b'#' => break,
+                    byte => data.push(byte),
+                }
+            }
+            let checksum = [self.read_byte()?, self.read_byte()?];
+            let checksum = std::str::from_utf8(&checksum).ok();
+            let checksum = checksum.and_then(|cs| u8::from_str_radix(cs, 16).ok());
+
+            if checksum == Some(checksum_of(&data)) {
+                self.conn.write_all(b"+").ok()?;
+                return Some(String::from_utf8_lossy(&data).to_string());
+            }
+            self.conn.write_all(b"-").ok()?;
+        }
+    }
+
+    fn write_packet(&mut self, data: &str) -> std::io::Result<()> {
+        let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+        self.conn.write_all(packet.as_bytes())?;
+        self.conn.flush()
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn register_hex(regs: &Registers, reg: usize) -> String {
+    match reg {
+        0 => format!("{:02x}", regs.a),
+        1 => format!("{:02x}", regs.x),
+        2 => format!("{:02x}", regs.y),
+        3 => format!("{:02x}", regs.p),
+        4 => format!("{:02x}", regs.sp),
+        PC_REGISTER => regs
+            .pc
+            .to_le_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect(),
+        _ => unreachable!(),
+    }
+}
+
+// <addr>,<len>
+fn parse_addr_len(args: &str) -> Option<(u16, usize)> {
+    let (addr, len) = args.split_once(',')?;
+    let addr = u16::from_str_radix(addr, 16).ok()?;
+    let len = usize::from_str_radix(len, 16).ok()?;
+    Some((addr, len))
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| match pair {
+            [hi, lo] => u8::from_str_radix(std::str::from_utf8(&[*hi, *lo]).ok()?, 16).ok(),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::cartridge;
+    use std::net::{TcpListener, TcpStream};
+
+    // $8000 LDX #$02; $8002 INX; $8003 STX $10; $8005 JMP $8002
+    fn mk_emulator() -> Emulator {
+        Emulator::new(cartridge::Cartridge::with_program(&[(
+            0x8000,
+            &[0xA2, 0x02, 0xE8, 0x86, 0x10, 0x4C, 0x02, 0x80],
+        )]))
+    }
+
+    struct Client(TcpStream);
+
+    impl Client {
+        fn send(&mut self, data: &str) -> String {
+            let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+            self.0.write_all(packet.as_bytes()).unwrap();
+            self.receive()
+        }
+
+        fn receive(&mut self) -> String {
+            let mut reply = Vec::new();
+            let mut byte = [0];
+            loop {
+                self.0.read_exact(&mut byte).unwrap();
+                match byte[0] {
+                    b'+' if reply.is_empty() => (),
+                    b'#' => break,
+                    byte => reply.push(byte),
+                }
+            }
+            let mut checksum = [0; 2];
+            self.0.read_exact(&mut checksum).unwrap();
+            self.0.write_all(b"+").unwrap();
+            String::from_utf8(reply[1..].to_vec()).unwrap()
+        }
+    }
+
+    fn with_client(script: fn(&mut Client)) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = std::thread::spawn(move || {
+            let mut client = Client(TcpStream::connect(addr).unwrap());
+            script(&mut client);
+            assert_eq!(client.send("D"), "OK");
+        });
+
+        let (conn, _) = listener.accept().unwrap();
+        GdbStub::new(mk_emulator(), conn).run().unwrap();
+        client.join().unwrap();
+    }
+
+    #[test]
+    fn test_registers() {
+        with_client(|client| {
+            assert!(client
+                .send("qSupported:swbreak+")
+                .contains("qXfer:features:read+"));
+            assert_eq!(client.send("?"), "S05");
+            assert_eq!(client.send("g"), "00000024fd0080");
+            assert_eq!(client.send("s"), "S05");
+            assert_eq!(client.send("p1"), "02");
+            assert_eq!(client.send("p5"), "0280");
+            assert_eq!(client.send("P0=7f"), "OK");
+            assert_eq!(client.send("P5=0580"), "OK");
+            assert_eq!(client.send("g"), "7f020024fd0580");
+            assert_eq!(client.send("G010203240a0080"), "OK");
+            assert_eq!(client.send("g"), "010203240a0080");
+            assert_eq!(client.send("p9"), "E01");
+        });
+    }
+
+    #[test]
+    fn test_memory() {
+        with_client(|client| {
+            assert_eq!(client.send("m8000,3"), "a202e8");
+            assert_eq!(client.send("M0010,2:abcd"), "OK");
+            assert_eq!(client.send("m10,2"), "abcd");
+            assert_eq!(client.send("M0010,2:ab"), "E01");
+            assert_eq!(client.send("m0,ffffffff").len(), MAX_MEMORY_READ * 2);
+            let xml = client.send("qXfer:features:read:target.xml:0,fff");
+            assert!(xml.starts_with("l<?xml"));
+        });
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        with_client(|client| {
+            assert_eq!(client.send("Z0,8003,1"), "OK");
+            assert_eq!(client.send("c"), "T05swbreak:;");
+            assert_eq!(client.send("p5"), "0380");
+            assert_eq!(client.send("z0,8003,1"), "OK");
+
+            assert_eq!(client.send("Z2,10,1"), "OK");
+            assert_eq!(client.send("c"), "T05watch:10;");
+            assert_eq!(client.send("m10,1"), "03");
+            assert_eq!(client.send("z2,10,1"), "OK");
+        });
+    }
+
+    #[test]
+    fn test_interrupt() {
+        with_client(|client| {
+            client.0.write_all(b"$c#63").unwrap();
+            client.0.write_all(&[INTERRUPT]).unwrap();
+            assert_eq!(client.receive(), "S02");
+        });
+    }
+
+    #[test]
+    fn test_disconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = std::thread::spawn(move || {
+            let mut client = TcpStream::connect(addr).unwrap();
+            client.write_all(b"$c#63").unwrap();
+        });
+
+        let (conn, _) = listener.accept().unwrap();
+        client.join().unwrap();
+        let mut stub = GdbStub::new(mk_emulator(), conn);
+        assert!(stub.run().is_ok(), "a disconnection ends the session");
+    }
+}
@@ -0,0 +1,333 @@
+use std::path::{Path, PathBuf};
+
+use crate::emulator::{cartridge, Color, Emulator, Image};
+use crate::headless::Headless;
+use crate::joypad_cable::movie;
+use crate::screenshot;
+
+// a hash not recorded yet, the entry is run but never fails
+const UNRECORDED_HASH: &str = "-";
+const DIFF_COLOR: Color = Color(0xFF, 0x00, 0xFF);
+
+/*
+ * One line of the manifest: <rom> <frames> <hash> [input]
+ * The ROM is relative to the ROMs directory and the input (a movie recorded with --record) to
+ * the manifest directory. Empty lines and lines starting with # are ignored.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub rom: PathBuf,
+    pub frames: usize,
+    pub hash: Option<u64>,
+    pub input: Option<PathBuf>,
+}
+
+impl Entry {
+    // the file name of the reference image and of the failure outputs
+    pub fn name(&self) -> String {
+        let rom = self
+            .rom
+            .with_extension("")
+            .to_string_lossy()
+            .replace(['/', '\\'], "-");
+        format!("{}-{}", rom, self.frames)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub manifest: PathBuf,
+    pub roms_dir: Option<PathBuf>,
+    // where the actual and the diff images are written on failure
+    pub output_dir: PathBuf,
+    // records the hashes in the manifest and the reference images instead of checking them
+    pub update: bool,
+}
+
+#[derive(Debug)]
+pub enum Outcome {
+    Passed,
+    Unrecorded(u64),
+    Failed(String),
+}
+
+pub fn parse_manifest(text: &str) -> Result<Vec<Entry>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim().starts_with('#'))
+        .map(|(num, line)| parse_entry(line).map_err(|e| format!("line {}: {}", num + 1, e)))
+        .collect()
+}
+
+fn parse_entry(line: &str) -> Result<Entry, String> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let [rom, frames, hash, rest @ ..] = fields.as_slice() else {
+        return Err("expected <rom> <frames> <hash> [input]".to_string());
+    };
+    let input = match rest {
+        [] => None,
+        [input] => Some(PathBuf::from(input)),
+        _ => return Err("too many fields".to_string()),
+    };
+    let frames = frames
+        .parse()
+        .map_err(|_| format!("invalid frames: {}", frames))?;
+    let hash = match *hash {
+        UNRECORDED_HASH => None,
+        hash => Some(u64::from_str_radix(hash, 16).map_err(|_| format!("invalid hash: {}", hash))?),
+    };
+    Ok(Entry {
+        rom: PathBuf::from(rom),
+        frames,
+        hash,
+        input,
+    })
+}
+
+// FNV-1a of the PPU output, so the hashes don't depend on the color palette
+pub fn hash_framebuffer(pixels: &[u16]) -> u64 {
+    pixels
+        .iter()
+        .flat_map(|pixel| pixel.to_le_bytes())
+        .fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+        })
+}
+
+// the matching pixels are dimmed and the different ones are highlighted
+pub fn diff_image(actual: &Image, expected: &Image) -> Image {
+    let mut diff = Image::new(actual.width, actual.height);
+    for y in 0..actual.height {
+        for x in 0..actual.width {
+            let color = actual.get(x, y);
+            let same = x < expected.width && y < expected.height && expected.get(x, y) == color;
+            diff.set(
+                x,
+                y,
+                if same {
+                    Color(color.0 / 4, color.1 / 4, color.2 / 4)
+                } else {
+                    DIFF_COLOR
+                },
+            );
+        }
+    }
+    diff
+}
+
+pub fn run_entry(
+    entry: &Entry,
+    roms_dir: &Path,
+    manifest_dir: &Path,
+) -> Result<(u64, Image), String> {
+    let rom_path = roms_dir.join(&entry.rom);
+    if !rom_path.is_file() {
+        return Err(format!("{} not found", rom_path.display()));
+    }
+    let cartridge = cartridge::open_rom(&rom_path)?;
+    let input = match &entry.input {
+        Some(input) => Some(movie::load(
+            &manifest_dir.join(input),
+            cartridge.rom_info(),
+        )?),
+        None => None,
+    };
+    let ram_pattern = input.as_ref().map(|movie| movie.header.ram_pattern);
+    let emulator = Emulator::with_ram_pattern(cartridge, ram_pattern.unwrap_or_default());
+    let mut headless = Headless::new(emulator, input)?;
+    headless.run(entry.frames)?;
+    Ok((
+        hash_framebuffer(headless.emulator().framebuffer()),
+        headless.framebuffer(),
+    ))
+}
+
+pub fn check_entry(entry: &Entry, options: &Options) -> Outcome {
+    let manifest_dir = manifest_dir(&options.manifest);
+    let roms_dir = options.roms_dir.as_deref().unwrap_or(manifest_dir);
+    let (hash, image) = match run_entry(entry, roms_dir, manifest_dir) {
+        Ok(result) => result,
+        Err(err) => return Outcome::Failed(err),
+    };
+    let Some(expected_hash) = entry.hash else {
+        return Outcome::Unrecorded(hash);
+    };
+    if hash == expected_hash {
+        return Outcome::Passed;
+    }
+
+    let mut message = format!("expected hash {:016x}, got {:016x}", expected_hash, hash);
+    let write = |suffix: &str, image: &Image| -> Result<PathBuf, String> {
+        std::fs::create_dir_all(&options.output_dir)
+            .map_err(|e| format!("Failed to create {}: {}", options.output_dir.display(), e))?;
+        let path = options
+            .output_dir
+            .join(format!("{}.{}.png", entry.name(), suffix));
+        screenshot::save_png(image, &path)?;
+        Ok(path)
+    };
+    match write("actual", &image) {
+        Ok(path) => message += &format!(", saved {}", path.display()),
+        Err(err) => message += &format!(", {}", err),
+    }
+    let reference_path = reference_path(entry, manifest_dir);
+    if reference_path.is_file() {
+        match screenshot::load_png(&reference_path)
+            .and_then(|reference| write("diff", &diff_image(&image, &reference)))
+        {
+            Ok(path) => message += &format!(", saved {}", path.display()),
+            Err(err) => message += &format!(", {}", err),
+        }
+    }
+    Outcome::Failed(message)
+}
+
+fn manifest_dir(manifest: &Path) -> &Path {
+    manifest.parent().unwrap_or(Path::new("."))
+}
+
+fn reference_path(entry: &Entry, manifest_dir: &Path) -> PathBuf {
+    manifest_dir.join(format!("{}.png", entry.name()))
+}
+
+// rewrites the hash field of every entry, keeping the comments and the order
+fn update_manifest(options: &Options) -> Result<(), String> {
+    let path = &options.manifest;
+    let manifest_dir = manifest_dir(path);
+    let roms_dir = options.roms_dir.as_deref().unwrap_or(manifest_dir);
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    let mut updated = String::new();
+    for line in text.lines() {
+        match parse_manifest(line)?.first() {
+            Some(entry) => {
+                let (hash, image) = run_entry(entry, roms_dir, manifest_dir)?;
+                screenshot::save_png(&image, &reference_path(entry, manifest_dir))?;
+                println!("{} {:016x}", entry.name(), hash);
+                let mut fields: Vec<String> = line.split_whitespace().map(str::to_string).collect();
+                fields[2] = format!("{:016x}", hash);
+                updated += &fields.join(" ");
+            }
+            None => updated += line,
+        }
+        updated.push('\n');
+    }
+    std::fs::write(path, updated).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+pub fn run(options: &Options) -> Result<(), String> {
+    if options.update {
+        return update_manifest(options);
+    }
+
+    let path = &options.manifest;
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let entries = parse_manifest(&text)?;
+    let mut failures = 0;
+    for entry in &entries {
+        match check_entry(entry, options) {
+            Outcome::Passed => println!("ok      {}", entry.name()),
+            // a check that was never recorded can't pass
+            Outcome::Unrecorded(hash) => {
+                failures += 1;
+                println!(
+                    "NEW     {} {:016x}, record it with --update",
+                    entry.name(),
+                    hash
+                );
+            }
+            Outcome::Failed(message) => {
+                failures += 1;
+                println!("FAILED  {}: {}", entry.name(), message);
+            }
+        }
+    }
+    println!("{} entries, {} failed", entries.len(), failures);
+    match failures {
+        0 => Ok(()),
+        _ => Err(format!("{} golden image checks failed", failures)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let text = "\
+# comment
+full_palette/full_palette.nes 60 00000000deadbeef
+
+scanline/scanline.nes 120 - inputs/scanline.bin
+";
+        let entries = parse_manifest(text).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                Entry {
+                    rom: PathBuf::from("full_palette/full_palette.nes"),
+                    frames: 60,
+                    hash: Some(0xDEADBEEF),
+                    input: None,
+                },
+                Entry {
+                    rom: PathBuf::from("scanline/scanline.nes"),
+                    frames: 120,
+                    hash: None,
+                    input: Some(PathBuf::from("inputs/scanline.bin")),
+                },
+            ]
+        );
+        assert_eq!(entries[0].name(), "full_palette-full_palette-60");
+
+        assert_eq!(
+            parse_manifest("# header\nrom.nes sixty -").unwrap_err(),
+            "line 2: invalid frames: sixty"
+        );
+        assert!(parse_manifest("rom.nes 60").is_err());
+    }
+
+    #[test]
+    fn test_hash_framebuffer() {
+        assert_eq!(hash_framebuffer(&[]), 0xCBF2_9CE4_8422_2325);
+        assert_ne!(
+            hash_framebuffer(&[0x0F, 0x00]),
+            hash_framebuffer(&[0x00, 0x0F])
+        );
+        // the emphasis bits are part of the hash
+        assert_ne!(hash_framebuffer(&[0x0F]), hash_framebuffer(&[0x4F]));
+    }
+
+    #[test]
+    fn test_diff_image() {
+        let mut actual = Image::new(2, 1);
+        actual.set(0, 0, Color(0x80, 0x40, 0x20));
+        actual.set(1, 0, Color(0x10, 0x10, 0x10));
+        let mut expected = actual.clone();
+        expected.set(1, 0, Color(0x00, 0x00, 0x00));
+
+        let diff = diff_image(&actual, &expected);
+        assert_eq!(diff.get(0, 0), Color(0x20, 0x10, 0x08));
+        assert_eq!(diff.get(1, 0), DIFF_COLOR);
+    }
+
+    // runs the manifest in the golden directory against the nes-test-roms
+    #[test]
+    #[ignore]
+    fn golden_images() {
+        let options = Options {
+            manifest: Path::new(env!("CARGO_MANIFEST_DIR")).join("golden/manifest.txt"),
+            roms_dir: Some(PathBuf::from(
+                std::env::var("NES_TEST_ROMS_PATH").expect("NES_TEST_ROMS_PATH not set"),
+            )),
+            output_dir: Path::new(env!("CARGO_MANIFEST_DIR")).join("target/golden"),
+            update: false,
+        };
+        if let Err(err) = run(&options) {
+            panic!("{}", err);
+        }
+    }
+}
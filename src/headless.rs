@@ -0,0 +1,299 @@
+use std::path::PathBuf;
+
+use crate::emulator::{Emulator, Image};
+use crate::joypad_cable::{Command, FrameInput, InputCable, JoypadCable, Movie};
+use crate::joypad_handler::Joypads;
+use crate::screenshot;
+use crate::script::{self, Script};
+
+const SAMPLE_RATE: u32 = 44100;
+const MASTER_CLOCK_RATE: f32 = 21_477_272.0;
+
+#[derive(Debug, Default)]
+pub struct Options {
+    pub frames: usize,
+    pub input: Option<Movie>,
+    pub screenshot: Option<PathBuf>,
+    // timestamped screenshot of the last frame
+    pub screenshot_dir: Option<PathBuf>,
+    pub screenshot_options: screenshot::Options,
+    pub dump_audio: Option<PathBuf>,
+    pub script: Option<PathBuf>,
+}
+
+/*
+ * Runs the emulator as fast as possible, without opening any window or audio device.
+ * The frames are counted by the PPU, so the same ROM and input always end on the same cycle.
+ */
+pub struct Headless {
+    emulator: Emulator,
+    joypad_cable: Box<dyn JoypadCable>,
+    samples: Vec<f32>,
+    script: Option<Script>,
+}
+
+impl Headless {
+    // without an input movie the joypads are never pressed
+    pub fn new(emulator: Emulator, input: Option<Movie>) -> Result<Self, String> {
+        Self::with_script(emulator, input, None)
+    }
+
+    // the script must be loaded with the same emulator, the movie starts from its save state
+    pub fn with_script(
+        mut emulator: Emulator,
+        input: Option<Movie>,
+        script: Option<Script>,
+    ) -> Result<Self, String> {
+        if let Some(movie) = &input {
+            movie.header.start(&mut emulator)?;
+        }
+        let joypads = Joypads::new();
+        joypads.connect(&mut emulator);
+        let mut joypad_cable: Box<dyn JoypadCable> = match input {
+            Some(movie) => Box::new(InputCable::new(joypads, movie)),
+            None => Box::new(joypads),
+        };
+        if let Some(script) = &script {
+            joypad_cable = Box::new(script.cable(joypad_cable));
+        }
+
+        Ok(Self {
+            emulator,
+            joypad_cable,
+            samples: Vec::new(),
+            script,
+        })
+    }
+
+    // fails when the input movie is over before the last frame
+    pub fn run(&mut self, frames: usize) -> Result<(), String> {
+        let sample_clock = (MASTER_CLOCK_RATE / SAMPLE_RATE as f32) as usize;
+        for num in 0..frames {
+            let mut error = None;
+            let Self {
+                emulator,
+                script,
+                samples,
+                ..
+            } = self;
+            emulator.run_frame(|emulator| {
+                if let Err(err) = script::clock(script, emulator) {
+                    error = Some(err);
+                }
+                if emulator.cycle.is_multiple_of(sample_clock) {
+                    samples.push(emulator.audio_signal().sample());
+                }
+            });
+            if let Some(err) = error {
+                self.stop_script(&err);
+            }
+            if let Some(script) = self.script.as_mut() {
+                if let Err(err) = script.frame_end(&mut self.emulator) {
+                    self.stop_script(&err);
+                }
+            }
+            // the input of the next frame, at the same point the Ui writes it
+            if num + 1 < frames && self.joypad_cable.finished() {
+                return Err(format!(
+                    "the movie ended at frame {}, before the last frame",
+                    self.emulator.frame()
+                ));
+            }
+            match self.joypad_cable.write(FrameInput::default()).command {
+                Some(Command::Reset) => self.emulator.reset(),
+                Some(Command::PowerCycle) => self.emulator.power_cycle(),
+                None => {}
+            }
+        }
+        Ok(())
+    }
+
+    // the emulation goes on without the script
+    fn stop_script(&mut self, err: &str) {
+        eprintln!("script error: {}", err);
+        if let Some(script) = self.script.take() {
+            script.detach(&mut self.emulator);
+        }
+    }
+
+    // with the overlay drawn by the script
+    pub fn framebuffer(&self) -> Image {
+        let mut image = self.emulator.framebuffer_image();
+        if let Some(script) = &self.script {
+            script.draw_overlay(&mut image);
+        }
+        image
+    }
+
+    pub fn emulator(&self) -> &Emulator {
+        &self.emulator
+    }
+
+    #[cfg(test)]
+    pub fn script(&self) -> Option<&Script> {
+        self.script.as_ref()
+    }
+
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    pub fn frame(&self) -> usize {
+        self.emulator.frame()
+    }
+
+    pub fn screenshot(&self, name: &str, options: &screenshot::Options) -> Result<PathBuf, String> {
+        screenshot::capture(&self.emulator, name, options)
+    }
+}
+
+pub fn run(mut emulator: Emulator, options: &Options) -> Result<(), String> {
+    let script = match &options.script {
+        Some(path) => Some(Script::load(path, &mut emulator)?),
+        None => None,
+    };
+    let mut headless = Headless::with_script(emulator, options.input.clone(), script)?;
+    headless.run(options.frames)?;
+
+    if let Some(path) = &options.screenshot {
+        let image = screenshot::prepare(&headless.framebuffer(), &options.screenshot_options);
+        screenshot::save_png(&image, path)?;
+    }
+    if let Some(directory) = &options.screenshot_dir {
+        let options = screenshot::Options {
+            directory: directory.clone(),
+            ..options.screenshot_options.clone()
+        };
+        let path = headless.screenshot("sunrest", &options)?;
+        println!("saved {}", path.display());
+    }
+    if let Some(path) = &options.dump_audio {
+        write_wav(headless.samples(), path)?;
+    }
+    println!("{} frames", headless.frame());
+    Ok(())
+}
+
+fn write_wav(samples: &[f32], path: &std::path::Path) -> Result<(), String> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let error = |e: hound::Error| format!("Failed to write {}: {}", path.display(), e);
+    let mut writer = hound::WavWriter::create(path, spec).map_err(error)?;
+    for &sample in samples {
+        writer.write_sample(sample).map_err(error)?;
+    }
+    writer.finalize().map_err(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::cartridge;
+    use crate::joypad_cable::movie::{Header, Start};
+
+    /*
+     * Writes the joypad 1 state read at every NMI to $0300:
+     * $8000 JMP $8000
+     * $8010 LDA #$01; STA $4016; LDA #$00; STA $4016
+     * $801A LDX #$08
+     * $801C LDA $4016; LSR; ROL $0300; DEX; BNE $801C
+     * $8026 RTI
+     */
+    fn mk_emulator() -> Emulator {
+        let cartridge = cartridge::Cartridge::with_program(&[
+            (0x8000, &[0x4C, 0x00, 0x80]),
+            (
+                0x8010,
+                &[
+                    0xA9, 0x01, 0x8D, 0x16, 0x40, 0xA9, 0x00, 0x8D, 0x16, 0x40, 0xA2, 0x08, 0xAD,
+                    0x16, 0x40, 0x4A, 0x2E, 0x00, 0x03, 0xCA, 0xD0, 0xF6, 0x40,
+                ],
+            ),
+            (0xFFFA, &[0x10, 0x80, 0x00, 0x80, 0x00, 0x80]),
+        ]);
+        let mut emulator = Emulator::new(cartridge);
+        emulator.write_memory(0x2000, 0x80); // enable NMI
+        emulator
+    }
+
+    #[test]
+    fn test_run() {
+        let mut headless = Headless::new(mk_emulator(), None).unwrap();
+        headless.run(3).unwrap();
+        assert_eq!(headless.frame(), 3);
+        // ~735 samples per frame
+        assert!((3 * 730..3 * 740).contains(&headless.samples().len()));
+        assert_eq!(headless.framebuffer().width, crate::emulator::SCREEN_WIDTH);
+    }
+
+    #[test]
+    fn test_screenshot() {
+        let mut headless = Headless::new(mk_emulator(), None).unwrap();
+        headless.run(1).unwrap();
+        let options = screenshot::Options {
+            directory: std::env::temp_dir().join(format!("sunrest-{}", std::process::id())),
+            crop_overscan: true,
+            ..Default::default()
+        };
+        let path = headless.screenshot("test", &options).unwrap();
+        let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+        let decoder = png::Decoder::new(std::fs::File::open(&path).unwrap());
+        let info = decoder.read_info().unwrap().info().clone();
+        std::fs::remove_dir_all(&options.directory).unwrap();
+
+        assert!(file_name.starts_with("test-") && file_name.ends_with(".png"));
+        assert_eq!((info.width, info.height), (256, 224));
+    }
+
+    #[test]
+    fn test_input() {
+        let input = Movie::read(&mut [0x81, 0x01, 0x02].as_slice()).unwrap();
+        let mut headless = Headless::new(mk_emulator(), Some(input)).unwrap();
+        headless.run(1).unwrap();
+        assert_eq!(
+            headless.emulator.peek_memory(0x0300),
+            0x00,
+            "no input in the first frame"
+        );
+        headless.run(1).unwrap();
+        // A (bit 0) and Right (bit 7) are read in this order, so the bits end up reversed
+        assert_eq!(headless.emulator.peek_memory(0x0300), 0x81);
+        headless.run(2).unwrap();
+        assert_eq!(headless.emulator.peek_memory(0x0300), 0x40);
+        assert_eq!(
+            headless.run(2).unwrap_err(),
+            "the movie ended at frame 5, before the last frame"
+        );
+    }
+    #[test]
+    fn test_input_savestate() {
+        let mut headless = Headless::new(mk_emulator(), None).unwrap();
+        headless.run(2).unwrap();
+        let mut input = Movie {
+            header: Header {
+                start: Start::Savestate(headless.emulator.state_data()),
+                ..Default::default()
+            },
+            frames: vec![FrameInput {
+                ports: [0x81, 0x00],
+                command: None,
+            }],
+        };
+
+        let mut headless = Headless::new(mk_emulator(), Some(input.clone())).unwrap();
+        assert_eq!(headless.frame(), 2, "the movie starts from the state");
+        headless.run(2).unwrap();
+        assert_eq!(headless.frame(), 4);
+        assert_eq!(headless.emulator.peek_memory(0x0300), 0x81);
+
+        input.header.start = Start::Savestate(vec![1, 2, 3]);
+        assert_eq!(
+            Headless::new(mk_emulator(), Some(input)).err(),
+            Some("Failed to load the movie's save state: not a save state".to_string())
+        );
+    }
+}
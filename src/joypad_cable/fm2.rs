@@ -0,0 +1,447 @@
+use std::io::{Read, Write};
+
+use super::movie::{Header, Region, Start};
+use super::{Command, FrameInput, Movie};
+use crate::emulator::{cartridge::RomInfo, RamPattern};
+
+const FM2_VERSION: u32 = 3;
+// the value written by FCEUX 2.2
+const EMU_VERSION: u32 = 22020;
+// the RLDUTSBA order of the records, from the bit 7 to the bit 0 of the joypad state
+const BUTTONS: &[u8; 8] = b"RLDUTSBA";
+const COMMAND_RESET: u8 = 0x01;
+const COMMAND_POWER: u8 = 0x02;
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Port {
+    None,
+    Gamepad,
+}
+
+/*
+ * A movie in the text format of FCEUX. Its first record is the input of the first frame, which
+ * Sunrest doesn't have (the cables are written at the end of a frame), so the conversions drop
+ * or add it. FCEUX starts with the alternating RAM pattern.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fm2 {
+    pub rom_filename: String,
+    pub rom_md5: [u8; 16],
+    pub pal: bool,
+    pub rerecords: u32,
+    pub ports: [Port; 2],
+    pub frames: Vec<FrameInput>,
+}
+
+impl Fm2 {
+    pub fn from_movie(movie: &Movie, rom_info: &RomInfo) -> Result<Self, String> {
+        if let Start::Savestate(_) = movie.header.start {
+            return Err("movies starting from a save state can't be exported".to_string());
+        }
+        let rom_filename = match rom_info.name.rsplit_once('.') {
+            Some((name, _)) => name.to_string(),
+            None => rom_info.name.clone(),
+        };
+        Ok(Self {
+            rom_filename,
+            rom_md5: rom_info.md5,
+            pal: movie.header.region == Region::Pal,
+            rerecords: movie.header.rerecords,
+            ports: [Port::Gamepad; 2],
+            frames: std::iter::once(FrameInput::default())
+                .chain(movie.frames.iter().copied())
+                .collect(),
+        })
+    }
+
+    // the movie is converted for the ROM, check it first
+    pub fn into_movie(self, rom_info: &RomInfo) -> Movie {
+        let mut header = Header::new(rom_info, RamPattern::Alternating);
+        header.region = if self.pal { Region::Pal } else { Region::Ntsc };
+        header.rerecords = self.rerecords;
+        Movie {
+            header,
+            frames: self.frames.into_iter().skip(1).collect(),
+        }
+    }
+
+    pub fn check(&self, rom_info: &RomInfo) -> Vec<String> {
+        let mut mismatches = Vec::new();
+        if self.rom_md5 != rom_info.md5 {
+            mismatches.push(format!(
+                "the movie was recorded with another ROM (MD5 {}, {} is {})",
+                hex(&self.rom_md5),
+                rom_info.name,
+                hex(&rom_info.md5)
+            ));
+        }
+        mismatches
+    }
+
+    pub fn read(reader: &mut impl Read) -> Result<Self, String> {
+        let mut text = String::new();
+        reader
+            .read_to_string(&mut text)
+            .map_err(|e| format!("Failed to read the movie: {}", e))?;
+
+        let mut fm2 = Self {
+            rom_filename: String::new(),
+            rom_md5: [0; 16],
+            pal: false,
+            rerecords: 0,
+            ports: [Port::Gamepad; 2],
+            frames: Vec::new(),
+        };
+        let mut rom_checksum = false;
+        for (line_idx, line) in text.lines().enumerate() {
+            let result = if line.starts_with('|') {
+                fm2.read_record(line).map(|input| fm2.frames.push(input))
+            } else if line.trim().is_empty() {
+                Ok(())
+            } else {
+                let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+                rom_checksum |= key == "romChecksum";
+                fm2.read_header(key, value)
+            };
+            result.map_err(|e| format!("line {}: {}", line_idx + 1, e))?;
+        }
+        if !rom_checksum {
+            return Err("missing romChecksum".to_string());
+        }
+        Ok(fm2)
+    }
+
+    fn read_header(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let number = || {
+            value
+                .parse::<u32>()
+                .map_err(|_| format!("invalid {}: {}", key, value))
+        };
+        match key {
+            "version" if number()? != FM2_VERSION => {
+                return Err(format!("unsupported fm2 version: {}", value));
+            }
+            "romFilename" => self.rom_filename = value.to_string(),
+            "romChecksum" => {
+                self.rom_md5 = value
+                    .strip_prefix("base64:")
+                    .and_then(base64_decode)
+                    .and_then(|md5| md5.try_into().ok())
+                    .ok_or_else(|| format!("invalid romChecksum: {}", value))?;
+            }
+            "rerecordCount" => self.rerecords = number()?,
+            "palFlag" => self.pal = number()? != 0,
+            "port0" | "port1" => {
+                let idx = (key == "port1") as usize;
+                self.ports[idx] = match number()? {
+                    0 => Port::None,
+                    1 => Port::Gamepad,
+                    port => return Err(format!("unsupported {} device: {}", key, port)),
+                };
+            }
+            "fourscore" | "binary" if number()? != 0 => {
+                return Err(format!("{} movies are not supported", key));
+            }
+            "port2" if number()? != 0 => {
+                return Err("expansion port devices are not supported".to_string());
+            }
+            "savestate" => {
+                return Err("movies starting from a save state are not supported".to_string());
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // |commands|port0|port1|port2|
+    fn read_record(&self, line: &str) -> Result<FrameInput, String> {
+        let fields: Vec<&str> = line.split('|').collect();
+        if fields.len() < 5 {
+            return Err(format!("invalid record: {}", line));
+        }
+        let command = match fields[1].parse::<u8>() {
+            Ok(0) => None,
+            Ok(COMMAND_RESET) => Some(Command::Reset),
+            Ok(COMMAND_POWER) => Some(Command::PowerCycle),
+            Ok(command) => return Err(format!("unsupported command: {}", command)),
+            Err(_) => return Err(format!("invalid command: {}", fields[1])),
+        };
+        let mut ports = [0; 2];
+        for ((state, field), port) in ports.iter_mut().zip(&fields[2..4]).zip(self.ports) {
+            *state = match port {
+                Port::None => 0,
+                Port::Gamepad if field.len() == BUTTONS.len() => field
+                    .bytes()
+                    .fold(0, |state, ch| state << 1 | (ch != b'.' && ch != b' ') as u8),
+                Port::Gamepad => return Err(format!("invalid gamepad: {}", field)),
+            };
+        }
+        Ok(FrameInput { ports, command })
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let port = |port| match port {
+            Port::None => 0,
+            Port::Gamepad => 1,
+        };
+        writeln!(writer, "version {}", FM2_VERSION)?;
+        writeln!(writer, "emuVersion {}", EMU_VERSION)?;
+        writeln!(writer, "rerecordCount {}", self.rerecords)?;
+        writeln!(writer, "palFlag {}", self.pal as u8)?;
+        writeln!(writer, "romFilename {}", self.rom_filename)?;
+        writeln!(
+            writer,
+            "romChecksum base64:{}",
+            base64_encode(&self.rom_md5)
+        )?;
+        writeln!(writer, "guid {}", guid(&random_bytes()))?;
+        writeln!(writer, "fourscore 0")?;
+        writeln!(writer, "microphone 0")?;
+        writeln!(writer, "port0 {}", port(self.ports[0]))?;
+        writeln!(writer, "port1 {}", port(self.ports[1]))?;
+        writeln!(writer, "port2 0")?;
+        writeln!(
+            writer,
+            "comment author Sunrest {}",
+            env!("CARGO_PKG_VERSION")
+        )?;
+        for frame in &self.frames {
+            let command = match frame.command {
+                None => 0,
+                Some(Command::Reset) => COMMAND_RESET,
+                Some(Command::PowerCycle) => COMMAND_POWER,
+            };
+            write!(writer, "|{}|", command)?;
+            for (state, port) in frame.ports.iter().zip(self.ports) {
+                if port == Port::Gamepad {
+                    let buttons: String = BUTTONS
+                        .iter()
+                        .enumerate()
+                        .map(|(idx, &ch)| match state & (0x80 >> idx) {
+                            0 => '.',
+                            _ => ch as char,
+                        })
+                        .collect();
+                    write!(writer, "{}", buttons)?;
+                }
+                write!(writer, "|")?;
+            }
+            writeln!(writer, "|")?;
+        }
+        Ok(())
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// FCEUX tells the movies apart by their GUID, a random (version 4) one for each movie written
+fn guid(random: &[u8; 16]) -> String {
+    let mut bytes = *random;
+    bytes[6] = bytes[6] & 0x0F | 0x40;
+    bytes[8] = bytes[8] & 0x3F | 0x80;
+    let hex = hex(&bytes).to_uppercase();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+// the hashers of the standard library are randomly seeded, on top of the clock
+fn random_bytes() -> [u8; 16] {
+    use std::hash::{BuildHasher, Hasher};
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let mut bytes = [0; 16];
+    for chunk in bytes.chunks_exact_mut(8) {
+        let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+        hasher.write_u128(now.as_nanos());
+        chunk.copy_from_slice(&hasher.finish().to_le_bytes());
+    }
+    bytes
+}
+
+fn base64_encode(data: &[u8]) -> String {
+    let mut text = String::new();
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (idx, &byte)| {
+            bits | (byte as u32) << (16 - idx * 8)
+        });
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                text.push(BASE64[(bits >> (18 - idx * 6)) as usize & 0x3F] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim_end_matches('=');
+    let mut data = Vec::new();
+    let mut bits = 0u32;
+    for (idx, ch) in text.bytes().enumerate() {
+        let value = BASE64.iter().position(|&c| c == ch)? as u32;
+        bits = bits << 6 | value;
+        if idx % 4 == 3 {
+            data.extend_from_slice(&bits.to_be_bytes()[1..]);
+            bits = 0;
+        }
+    }
+    match text.len() % 4 {
+        0 => {}
+        2 => data.push((bits >> 4) as u8),
+        3 => data.extend_from_slice(&((bits >> 2) as u16).to_be_bytes()),
+        _ => return None,
+    }
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::movie::VERSION;
+    use super::*;
+
+    fn rom_info() -> RomInfo {
+        RomInfo {
+            name: "game.nes".to_string(),
+            cksum: 0x1234_5678,
+            md5: *b"0123456789ABCDEF",
+        }
+    }
+
+    const FM2: &str = "version 3
+emuVersion 22020
+rerecordCount 12
+palFlag 0
+romFilename game
+romChecksum base64:MDEyMzQ1Njc4OUFCQ0RFRg==
+guid 452DE2C3-EF43-2FA9-77AC-0677FC51543B
+fourscore 0
+microphone 0
+port0 1
+port1 1
+port2 0
+comment author someone
+|0|........|........||
+|0|R......A|.......A||
+|1|...U....|........||
+|2|....TS..|RLDUTSBA||
+";
+
+    #[test]
+    fn test_read() {
+        let fm2 = Fm2::read(&mut FM2.as_bytes()).unwrap();
+        assert_eq!(fm2.rom_filename, "game");
+        assert_eq!(fm2.rerecords, 12);
+        assert!(fm2.check(&rom_info()).is_empty());
+
+        let movie = fm2.into_movie(&rom_info());
+        assert_eq!(movie.header.version, VERSION);
+        assert_eq!(movie.header.rom_checksum, 0x1234_5678);
+        assert_eq!(movie.header.ram_pattern, RamPattern::Alternating);
+        assert_eq!(
+            movie.frames,
+            vec![
+                FrameInput {
+                    ports: [0x81, 0x01],
+                    command: None,
+                },
+                FrameInput {
+                    ports: [0x10, 0x00],
+                    command: Some(Command::Reset),
+                },
+                FrameInput {
+                    ports: [0x0C, 0xFF],
+                    command: Some(Command::PowerCycle),
+                },
+            ]
+        );
+
+        let mut other_rom = rom_info();
+        other_rom.md5[0] = 0xFF;
+        let fm2 = Fm2::read(&mut FM2.as_bytes()).unwrap();
+        assert_eq!(
+            fm2.check(&other_rom),
+            vec![
+                "the movie was recorded with another ROM (MD5 30313233343536373839414243444546, \
+                 game.nes is ff313233343536373839414243444546)"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write() {
+        let fm2 = Fm2::read(&mut FM2.as_bytes()).unwrap();
+        let mut movie = fm2.clone().into_movie(&rom_info());
+        let exported = Fm2::from_movie(&movie, &rom_info()).unwrap();
+        assert_eq!(exported, fm2);
+
+        let mut text = Vec::new();
+        exported.write(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.contains("romChecksum base64:MDEyMzQ1Njc4OUFCQ0RFRg==\n"));
+        assert!(text.ends_with("|1|...U....|........||\n|2|....TS..|RLDUTSBA||\n"));
+        assert_eq!(Fm2::read(&mut text.as_bytes()), Ok(fm2));
+
+        movie.header.start = Start::Savestate(vec![1, 2, 3]);
+        assert_eq!(
+            Fm2::from_movie(&movie, &rom_info()),
+            Err("movies starting from a save state can't be exported".to_string())
+        );
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let read = |text: &str| Fm2::read(&mut text.as_bytes()).unwrap_err();
+        assert_eq!(read("version 3\n"), "missing romChecksum");
+        assert_eq!(
+            read("romChecksum base64:AAAA\n"),
+            "line 1: invalid romChecksum: base64:AAAA"
+        );
+        assert_eq!(read("port0 2\n"), "line 1: unsupported port0 device: 2");
+        assert_eq!(
+            read("fourscore 1\n"),
+            "line 1: fourscore movies are not supported"
+        );
+        assert_eq!(
+            read("|4|........|........||\n"),
+            "line 1: unsupported command: 4"
+        );
+        assert_eq!(read("|0|...|........||\n"), "line 1: invalid gamepad: ...");
+
+        let fm2 = Fm2::read(
+            &mut "port1 0\nromChecksum base64:MDEyMzQ1Njc4OUFCQ0RFRg==\n|0|A.......|||\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(fm2.ports, [Port::Gamepad, Port::None]);
+        assert_eq!(fm2.frames[0].ports, [0x80, 0x00]);
+    }
+
+    #[test]
+    fn test_guid() {
+        assert_eq!(guid(&[0xFF; 16]), "FFFFFFFF-FFFF-4FFF-BFFF-FFFFFFFFFFFF");
+        assert_eq!(guid(&[0x00; 16]), "00000000-0000-4000-8000-000000000000");
+        assert_ne!(random_bytes(), random_bytes());
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64_encode(b"sunrest"), "c3VucmVzdA==");
+        assert_eq!(base64_encode(b"nes"), "bmVz");
+        assert_eq!(base64_decode("c3VucmVzdA=="), Some(b"sunrest".to_vec()));
+        assert_eq!(base64_decode("c3VucmVzdA"), Some(b"sunrest".to_vec()));
+        assert_eq!(base64_decode("bmVzMA=="), Some(b"nes0".to_vec()));
+        assert_eq!(base64_decode("bmVz!"), None);
+    }
+}
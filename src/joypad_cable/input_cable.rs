@@ -1,23 +1,138 @@
-use super::JoypadCable;
-use std::io::Read;
+use super::{FrameInput, JoypadCable, Movie, MovieMode};
 
-pub struct InputCable<C: JoypadCable, R: Read> {
+/*
+ * Plays a movie, the live input goes through once it is over. While recording the live input
+ * is added to the movie, so an earlier part can be played again from a save state.
+ */
+pub struct InputCable<C: JoypadCable> {
     cable: C,
-    reader: R,
+    movie: Movie,
+    frame: usize,
+    recording: bool,
 }
 
-impl<C: JoypadCable, R: Read> InputCable<C, R> {
-    pub fn new(cable: C, reader: R) -> Self {
-        Self { cable, reader }
+impl<C: JoypadCable> InputCable<C> {
+    pub fn new(cable: C, movie: Movie) -> Self {
+        Self {
+            cable,
+            movie,
+            frame: 0,
+            recording: false,
+        }
+    }
+
+    // records a new movie from the first frame
+    pub fn record(cable: C, header: super::movie::Header) -> Self {
+        Self {
+            cable,
+            movie: Movie {
+                header,
+                frames: Vec::new(),
+            },
+            frame: 0,
+            recording: true,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+}
+
+impl<C: JoypadCable> JoypadCable for InputCable<C> {
+    fn write(&mut self, input: FrameInput) -> FrameInput {
+        let input = match self.movie.frames.get(self.frame) {
+            Some(&frame) if !self.recording => frame,
+            _ => input,
+        };
+        let input = self.cable.write(input);
+        if self.recording {
+            self.movie.frames.push(input);
+        }
+        self.frame += 1;
+        input
+    }
+
+    fn position(&self) -> usize {
+        self.frame
+    }
+
+    fn seek(&mut self, position: usize, mode: MovieMode) -> Result<(), String> {
+        if position > self.movie.frames.len() {
+            return Err("the save state is after the end of the movie".to_string());
+        }
+        self.cable.seek(position, mode)?;
+        self.frame = position;
+        self.recording = mode == MovieMode::ReadWrite;
+        if self.recording {
+            self.movie.frames.truncate(position);
+            self.movie.header.rerecords += 1;
+        }
+        Ok(())
+    }
+
+    fn finished(&self) -> bool {
+        !self.recording && self.frame >= self.movie.frames.len()
     }
 }
 
-impl<C: JoypadCable, R: Read> JoypadCable for InputCable<C, R> {
-    fn write(&mut self, state: u8) {
-        let mut buf = [0];
-        if self.reader.read_exact(&mut buf).is_err() {
-            buf[0] = state;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::joypad_handler::Joypads;
+
+    fn frame(port1: u8) -> FrameInput {
+        FrameInput {
+            ports: [port1, 0],
+            command: None,
         }
-        self.cable.write(buf[0]);
+    }
+
+    fn ports1(cable: &InputCable<Joypads>) -> Vec<u8> {
+        cable.movie().frames.iter().map(|f| f.ports[0]).collect()
+    }
+
+    #[test]
+    fn test_read_only() {
+        let movie = Movie {
+            frames: vec![frame(1), frame(2), frame(3)],
+            ..Default::default()
+        };
+        let mut cable = InputCable::new(Joypads::new(), movie);
+        assert_eq!(cable.write(frame(9)), frame(1));
+        assert_eq!(cable.write(frame(9)), frame(2));
+        assert_eq!(cable.position(), 2);
+
+        cable.seek(1, MovieMode::ReadOnly).unwrap();
+        assert_eq!(cable.write(frame(9)), frame(2));
+        assert_eq!(cable.write(frame(9)), frame(3));
+        assert!(cable.finished());
+        // the movie is over, the live input is not recorded
+        assert_eq!(cable.write(frame(9)), frame(9));
+        assert_eq!(ports1(&cable), vec![1, 2, 3]);
+        assert!(cable.seek(4, MovieMode::ReadOnly).is_err());
+    }
+
+    #[test]
+    fn test_read_write() {
+        let movie = Movie {
+            frames: vec![frame(1), frame(2), frame(3)],
+            ..Default::default()
+        };
+        let mut cable = InputCable::new(Joypads::new(), movie);
+        cable.write(frame(9));
+        cable.write(frame(9));
+
+        cable.seek(1, MovieMode::ReadWrite).unwrap();
+        assert_eq!(cable.write(frame(7)), frame(7));
+        assert_eq!(cable.write(frame(8)), frame(8));
+        assert_eq!(ports1(&cable), vec![1, 7, 8]);
+        assert_eq!(cable.movie().header.rerecords, 1);
+
+        // back to the playback of the new branch
+        cable.seek(0, MovieMode::ReadOnly).unwrap();
+        assert_eq!(cable.write(frame(9)), frame(1));
+        assert_eq!(cable.write(frame(9)), frame(7));
     }
 }
@@ -1,8 +1,42 @@
+pub mod fm2;
 mod input_cable;
+pub mod movie;
 mod output_cable;
+mod turbo_cable;
 pub use input_cable::InputCable;
+pub use movie::{Command, FrameInput, Movie};
 pub use output_cable::OutputCable;
+pub use turbo_cable::{Turbo, TurboMode};
 
+/*
+ * What loading a save state does to a movie: in read-only mode the movie plays from the frame of
+ * the state, in read-write mode the frames after it are dropped and the recording goes on.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovieMode {
+    ReadOnly,
+    ReadWrite,
+}
+
+/*
+ * Carries the input of both joypads and the console buttons to the console, once a frame.
+ * The cables can be chained, each one returns the input that reached the console.
+ */
 pub trait JoypadCable {
-    fn write(&mut self, state: u8);
+    fn write(&mut self, input: FrameInput) -> FrameInput;
+
+    // the number of frames written, kept with the save states
+    fn position(&self) -> usize {
+        0
+    }
+
+    // a save state was loaded, the cables go back to its position
+    fn seek(&mut self, _position: usize, _mode: MovieMode) -> Result<(), String> {
+        Ok(())
+    }
+
+    // the movie played is over, the next frames get the live input
+    fn finished(&self) -> bool {
+        false
+    }
 }
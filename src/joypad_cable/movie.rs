@@ -0,0 +1,354 @@
+use std::io::{Read, Write};
+
+use super::fm2::Fm2;
+use crate::emulator::{cartridge::RomInfo, Emulator, RamPattern};
+
+const MAGIC: &[u8; 4] = b"SRMV";
+pub const VERSION: u8 = 1;
+// the position of the rerecord counter, so it can be updated while recording
+pub const RERECORDS_OFFSET: u64 = 6;
+pub const FRAME_SIZE: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Reset,
+    PowerCycle,
+}
+
+// what is sent to the console at the start of a frame
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FrameInput {
+    pub ports: [u8; 2],
+    pub command: Option<Command>,
+}
+
+impl FrameInput {
+    fn to_bytes(self) -> [u8; FRAME_SIZE] {
+        let command = match self.command {
+            None => 0,
+            Some(Command::Reset) => 1,
+            Some(Command::PowerCycle) => 2,
+        };
+        [self.ports[0], self.ports[1], command]
+    }
+
+    fn from_bytes(bytes: [u8; FRAME_SIZE]) -> Result<Self, String> {
+        let command = match bytes[2] {
+            0 => None,
+            1 => Some(Command::Reset),
+            2 => Some(Command::PowerCycle),
+            command => return Err(format!("invalid command: {}", command)),
+        };
+        Ok(Self {
+            ports: [bytes[0], bytes[1]],
+            command,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Ntsc,
+    Pal,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Start {
+    PowerOn,
+    // a save state in the emulator's format
+    Savestate(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub version: u8,
+    pub region: Region,
+    pub rerecords: u32,
+    pub rom_checksum: u32,
+    pub ram_pattern: RamPattern,
+    pub start: Start,
+}
+
+impl Header {
+    // sunrest only emulates the NTSC console
+    pub fn new(rom_info: &RomInfo, ram_pattern: RamPattern) -> Self {
+        Self {
+            version: VERSION,
+            region: Region::Ntsc,
+            rerecords: 0,
+            rom_checksum: rom_info.cksum,
+            ram_pattern,
+            start: Start::PowerOn,
+        }
+    }
+
+    /*
+     * The differences that make the playback go out of sync. A version 0 movie, a joypad 1 byte
+     * per frame without a header, can't be checked.
+     */
+    pub fn check(&self, rom_info: &RomInfo) -> Vec<String> {
+        let mut mismatches = Vec::new();
+        if self.version == 0 {
+            return mismatches;
+        }
+        if self.rom_checksum != rom_info.cksum {
+            mismatches.push(format!(
+                "the movie was recorded with another ROM (checksum {:08X}, {} is {:08X})",
+                self.rom_checksum, rom_info.name, rom_info.cksum
+            ));
+        }
+        if self.region == Region::Pal {
+            mismatches.push("the movie was recorded on a PAL console".to_string());
+        }
+        mismatches
+    }
+
+    // before the first frame, over the emulator powered on with the RAM pattern of the movie
+    pub fn start(&self, emulator: &mut Emulator) -> Result<(), String> {
+        match &self.start {
+            Start::PowerOn => Ok(()),
+            Start::Savestate(data) => emulator
+                .load_state_data(data)
+                .map_err(|e| format!("Failed to load the movie's save state: {}", e)),
+        }
+    }
+
+    /*
+     * "SRMV", version, region, rerecords (u32), ROM checksum (u32), RAM pattern (u8 length and
+     * text), savestate (u32 length, 0 for a movie starting at power on, and data).
+     * The integers are little-endian.
+     */
+    pub fn write(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let ram_pattern = self.ram_pattern.to_string();
+        let savestate = match &self.start {
+            Start::PowerOn => &[][..],
+            Start::Savestate(data) => data.as_slice(),
+        };
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[self.version, self.region as u8])?;
+        writer.write_all(&self.rerecords.to_le_bytes())?;
+        writer.write_all(&self.rom_checksum.to_le_bytes())?;
+        writer.write_all(&[ram_pattern.len() as u8])?;
+        writer.write_all(ram_pattern.as_bytes())?;
+        writer.write_all(&(savestate.len() as u32).to_le_bytes())?;
+        writer.write_all(savestate)
+    }
+
+    fn read(reader: &mut impl Read) -> Result<Self, String> {
+        let version = read_u8(reader)?;
+        if version != VERSION {
+            return Err(format!("unsupported movie version: {}", version));
+        }
+        let region = match read_u8(reader)? {
+            0 => Region::Ntsc,
+            1 => Region::Pal,
+            region => return Err(format!("invalid region: {}", region)),
+        };
+        let rerecords = read_u32(reader)?;
+        let rom_checksum = read_u32(reader)?;
+        let len = read_u8(reader)?;
+        let ram_pattern = read_bytes(reader, len as usize)?;
+        let ram_pattern = String::from_utf8_lossy(&ram_pattern).parse()?;
+        let start = match read_u32(reader)? {
+            0 => Start::PowerOn,
+            len => Start::Savestate(read_bytes(reader, len as usize)?),
+        };
+        Ok(Self {
+            version,
+            region,
+            rerecords,
+            rom_checksum,
+            ram_pattern,
+            start,
+        })
+    }
+}
+
+impl Default for Header {
+    fn default() -> Self {
+        Self {
+            version: 0,
+            region: Region::Ntsc,
+            rerecords: 0,
+            rom_checksum: 0,
+            ram_pattern: RamPattern::default(),
+            start: Start::PowerOn,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Movie {
+    pub header: Header,
+    pub frames: Vec<FrameInput>,
+}
+
+impl Movie {
+    // the files without the magic are version 0 movies, the bare joypad 1 bytes
+    pub fn read(reader: &mut impl Read) -> Result<Self, String> {
+        let mut data = Vec::new();
+        reader
+            .read_to_end(&mut data)
+            .map_err(|e| format!("Failed to read the movie: {}", e))?;
+        let Some(mut data) = data.strip_prefix(MAGIC) else {
+            return Ok(Self {
+                header: Header::default(),
+                frames: data
+                    .iter()
+                    .map(|&port1| FrameInput {
+                        ports: [port1, 0],
+                        command: None,
+                    })
+                    .collect(),
+            });
+        };
+
+        let header = Header::read(&mut data)?;
+        if data.len() % FRAME_SIZE != 0 {
+            return Err("truncated movie frame".to_string());
+        }
+        let frames = data
+            .chunks_exact(FRAME_SIZE)
+            .map(|bytes| FrameInput::from_bytes(bytes.try_into().unwrap()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { header, frames })
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        self.header.write(writer)?;
+        self.frames
+            .iter()
+            .try_for_each(|frame| write_frame(writer, frame))
+    }
+}
+
+/*
+ * Loads a movie, or an FCEUX .fm2 movie, to play with the ROM. The mismatches that can make
+ * the playback go out of sync are reported, the movie still plays.
+ */
+pub fn load(path: &std::path::Path, rom_info: &RomInfo) -> Result<Movie, String> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut reader = std::io::BufReader::new(file);
+    let mut mismatches = Vec::new();
+    let movie = if is_fm2(path) {
+        let fm2 = Fm2::read(&mut reader).map_err(|e| format!("{}: {}", path.display(), e))?;
+        mismatches = fm2.check(rom_info);
+        fm2.into_movie(rom_info)
+    } else {
+        Movie::read(&mut reader).map_err(|e| format!("{}: {}", path.display(), e))?
+    };
+    mismatches.extend(movie.header.check(rom_info));
+    for mismatch in mismatches {
+        eprintln!("warning: {}: {}", path.display(), mismatch);
+    }
+    Ok(movie)
+}
+
+pub fn is_fm2(path: &std::path::Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("fm2"))
+}
+
+pub fn write_frame(writer: &mut impl Write, frame: &FrameInput) -> std::io::Result<()> {
+    writer.write_all(&frame.to_bytes())
+}
+
+fn read_bytes(reader: &mut impl Read, len: usize) -> Result<Vec<u8>, String> {
+    // a broken length fails at the end of the file, not on the allocation
+    let mut buf = Vec::new();
+    match reader.take(len as u64).read_to_end(&mut buf) {
+        Ok(read) if read == len => Ok(buf),
+        _ => Err("truncated movie header".to_string()),
+    }
+}
+
+fn read_u8(reader: &mut impl Read) -> Result<u8, String> {
+    Ok(read_bytes(reader, 1)?[0])
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32, String> {
+    Ok(u32::from_le_bytes(
+        read_bytes(reader, 4)?.try_into().unwrap(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom_info() -> RomInfo {
+        RomInfo {
+            name: "game.nes".to_string(),
+            cksum: 0x1234_5678,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_write_read() {
+        let mut movie = Movie {
+            header: Header::new(&rom_info(), RamPattern::Random(42)),
+            frames: vec![
+                FrameInput {
+                    ports: [0x01, 0x80],
+                    command: None,
+                },
+                FrameInput {
+                    ports: [0x00, 0x00],
+                    command: Some(Command::Reset),
+                },
+                FrameInput {
+                    ports: [0x08, 0x00],
+                    command: Some(Command::PowerCycle),
+                },
+            ],
+        };
+        movie.header.rerecords = 7;
+        let mut data = Vec::new();
+        movie.write(&mut data).unwrap();
+        assert_eq!(&data[..4], MAGIC);
+        assert_eq!(
+            data[RERECORDS_OFFSET as usize..RERECORDS_OFFSET as usize + 4],
+            [7, 0, 0, 0]
+        );
+        assert_eq!(Movie::read(&mut data.as_slice()), Ok(movie.clone()));
+
+        movie.header.start = Start::Savestate(vec![1, 2, 3]);
+        let mut data = Vec::new();
+        movie.write(&mut data).unwrap();
+        assert_eq!(Movie::read(&mut data.as_slice()), Ok(movie));
+
+        assert_eq!(
+            Movie::read(&mut &data[..data.len() - 1]).unwrap_err(),
+            "truncated movie frame"
+        );
+        assert!(Movie::read(&mut &data[..10]).is_err());
+    }
+
+    #[test]
+    fn test_read_version_0() {
+        let movie = Movie::read(&mut [0x81, 0x01].as_slice()).unwrap();
+        assert_eq!(movie.header.version, 0);
+        assert_eq!(
+            movie.frames.iter().map(|f| f.ports).collect::<Vec<_>>(),
+            vec![[0x81, 0x00], [0x01, 0x00]]
+        );
+        assert!(movie.header.check(&rom_info()).is_empty());
+    }
+
+    #[test]
+    fn test_check() {
+        let mut header = Header::new(&rom_info(), RamPattern::Zeros);
+        assert!(header.check(&rom_info()).is_empty());
+        header.rom_checksum = 0xDEAD_BEEF;
+        header.region = Region::Pal;
+        assert_eq!(
+            header.check(&rom_info()),
+            vec![
+                "the movie was recorded with another ROM (checksum DEADBEEF, game.nes is 12345678)",
+                "the movie was recorded on a PAL console",
+            ]
+        );
+    }
+}
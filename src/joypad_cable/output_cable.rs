@@ -1,21 +1,166 @@
-use super::JoypadCable;
-use std::io::Write;
+use super::movie::{self, Header};
+use super::{FrameInput, JoypadCable, Movie, MovieMode};
+use std::io::{Seek, SeekFrom, Write};
 
-pub struct OutputCable<C: JoypadCable, W: Write> {
+// the movies are cut at the frame of a save state loaded in read-write mode
+pub trait Truncate {
+    fn truncate(&mut self, len: u64) -> std::io::Result<()>;
+}
+
+impl Truncate for std::fs::File {
+    fn truncate(&mut self, len: u64) -> std::io::Result<()> {
+        self.set_len(len)
+    }
+}
+
+impl Truncate for std::io::Cursor<Vec<u8>> {
+    fn truncate(&mut self, len: u64) -> std::io::Result<()> {
+        self.get_mut().truncate(len as usize);
+        Ok(())
+    }
+}
+
+/*
+ * Writes the frames recorded to a movie file. When the movie is played the file already has
+ * them, only a save state loaded in read-write mode starts the recording again.
+ */
+pub struct OutputCable<C: JoypadCable, W: Write + Seek + Truncate> {
     cable: C,
     writer: W,
+    header_len: u64,
+    frame: usize,
+    recording: bool,
+    rerecords: u32,
 }
 
-impl<C: JoypadCable, W: Write> OutputCable<C, W> {
-    pub fn new(cable: C, writer: W) -> Self {
-        Self { cable, writer }
+impl<C: JoypadCable, W: Write + Seek + Truncate> OutputCable<C, W> {
+    // records a new movie, the header is written when the cable is created
+    pub fn new(cable: C, mut writer: W, header: &Header) -> std::io::Result<Self> {
+        header.write(&mut writer)?;
+        Ok(Self {
+            cable,
+            header_len: writer.stream_position()?,
+            writer,
+            frame: 0,
+            recording: true,
+            rerecords: header.rerecords,
+        })
+    }
+
+    // the file of a movie being played, which is written again only from a read-write load
+    pub fn resume(cable: C, writer: W, movie: &Movie) -> std::io::Result<Self> {
+        let mut header = Vec::new();
+        movie.header.write(&mut header)?;
+        Ok(Self {
+            cable,
+            writer,
+            header_len: header.len() as u64,
+            frame: 0,
+            recording: false,
+            rerecords: movie.header.rerecords,
+        })
+    }
+
+    fn frame_offset(&self, frame: usize) -> u64 {
+        self.header_len + (frame * movie::FRAME_SIZE) as u64
+    }
+
+    fn truncate(&mut self) -> std::io::Result<()> {
+        let len = self.frame_offset(self.frame);
+        self.writer.truncate(len)?;
+        self.writer.seek(SeekFrom::Start(movie::RERECORDS_OFFSET))?;
+        self.writer.write_all(&self.rerecords.to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(len))?;
+        Ok(())
     }
 }
 
-impl<C: JoypadCable, W: Write> JoypadCable for OutputCable<C, W> {
-    fn write(&mut self, state: u8) {
-        let buf = [state];
-        self.cable.write(state);
-        self.writer.write_all(&buf).unwrap();
+impl<C: JoypadCable, W: Write + Seek + Truncate> JoypadCable for OutputCable<C, W> {
+    fn write(&mut self, input: FrameInput) -> FrameInput {
+        let input = self.cable.write(input);
+        if self.recording {
+            movie::write_frame(&mut self.writer, &input).unwrap();
+        }
+        self.frame += 1;
+        input
+    }
+
+    fn position(&self) -> usize {
+        self.frame
+    }
+
+    fn seek(&mut self, position: usize, mode: MovieMode) -> Result<(), String> {
+        self.cable.seek(position, mode)?;
+        self.frame = position;
+        self.recording = mode == MovieMode::ReadWrite;
+        if self.recording {
+            self.rerecords += 1;
+            self.truncate()
+                .map_err(|e| format!("Failed to write the movie: {}", e))?;
+        }
+        Ok(())
+    }
+
+    fn finished(&self) -> bool {
+        self.cable.finished()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::{cartridge::RomInfo, RamPattern};
+    use crate::joypad_handler::Joypads;
+    use std::io::Cursor;
+
+    fn frame(port1: u8) -> FrameInput {
+        FrameInput {
+            ports: [port1, 0],
+            command: None,
+        }
+    }
+
+    fn read(cable: &OutputCable<Joypads, Cursor<Vec<u8>>>) -> Movie {
+        Movie::read(&mut cable.writer.get_ref().as_slice()).unwrap()
+    }
+
+    #[test]
+    fn test_record() {
+        let header = Header::new(&RomInfo::default(), RamPattern::Zeros);
+        let mut cable = OutputCable::new(Joypads::new(), Cursor::new(Vec::new()), &header).unwrap();
+        for port1 in 1..=3 {
+            cable.write(frame(port1));
+        }
+        assert_eq!(read(&cable).frames, vec![frame(1), frame(2), frame(3)]);
+
+        // played again in read-only mode, the file doesn't change
+        cable.seek(1, MovieMode::ReadOnly).unwrap();
+        cable.write(frame(2));
+        assert_eq!(read(&cable).frames.len(), 3);
+
+        cable.seek(1, MovieMode::ReadWrite).unwrap();
+        cable.write(frame(5));
+        let movie = read(&cable);
+        assert_eq!(movie.frames, vec![frame(1), frame(5)]);
+        assert_eq!(movie.header.rerecords, 1);
+    }
+
+    #[test]
+    fn test_resume() {
+        let movie = Movie {
+            header: Header::new(&RomInfo::default(), RamPattern::Zeros),
+            frames: vec![frame(1), frame(2)],
+        };
+        let mut file = Vec::new();
+        movie.write(&mut file).unwrap();
+        let mut cable = OutputCable::resume(Joypads::new(), Cursor::new(file), &movie).unwrap();
+        cable.write(frame(1));
+        cable.write(frame(2));
+        cable.write(frame(3));
+        assert_eq!(read(&cable), movie);
+
+        cable.seek(2, MovieMode::ReadWrite).unwrap();
+        cable.write(frame(4));
+        assert_eq!(read(&cable).frames, vec![frame(1), frame(2), frame(4)]);
     }
 }
@@ -0,0 +1,152 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::{FrameInput, JoypadCable, MovieMode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TurboMode {
+    // the buttons fire while the turbo buttons are held
+    Hold,
+    // a turbo button starts the autofire, pressing it again stops it
+    Toggle,
+}
+
+#[derive(Debug)]
+struct TurboState {
+    frames: usize,
+    mode: TurboMode,
+    // the turbo buttons held on each port, as joypad bits
+    held: [u8; 2],
+    toggled: [u8; 2],
+}
+
+/*
+ * The turbo buttons of the ui, pressing A or B every few frames. The presses follow the frame
+ * count of the cable, so the same frames are pressed when a save state is loaded, and they are
+ * recorded like the other buttons.
+ */
+pub struct Turbo {
+    state: Rc<RefCell<TurboState>>,
+}
+
+impl Turbo {
+    // a press and a release take `frames` frames, 2 fires at 30 Hz, 3 at 20 Hz and 4 at 15 Hz
+    pub fn new(frames: usize, mode: TurboMode) -> Self {
+        assert!(frames >= 2, "a turbo press takes at least 2 frames");
+        Self {
+            state: Rc::new(RefCell::new(TurboState {
+                frames,
+                mode,
+                held: [0; 2],
+                toggled: [0; 2],
+            })),
+        }
+    }
+
+    pub fn cable(&self, cable: Box<dyn JoypadCable>) -> TurboCable {
+        TurboCable {
+            state: self.state.clone(),
+            cable,
+            frame: 0,
+        }
+    }
+
+    // the turbo buttons held on a port
+    pub fn hold(&self, port: usize, buttons: u8) {
+        let mut state = self.state.borrow_mut();
+        if state.mode == TurboMode::Toggle {
+            state.toggled[port] ^= buttons & !state.held[port];
+        }
+        state.held[port] = buttons;
+    }
+}
+
+pub struct TurboCable {
+    state: Rc<RefCell<TurboState>>,
+    cable: Box<dyn JoypadCable>,
+    frame: usize,
+}
+
+impl JoypadCable for TurboCable {
+    fn write(&mut self, mut input: FrameInput) -> FrameInput {
+        let state = self.state.borrow();
+        // pressed on the first half of the period, at least one frame released
+        if self.frame % state.frames < state.frames / 2 {
+            for (port, buttons) in input.ports.iter_mut().enumerate() {
+                *buttons |= match state.mode {
+                    TurboMode::Hold => state.held[port],
+                    TurboMode::Toggle => state.toggled[port],
+                };
+            }
+        }
+        drop(state);
+        self.frame += 1;
+        self.cable.write(input)
+    }
+
+    fn position(&self) -> usize {
+        self.frame
+    }
+
+    fn seek(&mut self, position: usize, mode: MovieMode) -> Result<(), String> {
+        self.cable.seek(position, mode)?;
+        self.frame = position;
+        Ok(())
+    }
+
+    fn finished(&self) -> bool {
+        self.cable.finished()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Joypads;
+
+    impl JoypadCable for Joypads {
+        fn write(&mut self, input: FrameInput) -> FrameInput {
+            input
+        }
+    }
+
+    fn ports(cable: &mut TurboCable, frames: usize) -> Vec<u8> {
+        (0..frames)
+            .map(|_| cable.write(FrameInput::default()).ports[0])
+            .collect()
+    }
+
+    #[test]
+    fn test_hold() {
+        let turbo = Turbo::new(3, TurboMode::Hold);
+        let mut cable = turbo.cable(Box::new(Joypads));
+        turbo.hold(0, 0x01);
+        assert_eq!(ports(&mut cable, 6), [1, 0, 0, 1, 0, 0]);
+        turbo.hold(0, 0x03);
+        let input = cable.write(FrameInput {
+            ports: [0x08, 0x00],
+            command: None,
+        });
+        assert_eq!(input.ports, [0x0B, 0x00]);
+
+        // the same frames are pressed from a save state
+        cable.seek(4, MovieMode::ReadWrite).unwrap();
+        assert_eq!(ports(&mut cable, 3), [0, 0, 3]);
+        turbo.hold(0, 0x00);
+        assert_eq!(ports(&mut cable, 3), [0, 0, 0]);
+    }
+
+    #[test]
+    fn test_toggle() {
+        let turbo = Turbo::new(4, TurboMode::Toggle);
+        let mut cable = turbo.cable(Box::new(Joypads));
+        turbo.hold(0, 0x02);
+        assert_eq!(ports(&mut cable, 4), [2, 2, 0, 0]);
+        turbo.hold(0, 0x00);
+        assert_eq!(ports(&mut cable, 4), [2, 2, 0, 0]);
+        turbo.hold(0, 0x02);
+        turbo.hold(0, 0x00);
+        assert_eq!(ports(&mut cable, 4), [0, 0, 0, 0]);
+    }
+}
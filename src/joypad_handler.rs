@@ -33,13 +33,35 @@ impl emulator::InputPort for JoypadHandler {
         self.joypad_mut().serial_read()
     }
 
+    fn peek(&self) -> u8 {
+        self.joypad.borrow().peek()
+    }
+
     fn write(&mut self, val: u8) {
         self.joypad_mut().set_strobe(val & 0x01 != 0);
     }
 }
 
-impl joypad_cable::JoypadCable for JoypadHandler {
-    fn write(&mut self, state: u8) {
-        self.joypad_mut().state = state.into();
+// the joypads of both ports, the end of the cables
+#[derive(Clone)]
+pub struct Joypads([JoypadHandler; 2]);
+
+impl Joypads {
+    pub fn new() -> Self {
+        Self([JoypadHandler::new(), JoypadHandler::new()])
+    }
+
+    pub fn connect(&self, emulator: &mut emulator::Emulator) {
+        emulator.connect_port1(Some(Box::new(self.0[0].clone())));
+        emulator.connect_port2(Some(Box::new(self.0[1].clone())));
+    }
+}
+
+impl joypad_cable::JoypadCable for Joypads {
+    fn write(&mut self, input: joypad_cable::FrameInput) -> joypad_cable::FrameInput {
+        for (joypad, state) in self.0.iter().zip(input.ports) {
+            joypad.joypad_mut().state = state.into();
+        }
+        input
     }
 }
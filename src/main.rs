@@ -1,35 +1,387 @@
 #[macro_use]
 mod log;
 
+mod debug_console;
 mod emulator;
+mod gdb_stub;
+mod golden;
+mod headless;
 mod joypad_handler;
+mod screenshot;
+mod script;
 mod ui;
-use std::path::PathBuf;
+use std::{ops::RangeInclusive, path::PathBuf};
 
 use emulator::input_devices;
-use joypad_cable::JoypadCable;
+use joypad_cable::{movie, JoypadCable};
 mod joypad_cable;
 use clap::{arg, value_parser, Command};
 
 fn cli() -> Command {
     clap::Command::new("sunrest")
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
+        .subcommand(run_cli())
+        .subcommand(golden_cli())
+        .subcommand(convert_cli())
         .arg(clap::arg!(<ROM> "Path to a ROM file").value_parser(value_parser!(PathBuf)))
         .arg(arg!(--volume <num> "Volume of the audio").value_parser(value_parser!(f32)))
         .arg(arg!(--speed <num> "Speed of the emulation").value_parser(value_parser!(f32)))
         .arg(
-            arg!(--replay <FILE> "Path to the replay file")
-                .conflicts_with("record")
+            arg!(--"screenshot-dir" <DIR> "Directory of the screenshots")
                 .value_parser(value_parser!(PathBuf)),
         )
         .arg(
-            arg!(--record <FILE> "Path to save the replay file")
-                .conflicts_with("replay")
+            arg!(--keys <FILE> "Key bindings of the joypads and the hotkeys")
                 .value_parser(value_parser!(PathBuf)),
         )
+        .arg(
+            arg!(--"pad-ports" <PORTS> "Joypad port of each game controller, like 1,2,0,0 (0: none)")
+                .value_parser(ui::parse_pad_ports),
+        )
+        .arg(
+            arg!(--"turbo-frames" <N> "Frames of a turbo press and release: 2 (30 Hz), 3 (20 Hz), 4 (15 Hz)...")
+                .value_parser(value_parser!(u64).range(2..)),
+        )
+        .arg(arg!(--"turbo-toggle" "The turbo buttons start and stop the autofire instead of being held"))
+        .arg(
+            arg!(--"fds-bios" <FILE> "Path to the FDS BIOS, required to load .fds disk images")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--trace <FILE> "Write a nestest.log style trace of the executed instructions")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"trace-pc" <RANGE> "Only trace the instructions in this PC range (eg. C000-C0FF)")
+                .requires("trace")
+                .value_parser(parse_pc_range),
+        )
+        .arg(
+            arg!(--"trace-frames" <RANGE> "Only trace the instructions in these frames (eg. 10-20)")
+                .requires("trace")
+                .value_parser(parse_frame_range),
+        )
+        .arg(
+            arg!(--ram <PATTERN> "Power-on RAM contents: zeros, ones, alternating, random or random:<seed>")
+                .value_parser(parse_ram_pattern),
+        )
+        .arg(
+            arg!(--cheats <FILE> "Cheats file (default: the ROM path with the .cht extension)")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--cheat <CODE> "Game Genie or Pro Action Replay code, can be repeated")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(arg!(--"no-cheats" "Start with the cheats disabled"))
+        .arg(
+            arg!(--script <FILE> "Lua script to run with the emulator")
+                .conflicts_with_all(["debug", "gdb"])
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(arg!(--debug "Run in the text debugger, without the graphical interface"))
+        .arg(
+            arg!(--gdb <ADDRESS> "Wait for GDB on host:port or unix:path, without the graphical interface")
+                .conflicts_with("debug"),
+        )
+        .arg(
+            arg!(--replay <FILE> "Play a movie recorded with --record")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--record <FILE> "Record the input of both joypads and the resets as a movie, or save the --replay changes")
+                .value_parser(value_parser!(PathBuf)),
+        )
+}
+
+fn run_cli() -> Command {
+    Command::new("run")
+        .about("Run a number of frames without the graphical interface")
+        .arg(clap::arg!(<ROM> "Path to a ROM file").value_parser(value_parser!(PathBuf)))
+        .arg(
+            arg!(--frames <N> "Number of frames to run")
+                .required(true)
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            arg!(--input <FILE> "Movie with the input, recorded with --record")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--screenshot <FILE> "Save the last frame as a PNG")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"screenshot-dir" <DIR> "Save the last frame as a timestamped PNG in this directory")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"screenshot-scale" <N> "Scale factor of the screenshots")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(arg!(--"crop-overscan" "Remove the first and the last 8 lines of the screenshots"))
+        .arg(
+            arg!(--ram <PATTERN> "Power-on RAM contents: zeros, ones, alternating, random or random:<seed>")
+                .value_parser(parse_ram_pattern),
+        )
+        .arg(
+            arg!(--cheats <FILE> "Cheats file (default: the ROM path with the .cht extension)")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--cheat <CODE> "Game Genie or Pro Action Replay code, can be repeated")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(arg!(--"no-cheats" "Start with the cheats disabled"))
+        .arg(
+            arg!(--script <FILE> "Lua script to run with the emulator")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"dump-audio" <FILE> "Save the audio as a WAV")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"fds-bios" <FILE> "Path to the FDS BIOS, required to load .fds disk images")
+                .value_parser(value_parser!(PathBuf)),
+        )
+}
+
+fn golden_cli() -> Command {
+    Command::new("golden")
+        .about("Check the last frame of ROMs against the hashes of a manifest")
+        .arg(
+            clap::arg!(<MANIFEST> "Path to the manifest, one <rom> <frames> <hash> [input] per line")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--roms <DIR> "Directory of the ROMs (default: the manifest directory)")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--output <DIR> "Where the actual and diff images of the failures are saved")
+                .default_value("golden-output")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(arg!(--update "Record the hashes and the reference images instead of checking them"))
+}
+
+fn convert_cli() -> Command {
+    Command::new("convert")
+        .about("Convert a movie between the Sunrest and the FCEUX (.fm2) formats")
+        .arg(clap::arg!(<ROM> "Path to the ROM of the movie").value_parser(value_parser!(PathBuf)))
+        .arg(clap::arg!(<INPUT> "Movie to convert").value_parser(value_parser!(PathBuf)))
+        .arg(
+            clap::arg!(<OUTPUT> "Converted movie, in the FCEUX format when it ends with .fm2")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"fds-bios" <FILE> "Path to the FDS BIOS, required to load .fds disk images")
+                .value_parser(value_parser!(PathBuf)),
+        )
+}
+
+fn open_cartridge(matches: &clap::ArgMatches) -> emulator::cartridge::Cartridge {
+    let rom_path = matches.get_one::<PathBuf>("ROM").unwrap();
+    let cartridge = if let Some(bios_path) = matches.get_one::<PathBuf>("fds-bios") {
+        emulator::cartridge::open_disk(rom_path, bios_path)
+    } else {
+        emulator::cartridge::open_rom(rom_path)
+    };
+    cartridge.unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    })
+}
+
+// a movie plays with the RAM pattern it was recorded with, the version 0 movies don't have one
+fn load_movie(
+    matches: &clap::ArgMatches,
+    id: &str,
+    cartridge: &emulator::cartridge::Cartridge,
+    ram_pattern: &mut emulator::RamPattern,
+) -> Option<movie::Movie> {
+    let path = matches.get_one::<PathBuf>(id)?;
+    let movie = movie::load(path, cartridge.rom_info()).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+    if movie.header.version > 0 {
+        *ram_pattern = movie.header.ram_pattern;
+    }
+    Some(movie)
+}
+
+/*
+ * The movie played is never written, its read-write changes are only saved in the --record file,
+ * which starts as a copy of the movie. Older and FCEUX movies are copied with a new header.
+ */
+fn replay_cable(
+    joypads: joypad_handler::Joypads,
+    mut movie: movie::Movie,
+    record_path: Option<&PathBuf>,
+    new_header: movie::Header,
+) -> Box<dyn JoypadCable> {
+    let Some(record_path) = record_path else {
+        return Box::new(joypad_cable::InputCable::new(joypads, movie));
+    };
+    if movie.header.version != movie::VERSION {
+        movie.header = movie::Header {
+            rerecords: movie.header.rerecords,
+            ..new_header
+        };
+    }
+    let cable = std::fs::File::create(record_path)
+        .and_then(|mut file| {
+            movie.write(&mut file)?;
+            joypad_cable::OutputCable::resume(joypads, file, &movie)
+        })
+        .unwrap_or_else(|err| {
+            eprintln!("Failed to write {}: {}", record_path.display(), err);
+            std::process::exit(1);
+        });
+    Box::new(joypad_cable::InputCable::new(cable, movie))
+}
+
+fn run_headless(matches: &clap::ArgMatches) {
+    let cartridge = open_cartridge(matches);
+    let mut ram_pattern = matches.get_one("ram").copied().unwrap_or_default();
+    let input = load_movie(matches, "input", &cartridge, &mut ram_pattern);
+    let options = headless::Options {
+        frames: *matches.get_one("frames").unwrap(),
+        input,
+        screenshot: matches.get_one("screenshot").cloned(),
+        screenshot_dir: matches.get_one("screenshot-dir").cloned(),
+        screenshot_options: screenshot::Options {
+            scale: matches.get_one("screenshot-scale").copied().unwrap_or(1),
+            crop_overscan: matches.get_flag("crop-overscan"),
+            ..Default::default()
+        },
+        dump_audio: matches.get_one("dump-audio").cloned(),
+        script: matches.get_one("script").cloned(),
+    };
+    let mut emulator = build_emulator(cartridge, ram_pattern);
+    if let Err(err) =
+        load_cheats(&mut emulator, matches).and_then(|_| headless::run(emulator, &options))
+    {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run_golden(matches: &clap::ArgMatches) {
+    let options = golden::Options {
+        manifest: matches.get_one::<PathBuf>("MANIFEST").unwrap().clone(),
+        roms_dir: matches.get_one("roms").cloned(),
+        output_dir: matches.get_one::<PathBuf>("output").unwrap().clone(),
+        update: matches.get_flag("update"),
+    };
+    if let Err(err) = golden::run(&options) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run_convert(matches: &clap::ArgMatches) {
+    let rom_info = open_cartridge(matches).rom_info().clone();
+    let input = matches.get_one::<PathBuf>("INPUT").unwrap();
+    let output = matches.get_one::<PathBuf>("OUTPUT").unwrap();
+    let result = movie::load(input, &rom_info).and_then(|movie| {
+        let mut writer = std::fs::File::create(output)
+            .map(std::io::BufWriter::new)
+            .map_err(|e| format!("Failed to create {}: {}", output.display(), e))?;
+        let result = if movie::is_fm2(output) {
+            if movie.header.ram_pattern != emulator::RamPattern::Alternating {
+                eprintln!(
+                    "warning: FCEUX starts with the alternating RAM pattern, the movie uses {}",
+                    movie.header.ram_pattern
+                );
+            }
+            let fm2 = joypad_cable::fm2::Fm2::from_movie(&movie, &rom_info)?;
+            fm2.write(&mut writer)
+        } else {
+            movie.write(&mut writer)
+        };
+        result
+            .and_then(|_| std::io::Write::flush(&mut writer))
+            .map_err(|e| format!("Failed to write {}: {}", output.display(), e))
+    });
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+// the --cheats file or, when it exists, the .cht file beside the ROM, plus the --cheat codes
+fn load_cheats(
+    emulator: &mut emulator::Emulator,
+    matches: &clap::ArgMatches,
+) -> Result<(), String> {
+    let default_path = matches
+        .get_one::<PathBuf>("ROM")
+        .unwrap()
+        .with_extension("cht");
+    let mut cheats = match matches.get_one::<PathBuf>("cheats") {
+        Some(path) => emulator::cheats::Cheats::load(path)?,
+        None if default_path.is_file() => emulator::cheats::Cheats::load(&default_path)?,
+        None => emulator::cheats::Cheats::default(),
+    };
+    for code in matches.get_many::<String>("cheat").unwrap_or_default() {
+        cheats.add(code, "", true)?;
+    }
+    cheats.enabled = !matches.get_flag("no-cheats");
+    emulator.set_cheats(cheats);
+    Ok(())
+}
+
+// random patterns are printed, and shown in the window title, so the run can be reproduced with
+// the same seed
+fn build_emulator(
+    cartridge: emulator::cartridge::Cartridge,
+    ram_pattern: emulator::RamPattern,
+) -> emulator::Emulator {
+    if let emulator::RamPattern::Random(_) = ram_pattern {
+        println!("power-on RAM: {}", ram_pattern);
+    }
+    emulator::Emulator::with_ram_pattern(cartridge, ram_pattern)
+}
+
+fn parse_ram_pattern(text: &str) -> Result<emulator::RamPattern, String> {
+    text.parse()
+}
+
+fn parse_pc_range(text: &str) -> Result<RangeInclusive<u16>, String> {
+    let parse = |addr: &str| {
+        u16::from_str_radix(addr.trim_start_matches('$'), 16)
+            .map_err(|_| format!("invalid address: {}", addr))
+    };
+    match text.split_once('-') {
+        Some((start, end)) => Ok(parse(start)?..=parse(end)?),
+        None => Err("expected START-END".to_string()),
+    }
+}
+
+fn parse_frame_range(text: &str) -> Result<RangeInclusive<usize>, String> {
+    let parse = |frame: &str| {
+        frame
+            .parse::<usize>()
+            .map_err(|_| format!("invalid frame: {}", frame))
+    };
+    match text.split_once('-') {
+        Some((start, end)) => Ok(parse(start)?..=parse(end)?),
+        None => Err("expected START-END".to_string()),
+    }
 }
 
 fn main() {
     let matches = cli().get_matches();
+    match matches.subcommand() {
+        Some(("run", matches)) => return run_headless(matches),
+        Some(("golden", matches)) => return run_golden(matches),
+        Some(("convert", matches)) => return run_convert(matches),
+        _ => {}
+    }
 
     let mut settings = ui::Settings::from_env();
     if let Some(volume) = matches.get_one::<f32>("volume") {
@@ -38,30 +390,117 @@ fn main() {
     if let Some(speed) = matches.get_one::<f32>("speed") {
         settings.speed = *speed;
     }
+    if let Some(dir) = matches.get_one::<PathBuf>("screenshot-dir") {
+        settings.screenshot_dir = dir.clone();
+    }
+    if let Some(ram_pattern) = matches.get_one("ram") {
+        settings.ram_pattern = *ram_pattern;
+    }
+    if let Some(path) = matches.get_one::<PathBuf>("keys") {
+        settings.keys = Some(path.clone());
+    }
+    if let Some(ports) = matches.get_one("pad-ports") {
+        settings.pad_ports = *ports;
+    }
+    if let Some(frames) = matches.get_one::<u64>("turbo-frames") {
+        settings.turbo_frames = *frames as usize;
+    }
+    if matches.get_flag("turbo-toggle") {
+        settings.turbo_toggle = true;
+    }
+    let bindings = match &settings.keys {
+        Some(path) => ui::Bindings::load(path).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }),
+        None => ui::Bindings::default(),
+    };
 
-    let rom_path = matches.get_one::<PathBuf>("ROM").unwrap();
-    let cartridge = emulator::cartridge::open_rom(rom_path);
+    let cartridge = open_cartridge(&matches);
+    let replay = load_movie(&matches, "replay", &cartridge, &mut settings.ram_pattern);
+    let mut emulator = build_emulator(cartridge, settings.ram_pattern);
+    if let Err(err) = load_cheats(&mut emulator, &matches) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+    if let Some(Err(err)) = replay
+        .as_ref()
+        .map(|movie| movie.header.start(&mut emulator))
+    {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+    if let Some(trace_path) = matches.get_one::<PathBuf>("trace") {
+        let condition = emulator::tracer::TraceCondition {
+            pc: matches.get_one("trace-pc").cloned(),
+            frames: matches.get_one("trace-frames").cloned(),
+        };
+        let tracer =
+            emulator::tracer::Tracer::create(trace_path, condition).unwrap_or_else(|err| {
+                eprintln!("Failed to create {}: {}", trace_path.display(), err);
+                std::process::exit(1);
+            });
+        emulator.set_tracer(Some(tracer));
+    }
+    if matches.get_flag("debug") {
+        let stdin = std::io::stdin();
+        debug_console::DebugConsole::new(emulator, stdin.lock(), std::io::stdout()).run();
+        return;
+    }
+    if let Some(address) = matches.get_one::<String>("gdb") {
+        if let Err(err) = gdb_stub::serve(emulator, address) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    let mut emulator = emulator::Emulator::new(cartridge);
-    let joypad1 = joypad_handler::JoypadHandler::new();
-    let joypad2 = joypad_handler::JoypadHandler::new();
-    emulator.connect_port1(Some(Box::new(joypad1.clone())));
-    emulator.connect_port2(Some(Box::new(joypad2.clone())));
+    let joypads = joypad_handler::Joypads::new();
+    joypads.connect(&mut emulator);
 
-    let mut ui = ui::Ui::<ui::engines::SdlEngine>::new(emulator, settings);
+    let script = matches.get_one::<PathBuf>("script").map(|path| {
+        script::Script::load(path, &mut emulator).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        })
+    });
 
-    let joypad1_cable: Box<dyn JoypadCable> =
-        if let Some(replay_path) = matches.get_one::<PathBuf>("replay") {
-            let file = std::fs::File::open(replay_path).unwrap();
-            Box::new(joypad_cable::InputCable::new(joypad1, file))
-        } else if let Some(record_path) = matches.get_one::<PathBuf>("record") {
-            let file = std::fs::File::create(record_path).unwrap();
-            Box::new(joypad_cable::OutputCable::new(joypad1, file))
-        } else {
-            Box::new(joypad1)
-        };
+    let (mut joypad_cable, movie_mode): (Box<dyn JoypadCable>, _) = if let Some(movie) = replay {
+        let header = movie::Header::new(&emulator.rom_info(), settings.ram_pattern);
+        let cable = replay_cable(joypads, movie, matches.get_one("record"), header);
+        (cable, Some(joypad_cable::MovieMode::ReadOnly))
+    } else if let Some(record_path) = matches.get_one::<PathBuf>("record") {
+        let header = movie::Header::new(&emulator.rom_info(), settings.ram_pattern);
+        let cable = std::fs::File::create(record_path)
+            .and_then(|file| joypad_cable::OutputCable::new(joypads, file, &header))
+            .unwrap_or_else(|err| {
+                eprintln!("Failed to create {}: {}", record_path.display(), err);
+                std::process::exit(1);
+            });
+        let cable = joypad_cable::InputCable::record(cable, header);
+        (Box::new(cable), Some(joypad_cable::MovieMode::ReadWrite))
+    } else {
+        (Box::new(joypads), None)
+    };
+
+    // the script input goes through the replay/record cable
+    if let Some(script) = &script {
+        joypad_cable = Box::new(script.cable(joypad_cable));
+    }
+    // the turbo presses are recorded with the other buttons
+    let turbo_mode = if settings.turbo_toggle {
+        joypad_cable::TurboMode::Toggle
+    } else {
+        joypad_cable::TurboMode::Hold
+    };
+    let turbo = joypad_cable::Turbo::new(settings.turbo_frames, turbo_mode);
+    let joypad_cable = Box::new(turbo.cable(joypad_cable));
 
-    ui.joypad1_cable = Some(joypad1_cable);
-    ui.joypad2_cable = Some(Box::new(joypad2));
+    let mut ui = ui::Ui::<ui::engines::SdlEngine>::new(emulator, settings);
+    ui.bindings = bindings;
+    ui.joypad_cable = Some(joypad_cable);
+    ui.turbo = Some(turbo);
+    ui.movie_mode = movie_mode;
+    ui.script = script;
     ui.run();
 }
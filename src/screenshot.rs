@@ -0,0 +1,193 @@
+use std::path::{Path, PathBuf};
+
+use crate::emulator::{Color, Emulator, Image};
+
+// NTSC TVs usually hide the first and the last 8 lines
+const OVERSCAN_LINES: usize = 8;
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub directory: PathBuf,
+    pub scale: usize,
+    pub crop_overscan: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("."),
+            scale: 1,
+            crop_overscan: false,
+        }
+    }
+}
+
+// saves the current frame as <directory>/<name>-<timestamp>.png and returns its path
+pub fn capture(emulator: &Emulator, name: &str, options: &Options) -> Result<PathBuf, String> {
+    std::fs::create_dir_all(&options.directory)
+        .map_err(|e| format!("Failed to create {}: {}", options.directory.display(), e))?;
+    let path = options.directory.join(format!(
+        "{}-{}.png",
+        name,
+        timestamp(std::time::SystemTime::now())
+    ));
+    save_png(&prepare(&emulator.framebuffer_image(), options), &path)?;
+    Ok(path)
+}
+
+pub fn prepare(image: &Image, options: &Options) -> Image {
+    let (top, height) = if options.crop_overscan {
+        (OVERSCAN_LINES, image.height - OVERSCAN_LINES * 2)
+    } else {
+        (0, image.height)
+    };
+    let scale = options.scale.max(1);
+    let mut prepared = Image::new(image.width * scale, height * scale);
+    for y in 0..prepared.height {
+        for x in 0..prepared.width {
+            prepared.set(x, y, image.get(x / scale, top + y / scale));
+        }
+    }
+    prepared
+}
+
+pub fn save_png(image: &Image, path: &Path) -> Result<(), String> {
+    let file = std::fs::File::create(path)
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    let mut encoder = png::Encoder::new(
+        std::io::BufWriter::new(file),
+        image.width as u32,
+        image.height as u32,
+    );
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let data: Vec<u8> = image
+        .pixels
+        .iter()
+        .flat_map(|color| [color.0, color.1, color.2])
+        .collect();
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&data))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+pub fn load_png(path: &Path) -> Result<Image, String> {
+    let error = |e: png::DecodingError| format!("Failed to read {}: {}", path.display(), e);
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut decoder = png::Decoder::new(std::io::BufReader::new(file));
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(error)?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).map_err(error)?;
+    let channels = match info.color_type {
+        png::ColorType::Rgb => 3,
+        png::ColorType::Rgba => 4,
+        color_type => {
+            return Err(format!(
+                "Failed to read {}: unsupported color type {:?}",
+                path.display(),
+                color_type
+            ))
+        }
+    };
+    Ok(Image {
+        width: info.width as usize,
+        height: info.height as usize,
+        pixels: data[..info.buffer_size()]
+            .chunks_exact(channels)
+            .map(|pixel| Color(pixel[0], pixel[1], pixel[2]))
+            .collect(),
+    })
+}
+
+// UTC, as YYYYMMDD-HHMMSS-mmm
+fn timestamp(time: std::time::SystemTime) -> String {
+    let since_epoch = time
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}",
+        year,
+        month,
+        day,
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_png() {
+        let mut image = Image::new(3, 2);
+        image.set(2, 1, Color(0x10, 0x20, 0x30));
+        let path = std::env::temp_dir().join(format!("sunrest-{}.png", std::process::id()));
+        save_png(&image, &path).unwrap();
+
+        let decoder = png::Decoder::new(std::fs::File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!((info.width, info.height), (3, 2));
+        assert_eq!(&data[15..18], &[0x10, 0x20, 0x30]);
+    }
+
+    #[test]
+    fn test_load_png() {
+        let mut image = Image::new(2, 3);
+        image.set(1, 2, Color(0x10, 0x20, 0x30));
+        let path = std::env::temp_dir().join(format!("sunrest-load-{}.png", std::process::id()));
+        save_png(&image, &path).unwrap();
+        let loaded = load_png(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, image);
+    }
+
+    #[test]
+    fn test_prepare() {
+        let mut image = Image::new(2, 20);
+        image.set(1, 8, Color(0xFF, 0x00, 0x00));
+        let options = Options {
+            scale: 2,
+            crop_overscan: true,
+            ..Default::default()
+        };
+        let prepared = prepare(&image, &options);
+        assert_eq!((prepared.width, prepared.height), (4, 8));
+        assert_eq!(prepared.get(2, 0), Color(0xFF, 0x00, 0x00));
+        assert_eq!(prepared.get(3, 1), Color(0xFF, 0x00, 0x00));
+        assert_eq!(prepared.get(1, 0), Color(0x00, 0x00, 0x00));
+        assert_eq!(prepared.get(2, 2), Color(0x00, 0x00, 0x00));
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let time = std::time::UNIX_EPOCH + std::time::Duration::from_millis(1_709_251_199_123);
+        assert_eq!(timestamp(time), "20240229-235959-123");
+    }
+}
@@ -0,0 +1,591 @@
+pub mod overlay;
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::rc::Rc;
+
+use mlua::{Function, Lua, RegistryKey, Scope, Table, UserDataRef, Value};
+
+use crate::emulator::{debugger::Access, Color, Emulator, Image, TimeMachine};
+use crate::joypad_cable::{FrameInput, JoypadCable, MovieMode};
+use overlay::Shape;
+
+const WHITE: Color = Color(0xFF, 0xFF, 0xFF);
+const INPUT_PORTS: [u16; 2] = [0x4016, 0x4017];
+const BUTTONS: [&str; 8] = ["a", "b", "select", "start", "up", "down", "left", "right"];
+
+#[derive(Debug, Clone, Copy)]
+enum Event {
+    Frame(usize),
+    Read(u16),
+    Write(u16),
+    Exec(u16),
+    // the first read of a joypad port in the frame
+    Input(usize),
+}
+
+#[derive(Default)]
+struct Callbacks {
+    frame: Vec<RegistryKey>,
+    read: BTreeMap<u16, Vec<RegistryKey>>,
+    write: BTreeMap<u16, Vec<RegistryKey>>,
+    exec: BTreeMap<u16, Vec<RegistryKey>>,
+    input: Vec<RegistryKey>,
+}
+
+struct SavedState(TimeMachine);
+
+impl mlua::UserData for SavedState {}
+
+/*
+ * A Lua script driving the emulator. The script registers callbacks (on frame end, on a memory
+ * read/write/exec and on the joypads being read) and the functions accessing the emulator are
+ * only available while the script or a callback runs, so they must be called through their
+ * tables (eg. memory.read) instead of being stored in a variable.
+ */
+pub struct Script {
+    lua: Lua,
+    callbacks: RefCell<Callbacks>,
+    shapes: RefCell<Vec<Shape>>,
+    // set by the script for the next frame, applied by the ScriptCable
+    joypads: Rc<RefCell<[Option<u8>; 2]>>,
+    polled: [bool; 2],
+}
+
+// Emulator::clock through the script, which is dropped when it fails
+pub fn clock(script: &mut Option<Script>, emulator: &mut Emulator) -> Result<(), String> {
+    let Some(running) = script.as_mut() else {
+        emulator.clock();
+        return Ok(());
+    };
+    let result = running.clock(emulator);
+    if result.is_err() {
+        if let Some(script) = script.take() {
+            script.detach(emulator);
+        }
+    }
+    result
+}
+
+impl Script {
+    pub fn load(path: &Path, emulator: &mut Emulator) -> Result<Self, String> {
+        let code = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::new(&code, &path.display().to_string(), emulator)
+    }
+
+    pub fn new(code: &str, name: &str, emulator: &mut Emulator) -> Result<Self, String> {
+        let script = Self {
+            lua: Lua::new(),
+            callbacks: RefCell::new(Callbacks::default()),
+            shapes: RefCell::new(Vec::new()),
+            joypads: Rc::new(RefCell::new([None; 2])),
+            polled: [false; 2],
+        };
+        script
+            .with_api(emulator, |lua| lua.load(code).set_name(name).exec())
+            .map_err(|e| e.to_string())?;
+        Ok(script)
+    }
+
+    // the joypad states set by the script replace the ones written to the cable
+    pub fn cable(&self, cable: Box<dyn JoypadCable>) -> ScriptCable {
+        ScriptCable {
+            joypads: self.joypads.clone(),
+            cable,
+        }
+    }
+
+    // same as Emulator::clock, but calling the exec, read, write and input callbacks
+    pub fn clock(&mut self, emulator: &mut Emulator) -> Result<(), String> {
+        let exec = match emulator.next_instruction() {
+            Some(pc) if self.callbacks.borrow().exec.contains_key(&pc) => {
+                self.dispatch(emulator, Event::Exec(pc))
+            }
+            _ => Ok(()),
+        };
+        emulator.clock();
+        exec?;
+
+        for (access, addr) in emulator.take_watchpoint_hits() {
+            if let Some(port) = INPUT_PORTS.iter().position(|&a| a == addr) {
+                if access == Access::Read && !self.polled[port] {
+                    self.polled[port] = true;
+                    self.dispatch(emulator, Event::Input(port + 1))?;
+                }
+            }
+            let event = match access {
+                Access::Read => Event::Read(addr),
+                Access::Write => Event::Write(addr),
+            };
+            self.dispatch(emulator, event)?;
+        }
+        Ok(())
+    }
+
+    // called when the PPU starts a new frame, before the joypads are written
+    pub fn frame_end(&mut self, emulator: &mut Emulator) -> Result<(), String> {
+        self.shapes.borrow_mut().clear();
+        self.polled = [false; 2];
+        self.dispatch(emulator, Event::Frame(emulator.frame()))
+    }
+
+    // removes the watchpoints of the callbacks, nothing would take their hits anymore
+    pub fn detach(self, emulator: &mut Emulator) {
+        let callbacks = self.callbacks.into_inner();
+        for &addr in callbacks.read.keys() {
+            emulator.set_watchpoint(Access::Read, addr, false);
+        }
+        for &addr in callbacks.write.keys() {
+            emulator.set_watchpoint(Access::Write, addr, false);
+        }
+        if !callbacks.input.is_empty() {
+            for addr in INPUT_PORTS {
+                emulator.set_watchpoint(Access::Read, addr, false);
+            }
+        }
+        emulator.take_watchpoint_hits();
+    }
+
+    // draws the shapes of the last frame callbacks
+    pub fn draw_overlay(&self, image: &mut Image) {
+        for shape in self.shapes.borrow().iter() {
+            shape.draw(image);
+        }
+    }
+
+    fn dispatch(&self, emulator: &mut Emulator, event: Event) -> Result<(), String> {
+        let value = |addr| emulator.peek_memory(addr) as u32;
+        let (args, addr) = match event {
+            Event::Frame(frame) => (vec![frame as u32], None),
+            Event::Read(addr) => (vec![addr as u32, value(addr)], Some(addr)),
+            Event::Write(addr) => (vec![addr as u32, value(addr)], Some(addr)),
+            Event::Exec(addr) => (vec![addr as u32], Some(addr)),
+            Event::Input(port) => (vec![port as u32], None),
+        };
+        let functions: Vec<Function> = {
+            let callbacks = self.callbacks.borrow();
+            let keys = match (event, addr) {
+                (Event::Frame(_), _) => Some(&callbacks.frame),
+                (Event::Input(_), _) => Some(&callbacks.input),
+                (Event::Read(_), Some(addr)) => callbacks.read.get(&addr),
+                (Event::Write(_), Some(addr)) => callbacks.write.get(&addr),
+                (Event::Exec(_), Some(addr)) => callbacks.exec.get(&addr),
+                _ => None,
+            };
+            keys.into_iter()
+                .flatten()
+                .map(|key| self.lua.registry_value(key))
+                .collect::<mlua::Result<_>>()
+                .map_err(|e| e.to_string())?
+        };
+        if functions.is_empty() {
+            return Ok(());
+        }
+        self.with_api(emulator, |_| {
+            functions.iter().try_for_each(|function| {
+                function.call::<_, ()>(mlua::Variadic::from_iter(args.iter().copied()))
+            })
+        })
+        .map_err(|e| e.to_string())
+    }
+
+    // runs f with the API tables pointing to the emulator
+    fn with_api(
+        &self,
+        emulator: &mut Emulator,
+        f: impl FnOnce(&Lua) -> mlua::Result<()>,
+    ) -> mlua::Result<()> {
+        let emulator = RefCell::new(emulator);
+        self.lua.scope(|scope| {
+            self.install_api(scope, &emulator)?;
+            f(&self.lua)
+        })
+    }
+
+    fn install_api<'lua, 'scope>(
+        &'scope self,
+        scope: &Scope<'lua, 'scope>,
+        emulator: &'scope RefCell<&mut Emulator>,
+    ) -> mlua::Result<()> {
+        let lua = &self.lua;
+        let globals = lua.globals();
+
+        let memory = lua.create_table()?;
+        memory.set(
+            "read",
+            scope.create_function(move |_, addr: u16| Ok(emulator.borrow().peek_memory(addr)))?,
+        )?;
+        memory.set(
+            "read_word",
+            scope.create_function(move |_, addr: u16| {
+                let emulator = emulator.borrow();
+                let lo = emulator.peek_memory(addr) as u16;
+                let hi = emulator.peek_memory(addr.wrapping_add(1)) as u16;
+                Ok(hi << 8 | lo)
+            })?,
+        )?;
+        memory.set(
+            "write",
+            scope.create_function(move |_, (addr, val): (u16, u8)| {
+                emulator.borrow_mut().write_memory(addr, val);
+                Ok(())
+            })?,
+        )?;
+        for (name, access) in [
+            ("on_read", Some(Access::Read)),
+            ("on_write", Some(Access::Write)),
+            ("on_exec", None),
+        ] {
+            memory.set(
+                name,
+                scope.create_function(move |lua, (addr, function): (u16, Function)| {
+                    let key = lua.create_registry_value(function)?;
+                    let mut callbacks = self.callbacks.borrow_mut();
+                    let map = match access {
+                        Some(Access::Read) => &mut callbacks.read,
+                        Some(Access::Write) => &mut callbacks.write,
+                        None => &mut callbacks.exec,
+                    };
+                    map.entry(addr).or_default().push(key);
+                    if let Some(access) = access {
+                        emulator.borrow_mut().set_watchpoint(access, addr, true);
+                    }
+                    Ok(())
+                })?,
+            )?;
+        }
+        globals.set("memory", memory)?;
+
+        let cpu = lua.create_table()?;
+        cpu.set(
+            "registers",
+            scope.create_function(move |lua, ()| {
+                let regs = emulator.borrow().registers();
+                let table = lua.create_table()?;
+                table.set("a", regs.a)?;
+                table.set("x", regs.x)?;
+                table.set("y", regs.y)?;
+                table.set("p", regs.p)?;
+                table.set("sp", regs.sp)?;
+                table.set("pc", regs.pc)?;
+                Ok(table)
+            })?,
+        )?;
+        globals.set("cpu", cpu)?;
+
+        let joypad = lua.create_table()?;
+        joypad.set(
+            "set",
+            scope.create_function(move |_, (port, buttons): (usize, Value)| {
+                let state = match buttons {
+                    Value::Integer(state) => state as u8,
+                    Value::Table(table) => buttons_state(&table)?,
+                    _ => return Err(mlua::Error::runtime("expected a table or an integer")),
+                };
+                match self.joypads.borrow_mut().get_mut(port.wrapping_sub(1)) {
+                    Some(joypad) => *joypad = Some(state),
+                    None => return Err(mlua::Error::runtime(format!("invalid port: {}", port))),
+                }
+                Ok(())
+            })?,
+        )?;
+        joypad.set(
+            "on_input",
+            scope.create_function(move |lua, function: Function| {
+                self.callbacks
+                    .borrow_mut()
+                    .input
+                    .push(lua.create_registry_value(function)?);
+                for addr in INPUT_PORTS {
+                    emulator
+                        .borrow_mut()
+                        .set_watchpoint(Access::Read, addr, true);
+                }
+                Ok(())
+            })?,
+        )?;
+        globals.set("joypad", joypad)?;
+
+        let state = lua.create_table()?;
+        state.set(
+            "save",
+            scope.create_function(move |_, ()| Ok(SavedState(emulator.borrow().save_state())))?,
+        )?;
+        state.set(
+            "load",
+            scope.create_function(move |_, state: UserDataRef<SavedState>| {
+                emulator.borrow_mut().load_state(state.0.clone());
+                Ok(())
+            })?,
+        )?;
+        globals.set("state", state)?;
+
+        let gui = lua.create_table()?;
+        gui.set(
+            "pixel",
+            scope.create_function(move |_, (x, y, color): (i32, i32, Option<u32>)| {
+                self.shapes.borrow_mut().push(Shape::Pixel {
+                    x,
+                    y,
+                    color: color.map_or(WHITE, to_color),
+                });
+                Ok(())
+            })?,
+        )?;
+        gui.set(
+            "box",
+            scope.create_function(
+                move |_,
+                      (x1, y1, x2, y2, outline, fill): (
+                    i32,
+                    i32,
+                    i32,
+                    i32,
+                    Option<u32>,
+                    Option<u32>,
+                )| {
+                    self.shapes.borrow_mut().push(Shape::Box {
+                        x1,
+                        y1,
+                        x2,
+                        y2,
+                        outline: outline.map_or(WHITE, to_color),
+                        fill: fill.map(to_color),
+                    });
+                    Ok(())
+                },
+            )?,
+        )?;
+        gui.set(
+            "text",
+            scope.create_function(
+                move |_, (x, y, text, color): (i32, i32, String, Option<u32>)| {
+                    self.shapes.borrow_mut().push(Shape::Text {
+                        x,
+                        y,
+                        text,
+                        color: color.map_or(WHITE, to_color),
+                    });
+                    Ok(())
+                },
+            )?,
+        )?;
+        globals.set("gui", gui)?;
+
+        let emu = lua.create_table()?;
+        emu.set(
+            "frame",
+            scope.create_function(move |_, ()| Ok(emulator.borrow().frame()))?,
+        )?;
+        emu.set(
+            "on_frame",
+            scope.create_function(move |lua, function: Function| {
+                self.callbacks
+                    .borrow_mut()
+                    .frame
+                    .push(lua.create_registry_value(function)?);
+                Ok(())
+            })?,
+        )?;
+        globals.set("emu", emu)
+    }
+}
+
+// a table of the pressed buttons, eg. { a = true, right = true }
+fn buttons_state(table: &Table) -> mlua::Result<u8> {
+    let mut state = 0;
+    for (bit, button) in BUTTONS.iter().enumerate() {
+        if table.get::<_, Option<bool>>(*button)?.unwrap_or(false) {
+            state |= 1 << bit;
+        }
+    }
+    Ok(state)
+}
+
+// 0xRRGGBB
+fn to_color(rgb: u32) -> Color {
+    Color((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+}
+
+pub struct ScriptCable {
+    joypads: Rc<RefCell<[Option<u8>; 2]>>,
+    cable: Box<dyn JoypadCable>,
+}
+
+impl JoypadCable for ScriptCable {
+    fn write(&mut self, mut input: FrameInput) -> FrameInput {
+        for (port, joypad) in self.joypads.borrow_mut().iter_mut().enumerate() {
+            if let Some(state) = joypad.take() {
+                input.ports[port] = state;
+            }
+        }
+        self.cable.write(input)
+    }
+
+    fn position(&self) -> usize {
+        self.cable.position()
+    }
+
+    fn seek(&mut self, position: usize, mode: MovieMode) -> Result<(), String> {
+        self.cable.seek(position, mode)
+    }
+
+    fn finished(&self) -> bool {
+        self.cable.finished()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::cartridge;
+    use crate::headless::Headless;
+
+    /*
+     * $8000 LDA #$05; STA $0200; LDA $0200
+     * $8008 JMP $8008
+     * $8010 LDA #$01; STA $4016; LDA #$00; STA $4016  (NMI)
+     * $801A LDX #$08
+     * $801C LDA $4016; LSR; ROL $0300; DEX; BNE $801C
+     * $8026 RTI
+     */
+    fn mk_emulator() -> Emulator {
+        let mut emulator = Emulator::new(cartridge::Cartridge::with_program(&[
+            (
+                0x8000,
+                &[
+                    0xA9, 0x05, 0x8D, 0x00, 0x02, 0xAD, 0x00, 0x02, 0x4C, 0x08, 0x80,
+                ],
+            ),
+            (
+                0x8010,
+                &[
+                    0xA9, 0x01, 0x8D, 0x16, 0x40, 0xA9, 0x00, 0x8D, 0x16, 0x40, 0xA2, 0x08, 0xAD,
+                    0x16, 0x40, 0x4A, 0x2E, 0x00, 0x03, 0xCA, 0xD0, 0xF6, 0x40,
+                ],
+            ),
+            (0xFFFA, &[0x10, 0x80, 0x00, 0x80, 0x00, 0x80]),
+        ]));
+        emulator.write_memory(0x2000, 0x80); // enable NMI
+        emulator
+    }
+
+    fn mk_headless(code: &str) -> Headless {
+        let mut emulator = mk_emulator();
+        let script = Script::new(code, "test", &mut emulator).unwrap();
+        Headless::with_script(emulator, None, Some(script)).unwrap()
+    }
+
+    fn global(headless: &Headless, name: &str) -> i64 {
+        headless.script().unwrap().lua.globals().get(name).unwrap()
+    }
+
+    #[test]
+    fn test_api() {
+        let mut emulator = mk_emulator();
+        let code = "
+            memory.write(0x10, 0x34)
+            memory.write(0x11, 0x12)
+            assert(memory.read(0x10) == 0x34)
+            assert(memory.read_word(0x10) == 0x1234)
+            assert(cpu.registers().pc == 0x8000)
+            local saved = state.save()
+            memory.write(0x10, 0x00)
+            state.load(saved)
+            assert(memory.read(0x10) == 0x34)
+            assert(emu.frame() == 0)
+        ";
+        Script::new(code, "test", &mut emulator).unwrap();
+        assert_eq!(emulator.peek_memory(0x10), 0x34);
+
+        let err = Script::new("memory.read(0x10000)", "test", &mut emulator).err();
+        assert!(err.is_some());
+        let err = Script::new("joypad.set(3, 0)", "test", &mut emulator).err();
+        assert!(err.unwrap().contains("invalid port: 3"));
+    }
+
+    #[test]
+    fn test_callbacks() {
+        let mut headless = mk_headless(
+            "
+            frames, writes, reads, execs, polls = 0, 0, 0, 0, 0
+            emu.on_frame(function(frame) frames = frame end)
+            memory.on_write(0x0200, function(addr, val) writes = writes + val end)
+            memory.on_read(0x0200, function(addr, val) reads = reads + 1 end)
+            memory.on_exec(0x8008, function(pc) execs = execs + 1 end)
+            joypad.on_input(function(port) polls = polls + port end)
+            ",
+        );
+        headless.run(2).unwrap();
+        assert_eq!(global(&headless, "frames"), 2);
+        assert_eq!(global(&headless, "writes"), 0x05);
+        assert_eq!(global(&headless, "reads"), 1);
+        // the JMP loops on itself, ~29780 / 3 cycles a frame
+        assert!(global(&headless, "execs") > 2 * 9000);
+        // only the port 1 is read, once a frame
+        assert_eq!(global(&headless, "polls"), 2);
+    }
+
+    #[test]
+    fn test_joypad() {
+        let mut headless = mk_headless(
+            "
+            emu.on_frame(function(frame)
+                if frame == 1 then joypad.set(1, { a = true, right = true }) end
+                if frame == 3 then joypad.set(1, 0x02) end
+            end)
+            ",
+        );
+        headless.run(2).unwrap();
+        // the bits are read from A to Right, so they end up reversed
+        assert_eq!(headless.emulator().peek_memory(0x0300), 0x81);
+        headless.run(1).unwrap();
+        assert_eq!(
+            headless.emulator().peek_memory(0x0300),
+            0x00,
+            "the state is only set for a frame"
+        );
+        headless.run(1).unwrap();
+        assert_eq!(headless.emulator().peek_memory(0x0300), 0x40);
+    }
+
+    #[test]
+    fn test_overlay() {
+        let mut headless = mk_headless(
+            "
+            emu.on_frame(function(frame)
+                gui.box(0, 0, 3, 3, 0xFF0000, 0x00FF00)
+                gui.pixel(10, 10)
+                gui.text(20, 20, 'hi', 0x0000FF)
+            end)
+            ",
+        );
+        headless.run(1).unwrap();
+        let image = headless.framebuffer();
+        assert_eq!(image.get(0, 0), Color(0xFF, 0x00, 0x00));
+        assert_eq!(image.get(1, 1), Color(0x00, 0xFF, 0x00));
+        assert_eq!(image.get(10, 10), WHITE);
+        assert_eq!(image.get(20, 20), Color(0x00, 0x00, 0xFF));
+        // the emulator picture is untouched
+        assert_ne!(headless.emulator().framebuffer_image().get(10, 10), WHITE);
+    }
+
+    #[test]
+    fn test_error() {
+        let mut headless = mk_headless(
+            "
+            memory.on_write(0x0200, function() end)
+            joypad.on_input(function() end)
+            emu.on_frame(function() error('boom') end)
+            ",
+        );
+        headless.run(2).unwrap();
+        assert!(headless.script().is_none(), "the script is stopped");
+        assert_eq!(headless.frame(), 2);
+        let emulator = headless.emulator();
+        assert!(emulator.watchpoints(Access::Read).is_empty());
+        assert!(emulator.watchpoints(Access::Write).is_empty());
+    }
+}
@@ -0,0 +1,217 @@
+use crate::emulator::{Color, Image};
+
+const GLYPH_WIDTH: i32 = 3;
+const GLYPH_HEIGHT: i32 = 5;
+// a column and a row between the characters
+const ADVANCE: i32 = GLYPH_WIDTH + 1;
+const LINE_HEIGHT: i32 = GLYPH_HEIGHT + 1;
+
+// 3x5 glyphs, one row per byte with the leftmost pixel in the bit 2, lowercase is drawn as uppercase
+#[rustfmt::skip]
+const FONT: &[(char, [u8; 5])] = &[
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]), ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b111, 0b001, 0b111, 0b100, 0b111]), ('3', [0b111, 0b001, 0b111, 0b001, 0b111]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]), ('5', [0b111, 0b100, 0b111, 0b001, 0b111]),
+    ('6', [0b111, 0b100, 0b111, 0b101, 0b111]), ('7', [0b111, 0b001, 0b001, 0b010, 0b010]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]), ('9', [0b111, 0b101, 0b111, 0b001, 0b111]),
+    ('A', [0b010, 0b101, 0b111, 0b101, 0b101]), ('B', [0b110, 0b101, 0b110, 0b101, 0b110]),
+    ('C', [0b011, 0b100, 0b100, 0b100, 0b011]), ('D', [0b110, 0b101, 0b101, 0b101, 0b110]),
+    ('E', [0b111, 0b100, 0b110, 0b100, 0b111]), ('F', [0b111, 0b100, 0b110, 0b100, 0b100]),
+    ('G', [0b011, 0b100, 0b101, 0b101, 0b011]), ('H', [0b101, 0b101, 0b111, 0b101, 0b101]),
+    ('I', [0b111, 0b010, 0b010, 0b010, 0b111]), ('J', [0b001, 0b001, 0b001, 0b101, 0b010]),
+    ('K', [0b101, 0b101, 0b110, 0b101, 0b101]), ('L', [0b100, 0b100, 0b100, 0b100, 0b111]),
+    ('M', [0b101, 0b111, 0b111, 0b101, 0b101]), ('N', [0b110, 0b101, 0b101, 0b101, 0b101]),
+    ('O', [0b010, 0b101, 0b101, 0b101, 0b010]), ('P', [0b110, 0b101, 0b110, 0b100, 0b100]),
+    ('Q', [0b010, 0b101, 0b101, 0b110, 0b011]), ('R', [0b110, 0b101, 0b110, 0b101, 0b101]),
+    ('S', [0b011, 0b100, 0b010, 0b001, 0b110]), ('T', [0b111, 0b010, 0b010, 0b010, 0b010]),
+    ('U', [0b101, 0b101, 0b101, 0b101, 0b111]), ('V', [0b101, 0b101, 0b101, 0b101, 0b010]),
+    ('W', [0b101, 0b101, 0b111, 0b111, 0b101]), ('X', [0b101, 0b101, 0b010, 0b101, 0b101]),
+    ('Y', [0b101, 0b101, 0b010, 0b010, 0b010]), ('Z', [0b111, 0b001, 0b010, 0b100, 0b111]),
+    (' ', [0b000, 0b000, 0b000, 0b000, 0b000]), ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+    (',', [0b000, 0b000, 0b000, 0b010, 0b100]), (':', [0b000, 0b010, 0b000, 0b010, 0b000]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000]), ('+', [0b000, 0b010, 0b111, 0b010, 0b000]),
+    ('=', [0b000, 0b111, 0b000, 0b111, 0b000]), ('/', [0b001, 0b001, 0b010, 0b100, 0b100]),
+    ('!', [0b010, 0b010, 0b010, 0b000, 0b010]), ('?', [0b110, 0b001, 0b010, 0b000, 0b010]),
+    ('(', [0b001, 0b010, 0b010, 0b010, 0b001]), (')', [0b100, 0b010, 0b010, 0b010, 0b100]),
+    ('%', [0b101, 0b001, 0b010, 0b100, 0b101]), ('#', [0b101, 0b111, 0b101, 0b111, 0b101]),
+    ('_', [0b000, 0b000, 0b000, 0b000, 0b111]), ('\'', [0b010, 0b010, 0b000, 0b000, 0b000]),
+    ('*', [0b000, 0b101, 0b010, 0b101, 0b000]), ('<', [0b001, 0b010, 0b100, 0b010, 0b001]),
+    ('>', [0b100, 0b010, 0b001, 0b010, 0b100]), ('$', [0b011, 0b110, 0b010, 0b011, 0b110]),
+];
+
+// the coordinates may be off screen, only the visible part is drawn
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Shape {
+    Pixel {
+        x: i32,
+        y: i32,
+        color: Color,
+    },
+    Box {
+        x1: i32,
+        y1: i32,
+        x2: i32,
+        y2: i32,
+        outline: Color,
+        fill: Option<Color>,
+    },
+    // a \n starts a new line
+    Text {
+        x: i32,
+        y: i32,
+        text: String,
+        color: Color,
+    },
+}
+
+impl Shape {
+    pub fn draw(&self, image: &mut Image) {
+        match self {
+            Shape::Pixel { x, y, color } => put(image, *x, *y, *color),
+            Shape::Box {
+                x1,
+                y1,
+                x2,
+                y2,
+                outline,
+                fill,
+            } => {
+                let (left, right) = (*x1.min(x2), *x1.max(x2));
+                let (top, bottom) = (*y1.min(y2), *y1.max(y2));
+                // only the visible part is walked, the box may be huge
+                let visible_x = left.max(0)..=right.min(image.width as i32 - 1);
+                let visible_y = top.max(0)..=bottom.min(image.height as i32 - 1);
+                for y in visible_y {
+                    for x in visible_x.clone() {
+                        let border = x == left || x == right || y == top || y == bottom;
+                        match (border, fill) {
+                            (true, _) => put(image, x, y, *outline),
+                            (false, Some(fill)) => put(image, x, y, *fill),
+                            (false, None) => {}
+                        }
+                    }
+                }
+            }
+            Shape::Text { x, y, text, color } => {
+                for (line_idx, line) in text.lines().enumerate() {
+                    let top = y + line_idx as i32 * LINE_HEIGHT;
+                    for (char_idx, ch) in line.chars().enumerate() {
+                        let left = x + char_idx as i32 * ADVANCE;
+                        draw_glyph(image, left, top, glyph(ch), *color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn glyph(ch: char) -> &'static [u8; 5] {
+    let ch = ch.to_ascii_uppercase();
+    FONT.iter()
+        .find(|(c, _)| *c == ch)
+        .or_else(|| FONT.iter().find(|(c, _)| *c == '?'))
+        .map(|(_, rows)| rows)
+        .unwrap()
+}
+
+fn draw_glyph(image: &mut Image, left: i32, top: i32, rows: &[u8; 5], color: Color) {
+    for (row_idx, row) in rows.iter().enumerate() {
+        for col in 0..GLYPH_WIDTH {
+            if row & (1 << (GLYPH_WIDTH - 1 - col)) != 0 {
+                put(image, left + col, top + row_idx as i32, color);
+            }
+        }
+    }
+}
+
+fn put(image: &mut Image, x: i32, y: i32, color: Color) {
+    if (0..image.width as i32).contains(&x) && (0..image.height as i32).contains(&y) {
+        image.set(x as usize, y as usize, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: Color = Color(0xFF, 0xFF, 0xFF);
+    const BLACK: Color = Color(0x00, 0x00, 0x00);
+
+    fn rows(image: &Image) -> Vec<String> {
+        (0..image.height)
+            .map(|y| {
+                (0..image.width)
+                    .map(|x| if image.get(x, y) == WHITE { '#' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_text() {
+        let mut image = Image::new(8, 6);
+        Shape::Text {
+            x: 0,
+            y: 0,
+            text: "h1".to_string(),
+            color: WHITE,
+        }
+        .draw(&mut image);
+        assert_eq!(
+            rows(&image),
+            vec!["#.#..#..", "#.#.##..", "###..#..", "#.#..#..", "#.#.###.", "........"]
+        );
+    }
+
+    #[test]
+    fn test_box() {
+        let mut image = Image::new(4, 4);
+        let fill = Color(0x10, 0x20, 0x30);
+        Shape::Box {
+            x1: 3,
+            y1: 3,
+            x2: 0,
+            y2: 0,
+            outline: WHITE,
+            fill: Some(fill),
+        }
+        .draw(&mut image);
+        assert_eq!(rows(&image), vec!["####", "#..#", "#..#", "####"]);
+        assert_eq!(image.get(1, 2), fill);
+
+        // partially off screen
+        let mut image = Image::new(4, 4);
+        Shape::Box {
+            x1: -2,
+            y1: 1,
+            x2: 1,
+            y2: 9,
+            outline: WHITE,
+            fill: None,
+        }
+        .draw(&mut image);
+        assert_eq!(rows(&image), vec!["....", "##..", ".#..", ".#.."]);
+        Shape::Pixel {
+            x: 3,
+            y: 0,
+            color: WHITE,
+        }
+        .draw(&mut image);
+        assert_eq!(image.get(3, 0), WHITE);
+        assert_eq!(image.get(2, 0), BLACK);
+
+        // covering the whole screen and far beyond, the border is off screen
+        let mut image = Image::new(4, 4);
+        Shape::Box {
+            x1: -1,
+            y1: -1,
+            x2: i32::MAX,
+            y2: i32::MAX,
+            outline: WHITE,
+            fill: Some(fill),
+        }
+        .draw(&mut image);
+        assert_eq!(rows(&image), vec!["...."; 4]);
+        assert_eq!(image.get(3, 3), fill);
+    }
+}
@@ -15,4 +15,7 @@ pub trait UiEngine {
     fn set_title(&mut self, title: &str);
     fn poll_events(&mut self) -> Self::EventIter<'_>;
     fn feed_samples(&mut self, samples: &[f32]) -> bool;
+    // opens the viewer window when it is not open yet
+    fn show_viewer(&mut self, viewer: Viewer, image: &emulator::Image);
+    fn close_viewer(&mut self, viewer: Viewer);
 }
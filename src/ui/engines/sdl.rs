@@ -1,21 +1,30 @@
+use std::collections::HashMap;
+
 use sdl2::audio::{AudioQueue, AudioSpecDesired};
-use sdl2::event::{Event, EventPollIterator};
+use sdl2::controller::{self, GameController};
+use sdl2::event::{Event, EventPollIterator, WindowEvent};
+use sdl2::keyboard::Keycode;
 use sdl2::pixels::PixelFormatEnum;
 use sdl2::render::{Canvas, Texture};
 use sdl2::video::Window;
-use sdl2::{AudioSubsystem, EventPump};
+use sdl2::{AudioSubsystem, EventPump, GameControllerSubsystem, VideoSubsystem};
 
+use crate::ui::pads::{PadAxis, PadButton, MAX_PADS};
 use crate::ui::*;
 
 use super::UiEngine;
 
 type TextureCreator = sdl2::render::TextureCreator<sdl2::video::WindowContext>;
 
+const VIEWER_SCALE: u32 = 2;
+
 struct SdlContext {
     event_pump: EventPump,
     canvas: Canvas<Window>,
     texture_creator: TextureCreator,
     audio_subsystem: AudioSubsystem,
+    video_subsystem: VideoSubsystem,
+    controller_subsystem: GameControllerSubsystem,
 }
 
 impl SdlContext {
@@ -26,8 +35,8 @@ impl SdlContext {
         let window = video_subsystem
             .window(
                 "rust-sdl2 demo: Video",
-                SCREEN_WIDTH as u32 * 4,
-                SCREEN_HEIGHT as u32 * 4,
+                (SCREEN_WIDTH * WINDOW_SCALE) as u32,
+                (SCREEN_HEIGHT * WINDOW_SCALE) as u32,
             )
             .position_centered()
             .opengl()
@@ -41,7 +50,9 @@ impl SdlContext {
             .build()
             .map_err(|e| e.to_string())
             .expect("Failed to create canvas");
-        canvas.set_scale(4.0, 4.0).unwrap();
+        canvas
+            .set_scale(WINDOW_SCALE as f32, WINDOW_SCALE as f32)
+            .unwrap();
 
         let event_pump = sdl_context
             .event_pump()
@@ -51,11 +62,18 @@ impl SdlContext {
 
         let audio_subsystem = sdl_context.audio().expect("Failed to initialize SDL audio");
 
+        // the controllers already plugged are added by events too
+        let controller_subsystem = sdl_context
+            .game_controller()
+            .expect("Failed to initialize SDL game controllers");
+
         Self {
             event_pump,
             canvas,
             texture_creator,
             audio_subsystem,
+            video_subsystem,
+            controller_subsystem,
         }
     }
 }
@@ -66,6 +84,11 @@ pub struct SdlEngine {
     canvas: &'static mut Canvas<Window>,
     event_pump: &'static mut EventPump,
     audio_device: AudioQueue<f32>,
+    video_subsystem: VideoSubsystem,
+    viewer_windows: HashMap<Viewer, Canvas<Window>>,
+    controller_subsystem: GameControllerSubsystem,
+    // the pads of the ui, in the order they were plugged
+    controllers: [Option<GameController>; MAX_PADS],
 }
 
 impl UiEngine for SdlEngine {
@@ -99,6 +122,10 @@ impl UiEngine for SdlEngine {
             canvas: &mut sdl_context.canvas,
             event_pump: &mut sdl_context.event_pump,
             audio_device,
+            video_subsystem: sdl_context.video_subsystem.clone(),
+            viewer_windows: HashMap::new(),
+            controller_subsystem: sdl_context.controller_subsystem.clone(),
+            controllers: Default::default(),
         }
     }
 
@@ -128,7 +155,13 @@ impl UiEngine for SdlEngine {
 
     type EventIter<'a> = UiEvents<'a>;
     fn poll_events(&mut self) -> Self::EventIter<'_> {
-        UiEvents(self.event_pump.poll_iter())
+        UiEvents {
+            events: self.event_pump.poll_iter(),
+            main_window_id: self.canvas.window().id(),
+            viewer_windows: &self.viewer_windows,
+            controller_subsystem: &self.controller_subsystem,
+            controllers: &mut self.controllers,
+        }
     }
 
     fn feed_samples(&mut self, samples: &[f32]) -> bool {
@@ -142,28 +175,206 @@ impl UiEngine for SdlEngine {
 
         false
     }
+
+    fn show_viewer(&mut self, viewer: Viewer, image: &emulator::Image) {
+        let video_subsystem = &self.video_subsystem;
+        let canvas = self.viewer_windows.entry(viewer).or_insert_with(|| {
+            let window = video_subsystem
+                .window(
+                    &format!("sunrest - {:?}", viewer),
+                    image.width as u32 * VIEWER_SCALE,
+                    image.height as u32 * VIEWER_SCALE,
+                )
+                .build()
+                .map_err(|e| e.to_string())
+                .expect("Failed to create viewer window");
+            window
+                .into_canvas()
+                .build()
+                .map_err(|e| e.to_string())
+                .expect("Failed to create viewer canvas")
+        });
+
+        let texture_creator = canvas.texture_creator();
+        let mut texture = texture_creator
+            .create_texture_streaming(
+                PixelFormatEnum::RGB24,
+                image.width as u32,
+                image.height as u32,
+            )
+            .expect("Failed to create texture");
+        let buffer: Vec<u8> = image
+            .pixels
+            .iter()
+            .flat_map(|color| [color.0, color.1, color.2])
+            .collect();
+        texture
+            .update(None, &buffer, image.width * 3)
+            .expect("Failed to update texture");
+        canvas
+            .copy(&texture, None, None)
+            .expect("Failed to copy texture");
+        canvas.present();
+    }
+
+    fn close_viewer(&mut self, viewer: Viewer) {
+        self.viewer_windows.remove(&viewer);
+    }
 }
 
-pub struct UiEvents<'a>(EventPollIterator<'a>);
+pub struct UiEvents<'a> {
+    events: EventPollIterator<'a>,
+    main_window_id: u32,
+    viewer_windows: &'a HashMap<Viewer, Canvas<Window>>,
+    controller_subsystem: &'a GameControllerSubsystem,
+    controllers: &'a mut [Option<GameController>; MAX_PADS],
+}
 
 impl Iterator for UiEvents<'_> {
     type Item = UiEvent;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.0.find_map(|event| match event {
+        let main_window_id = self.main_window_id;
+        let viewer_windows = self.viewer_windows;
+        let controller_subsystem = self.controller_subsystem;
+        let controllers = &mut *self.controllers;
+        let pad = |controllers: &[Option<GameController>], id: u32| {
+            controllers
+                .iter()
+                .position(|c| c.as_ref().is_some_and(|c| c.instance_id() == id))
+        };
+        self.events.find_map(|event| match event {
             Event::Quit { .. } => Some(UiEvent::Quit),
 
+            // SDL only sends Quit when the last window is closed
+            Event::Window {
+                win_event: WindowEvent::Close,
+                window_id,
+                ..
+            } if window_id == main_window_id => Some(UiEvent::Quit),
+
+            Event::Window {
+                win_event: WindowEvent::Close,
+                window_id,
+                ..
+            } => viewer_windows
+                .iter()
+                .find(|(_, canvas)| canvas.window().id() == window_id)
+                .map(|(viewer, _)| UiEvent::ViewerClosed(*viewer)),
+
             Event::KeyDown {
                 keycode: Some(keycode),
                 ..
-            } => Some(UiEvent::KeyPress(keycode as i32)),
+            } => to_key(keycode).map(UiEvent::KeyPress),
 
             Event::KeyUp {
                 keycode: Some(keycode),
                 ..
-            } => Some(UiEvent::KeyRelease(keycode as i32)),
+            } => to_key(keycode).map(UiEvent::KeyRelease),
+
+            Event::ControllerDeviceAdded { which, .. } => {
+                let controller = controller_subsystem.open(which).ok()?;
+                // more controllers than pads are ignored
+                let slot = controllers.iter().position(Option::is_none)?;
+                if pad(controllers, controller.instance_id()).is_some() {
+                    return None;
+                }
+                let name = controller.name();
+                controllers[slot] = Some(controller);
+                Some(UiEvent::PadAdded(slot, name))
+            }
+
+            Event::ControllerDeviceRemoved { which, .. } => {
+                let slot = pad(controllers, which)?;
+                controllers[slot] = None;
+                Some(UiEvent::PadRemoved(slot))
+            }
+
+            Event::ControllerButtonDown { which, button, .. } => {
+                let slot = pad(controllers, which)?;
+                to_pad_button(button).map(|button| UiEvent::PadButton(slot, button, true))
+            }
+
+            Event::ControllerButtonUp { which, button, .. } => {
+                let slot = pad(controllers, which)?;
+                to_pad_button(button).map(|button| UiEvent::PadButton(slot, button, false))
+            }
+
+            Event::ControllerAxisMotion {
+                which, axis, value, ..
+            } => {
+                let slot = pad(controllers, which)?;
+                let axis = match axis {
+                    controller::Axis::LeftX => PadAxis::LeftX,
+                    controller::Axis::LeftY => PadAxis::LeftY,
+                    _ => return None,
+                };
+                Some(UiEvent::PadAxis(slot, axis, value as f32 / i16::MAX as f32))
+            }
 
             _ => None,
         })
     }
 }
+
+fn to_key(keycode: Keycode) -> Option<Key> {
+    let key = match keycode {
+        Keycode::Space => Key::Space,
+        Keycode::Return | Keycode::KpEnter => Key::Return,
+        Keycode::Escape => Key::Escape,
+        Keycode::Backspace => Key::Backspace,
+        Keycode::Tab => Key::Tab,
+        Keycode::Delete => Key::Delete,
+        Keycode::Insert => Key::Insert,
+        Keycode::Home => Key::Home,
+        Keycode::End => Key::End,
+        Keycode::PageUp => Key::PageUp,
+        Keycode::PageDown => Key::PageDown,
+        Keycode::Up => Key::Up,
+        Keycode::Down => Key::Down,
+        Keycode::Left => Key::Left,
+        Keycode::Right => Key::Right,
+        Keycode::LShift => Key::LShift,
+        Keycode::RShift => Key::RShift,
+        Keycode::LCtrl => Key::LCtrl,
+        Keycode::RCtrl => Key::RCtrl,
+        Keycode::LAlt => Key::LAlt,
+        Keycode::RAlt => Key::RAlt,
+        Keycode::Pause => Key::Pause,
+        Keycode::F1 => Key::F(1),
+        Keycode::F2 => Key::F(2),
+        Keycode::F3 => Key::F(3),
+        Keycode::F4 => Key::F(4),
+        Keycode::F5 => Key::F(5),
+        Keycode::F6 => Key::F(6),
+        Keycode::F7 => Key::F(7),
+        Keycode::F8 => Key::F(8),
+        Keycode::F9 => Key::F(9),
+        Keycode::F10 => Key::F(10),
+        Keycode::F11 => Key::F(11),
+        Keycode::F12 => Key::F(12),
+        // the printable keys are their ASCII character
+        _ => match u8::try_from(keycode as i32).map(char::from) {
+            Ok(ch) if ch.is_ascii_graphic() => Key::Char(ch.to_ascii_lowercase()),
+            _ => return None,
+        },
+    };
+    Some(key)
+}
+
+fn to_pad_button(button: controller::Button) -> Option<PadButton> {
+    let button = match button {
+        controller::Button::A => PadButton::South,
+        controller::Button::B => PadButton::East,
+        controller::Button::X => PadButton::West,
+        controller::Button::Y => PadButton::North,
+        controller::Button::Back => PadButton::Back,
+        controller::Button::Start => PadButton::Start,
+        controller::Button::DPadUp => PadButton::DPadUp,
+        controller::Button::DPadDown => PadButton::DPadDown,
+        controller::Button::DPadLeft => PadButton::DPadLeft,
+        controller::Button::DPadRight => PadButton::DPadRight,
+        _ => return None,
+    };
+    Some(button)
+}
@@ -0,0 +1,417 @@
+use std::collections::HashMap;
+
+use crate::emulator::input_devices::standard_joypad::State;
+
+use super::Viewer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    // the printable ASCII characters but the space, the letters in lowercase
+    Char(char),
+    F(u8),
+    Space,
+    Return,
+    Escape,
+    Backspace,
+    Tab,
+    Delete,
+    Insert,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Up,
+    Down,
+    Left,
+    Right,
+    LShift,
+    RShift,
+    LCtrl,
+    RCtrl,
+    LAlt,
+    RAlt,
+    Pause,
+}
+
+const KEY_NAMES: &[(Key, &str)] = &[
+    (Key::Space, "Space"),
+    (Key::Return, "Return"),
+    (Key::Escape, "Escape"),
+    (Key::Backspace, "Backspace"),
+    (Key::Tab, "Tab"),
+    (Key::Delete, "Delete"),
+    (Key::Insert, "Insert"),
+    (Key::Home, "Home"),
+    (Key::End, "End"),
+    (Key::PageUp, "PageUp"),
+    (Key::PageDown, "PageDown"),
+    (Key::Up, "Up"),
+    (Key::Down, "Down"),
+    (Key::Left, "Left"),
+    (Key::Right, "Right"),
+    (Key::LShift, "LShift"),
+    (Key::RShift, "RShift"),
+    (Key::LCtrl, "LCtrl"),
+    (Key::RCtrl, "RCtrl"),
+    (Key::LAlt, "LAlt"),
+    (Key::RAlt, "RAlt"),
+    (Key::Pause, "Pause"),
+];
+
+impl std::str::FromStr for Key {
+    type Err = String;
+
+    // a character (A, 1, [), F1 to F12 or one of the names of KEY_NAMES, in any case
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        if text.eq_ignore_ascii_case("Enter") {
+            return Ok(Key::Return);
+        }
+        let mut chars = text.chars();
+        if let (Some(ch), None) = (chars.next(), chars.next()) {
+            if ch.is_ascii_graphic() {
+                return Ok(Key::Char(ch.to_ascii_lowercase()));
+            }
+        }
+        if let Some(num) = text.strip_prefix(['F', 'f']) {
+            if let Ok(num @ 1..=12) = num.parse() {
+                return Ok(Key::F(num));
+            }
+        }
+        KEY_NAMES
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(text))
+            .map(|(key, _)| *key)
+            .ok_or_else(|| format!("unknown key: {}", text))
+    }
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Key::Char(ch) => write!(f, "{}", ch.to_ascii_uppercase()),
+            Key::F(num) => write!(f, "F{}", num),
+            key => {
+                let (_, name) = KEY_NAMES.iter().find(|(k, _)| k == key).unwrap();
+                write!(f, "{}", name)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    A,
+    B,
+    Select,
+    Start,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+const BUTTON_NAMES: &[(Button, &str)] = &[
+    (Button::A, "a"),
+    (Button::B, "b"),
+    (Button::Select, "select"),
+    (Button::Start, "start"),
+    (Button::Up, "up"),
+    (Button::Down, "down"),
+    (Button::Left, "left"),
+    (Button::Right, "right"),
+];
+
+impl Button {
+    pub fn set(&self, state: &mut State, pressed: bool) {
+        match self {
+            Button::A => state.a = pressed,
+            Button::B => state.b = pressed,
+            Button::Select => state.select = pressed,
+            Button::Start => state.start = pressed,
+            Button::Up => state.up = pressed,
+            Button::Down => state.down = pressed,
+            Button::Left => state.left = pressed,
+            Button::Right => state.right = pressed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    // the port, 0 or 1, and the button
+    Joypad(usize, Button),
+    // A or B
+    Turbo(usize, Button),
+    Quit,
+    SaveState,
+    LoadState,
+    Reset,
+    PowerCycle,
+    Pause,
+    FrameAdvance,
+    SlowDown,
+    SpeedUp,
+    MovieMode,
+    Counters,
+    Screenshot,
+    Cheats,
+    EjectDisk,
+    InsertDisk,
+    SwitchDiskSide,
+    Viewer(Viewer),
+    NextPalette,
+}
+
+// the joypad buttons are p1.<button> and p2.<button>, the turbo ones p1.turbo_a and so on
+const ACTION_NAMES: &[(Action, &str)] = &[
+    (Action::Quit, "quit"),
+    (Action::SaveState, "save_state"),
+    (Action::LoadState, "load_state"),
+    (Action::Reset, "reset"),
+    (Action::PowerCycle, "power_cycle"),
+    (Action::Pause, "pause"),
+    (Action::FrameAdvance, "frame_advance"),
+    (Action::SlowDown, "slow_down"),
+    (Action::SpeedUp, "speed_up"),
+    (Action::MovieMode, "movie_mode"),
+    (Action::Counters, "counters"),
+    (Action::Screenshot, "screenshot"),
+    (Action::Cheats, "cheats"),
+    (Action::EjectDisk, "eject_disk"),
+    (Action::InsertDisk, "insert_disk"),
+    (Action::SwitchDiskSide, "switch_disk_side"),
+    (Action::Viewer(Viewer::Nametables), "nametables"),
+    (Action::Viewer(Viewer::PatternTables), "pattern_tables"),
+    (Action::Viewer(Viewer::Palettes), "palettes"),
+    (Action::Viewer(Viewer::Sprites), "sprites"),
+    (Action::NextPalette, "next_palette"),
+];
+
+impl std::str::FromStr for Action {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let joypad = |port: &str, button: &str| {
+            let port = match port {
+                "p1" => 0,
+                "p2" => 1,
+                _ => return None,
+            };
+            match button {
+                "turbo_a" => return Some(Action::Turbo(port, Button::A)),
+                "turbo_b" => return Some(Action::Turbo(port, Button::B)),
+                _ => {}
+            }
+            BUTTON_NAMES
+                .iter()
+                .find(|(_, name)| *name == button)
+                .map(|(button, _)| Action::Joypad(port, *button))
+        };
+        let action = match text.split_once('.') {
+            Some((port, button)) => joypad(port, button),
+            None => ACTION_NAMES
+                .iter()
+                .find(|(_, name)| *name == text)
+                .map(|(action, _)| *action),
+        };
+        action.ok_or_else(|| format!("unknown action: {}", text))
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Action::Joypad(port, button) => {
+                let (_, name) = BUTTON_NAMES.iter().find(|(b, _)| b == button).unwrap();
+                return write!(f, "p{}.{}", port + 1, name);
+            }
+            Action::Turbo(port, button) => {
+                let (_, name) = BUTTON_NAMES.iter().find(|(b, _)| b == button).unwrap();
+                return write!(f, "p{}.turbo_{}", port + 1, name);
+            }
+            _ => {}
+        }
+        let (_, name) = ACTION_NAMES.iter().find(|(a, _)| a == self).unwrap();
+        write!(f, "{}", name)
+    }
+}
+
+const DEFAULT_BINDINGS: &[(Key, Action)] = &[
+    (Key::Char('w'), Action::Joypad(0, Button::Up)),
+    (Key::Char('s'), Action::Joypad(0, Button::Down)),
+    (Key::Char('a'), Action::Joypad(0, Button::Left)),
+    (Key::Char('d'), Action::Joypad(0, Button::Right)),
+    (Key::Char('j'), Action::Joypad(0, Button::A)),
+    (Key::Char('k'), Action::Joypad(0, Button::B)),
+    (Key::Space, Action::Joypad(0, Button::Select)),
+    (Key::Return, Action::Joypad(0, Button::Start)),
+    (Key::Char('u'), Action::Turbo(0, Button::A)),
+    (Key::Char('y'), Action::Turbo(0, Button::B)),
+    (Key::Up, Action::Joypad(1, Button::Up)),
+    (Key::Down, Action::Joypad(1, Button::Down)),
+    (Key::Left, Action::Joypad(1, Button::Left)),
+    (Key::Right, Action::Joypad(1, Button::Right)),
+    (Key::Char('.'), Action::Joypad(1, Button::A)),
+    (Key::Char(','), Action::Joypad(1, Button::B)),
+    (Key::RShift, Action::Joypad(1, Button::Select)),
+    (Key::Char('/'), Action::Joypad(1, Button::Start)),
+    (Key::Char('\''), Action::Turbo(1, Button::A)),
+    (Key::Char(';'), Action::Turbo(1, Button::B)),
+    (Key::Escape, Action::Quit),
+    (Key::Char('['), Action::SaveState),
+    (Key::Char(']'), Action::LoadState),
+    (Key::Char('r'), Action::Reset),
+    (Key::Char('o'), Action::PowerCycle),
+    (Key::Tab, Action::Pause),
+    (Key::Char('\\'), Action::FrameAdvance),
+    (Key::Char('-'), Action::SlowDown),
+    (Key::Char('='), Action::SpeedUp),
+    (Key::Char('m'), Action::MovieMode),
+    (Key::Char('l'), Action::Counters),
+    (Key::Char('c'), Action::Screenshot),
+    (Key::Char('g'), Action::Cheats),
+    (Key::Char('e'), Action::EjectDisk),
+    (Key::Char('i'), Action::InsertDisk),
+    (Key::Char('f'), Action::SwitchDiskSide),
+    (Key::Char('1'), Action::Viewer(Viewer::Nametables)),
+    (Key::Char('2'), Action::Viewer(Viewer::PatternTables)),
+    (Key::Char('3'), Action::Viewer(Viewer::Palettes)),
+    (Key::Char('4'), Action::Viewer(Viewer::Sprites)),
+    (Key::Char('p'), Action::NextPalette),
+];
+
+#[derive(Debug, Clone)]
+pub struct Bindings {
+    actions: HashMap<Key, Action>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self {
+            actions: DEFAULT_BINDINGS.iter().copied().collect(),
+        }
+    }
+}
+
+impl Bindings {
+    pub fn get(&self, key: Key) -> Option<Action> {
+        self.actions.get(&key).copied()
+    }
+
+    /*
+     * One `<action> = <key> [<key>...]` per line, the lines starting with # are comments. The
+     * keys of an action replace its default ones, the actions not in the file keep them.
+     */
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut bindings = Self::default();
+        let mut bound = HashMap::new();
+        for (line_idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |e: String| format!("line {}: {}", line_idx + 1, e);
+            let (action, keys) = line
+                .split_once('=')
+                .ok_or_else(|| error("expected <action> = <key>".to_string()))?;
+            let action: Action = action.trim().parse().map_err(error)?;
+            bindings.actions.retain(|_, a| *a != action);
+            for key in keys.split_whitespace() {
+                let key: Key = key.parse().map_err(error)?;
+                if let Some(other) = bound.insert(key, action) {
+                    return Err(error(format!(
+                        "{} is bound to {} and {}",
+                        key, other, action
+                    )));
+                }
+                bindings.actions.insert(key, action);
+            }
+        }
+        Ok(bindings)
+    }
+
+    pub fn load(path: &std::path::Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_names() {
+        for (text, key) in [
+            ("A", Key::Char('a')),
+            ("a", Key::Char('a')),
+            ("[", Key::Char('[')),
+            ("F12", Key::F(12)),
+            ("space", Key::Space),
+            ("RShift", Key::RShift),
+            ("Enter", Key::Return),
+        ] {
+            assert_eq!(text.parse::<Key>(), Ok(key));
+        }
+        assert!("F13".parse::<Key>().is_err());
+        assert!("Shift".parse::<Key>().is_err());
+        assert_eq!(Key::Char('a').to_string(), "A");
+        assert_eq!(Key::PageUp.to_string(), "PageUp");
+
+        assert_eq!(
+            "p2.select".parse::<Action>(),
+            Ok(Action::Joypad(1, Button::Select))
+        );
+        assert_eq!("save_state".parse::<Action>(), Ok(Action::SaveState));
+        assert!("p3.a".parse::<Action>().is_err());
+        assert_eq!(
+            "p1.turbo_b".parse::<Action>(),
+            Ok(Action::Turbo(0, Button::B))
+        );
+        assert!("p1.turbo_start".parse::<Action>().is_err());
+        assert_eq!(Action::Turbo(1, Button::A).to_string(), "p2.turbo_a");
+        assert_eq!(Action::Joypad(0, Button::A).to_string(), "p1.a");
+        assert_eq!(Action::Viewer(Viewer::Sprites).to_string(), "sprites");
+    }
+
+    #[test]
+    fn test_parse() {
+        let bindings = Bindings::parse(
+            "# player 1 on the arrows\n\
+             p1.up = Up\n\
+             p1.a = Z X\n\
+             \n\
+             quit = F10\n",
+        )
+        .unwrap();
+        assert_eq!(bindings.get(Key::Up), Some(Action::Joypad(0, Button::Up)));
+        assert_eq!(bindings.get(Key::Char('w')), None);
+        assert_eq!(
+            bindings.get(Key::Char('x')),
+            Some(Action::Joypad(0, Button::A))
+        );
+        assert_eq!(bindings.get(Key::Char('j')), None);
+        assert_eq!(bindings.get(Key::F(10)), Some(Action::Quit));
+        assert_eq!(bindings.get(Key::Escape), None);
+        // the defaults of the other actions
+        assert_eq!(
+            bindings.get(Key::Down),
+            Some(Action::Joypad(1, Button::Down))
+        );
+        assert_eq!(bindings.get(Key::Char('[')), Some(Action::SaveState));
+
+        assert_eq!(
+            Bindings::parse("p1.a = Z\np1.b = z").unwrap_err(),
+            "line 2: Z is bound to p1.a and p1.b"
+        );
+        assert_eq!(
+            Bindings::parse("rewind = T").unwrap_err(),
+            "line 1: unknown action: rewind"
+        );
+        assert_eq!(
+            Bindings::parse("p1.a Z").unwrap_err(),
+            "line 1: expected <action> = <key>"
+        );
+    }
+}
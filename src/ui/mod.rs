@@ -1,15 +1,28 @@
 mod fps_calc;
+mod keys;
+mod pads;
 mod settings;
 use super::*;
 
 pub mod engines;
+pub use keys::{Bindings, Key};
+pub use pads::parse_ports as parse_pad_ports;
 pub use settings::Settings;
 
+use emulator::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use input_devices::standard_joypad::State;
+use keys::Action;
+
 const FPS: usize = 60;
-const SCREEN_WIDTH: usize = 256;
-const SCREEN_HEIGHT: usize = 240;
+const WINDOW_SCALE: usize = 4;
 const SAMPLE_BUFFER_SIZE: usize = 512;
 const SAMPLE_RATE: usize = 44100;
+const MIN_SPEED: f32 = 1.0 / 16.0;
+const MAX_SPEED: f32 = 16.0;
+const PAUSE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(10);
+const SPRITE_VIEWER_COLUMNS: usize = 4;
+// an 8x16 sprite and two lines of attributes on its right
+const SPRITE_VIEWER_CELL: (usize, usize) = (48, 18);
 
 #[derive(PartialEq, Eq)]
 enum UiState {
@@ -19,8 +32,23 @@ enum UiState {
 
 pub enum UiEvent {
     Quit,
-    KeyPress(i32),
-    KeyRelease(i32),
+    KeyPress(Key),
+    KeyRelease(Key),
+    // the pad number with the controller name
+    PadAdded(usize, String),
+    PadRemoved(usize),
+    PadButton(usize, pads::PadButton, bool),
+    PadAxis(usize, pads::PadAxis, f32),
+    ViewerClosed(Viewer),
+}
+
+// extra windows showing the PPU memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Viewer {
+    Nametables,
+    PatternTables,
+    Palettes,
+    Sprites,
 }
 
 pub struct Ui<E: engines::UiEngine> {
@@ -28,115 +56,428 @@ pub struct Ui<E: engines::UiEngine> {
     engine: E,
     state: UiState,
     settings: Settings,
-    joypad1_state: input_devices::standard_joypad::State,
-    joypad2_state: input_devices::standard_joypad::State,
-    pub joypad1_cable: Option<Box<dyn joypad_cable::JoypadCable>>,
-    pub joypad2_cable: Option<Box<dyn joypad_cable::JoypadCable>>,
+    pub bindings: Bindings,
+    // the keyboard and the pads plugged to the port
+    joypad1_state: State,
+    joypad2_state: State,
+    keyboard_states: [State; 2],
+    keyboard_turbo: [State; 2],
+    pads: pads::Pads,
+    pub joypad_cable: Option<Box<dyn joypad_cable::JoypadCable>>,
+    // the turbo buttons, pressed by the joypad cable
+    pub turbo: Option<joypad_cable::Turbo>,
+    // None without a movie
+    pub movie_mode: Option<joypad_cable::MovieMode>,
+    // the end of the movie was reported, until a save state goes back into it
+    movie_finished: bool,
+    // the reset and power buttons are pressed at the start of the next frame, like the joypads
+    command: Option<joypad_cable::Command>,
+    pub script: Option<script::Script>,
 
     sample_buffer: Vec<f32>,
-    emulator_state: Option<emulator::TimeMachine>,
+    // with the position of the joypad cable
+    emulator_state: Option<(emulator::TimeMachine, usize)>,
+    paused: bool,
+    // runs one frame and pauses again
+    frame_advance: bool,
+    show_counters: bool,
     base_title: String,
+    disk_side: usize,
+    viewers: Vec<Viewer>,
+    viewer_palette: u8,
 }
 
 impl<E: engines::UiEngine> Ui<E> {
     pub fn new(emulator: emulator::Emulator, settings: settings::Settings) -> Self {
         let mut engine = E::new();
-        let base_title = format!("sunrest - {}", emulator.rom_info().name);
+        let mut base_title = format!("sunrest - {}", emulator.rom_info().name);
+        // the seed stays in sight, to reproduce the run
+        if let emulator::RamPattern::Random(_) = emulator.ram_pattern() {
+            base_title += &format!(" - RAM {}", emulator.ram_pattern());
+        }
         engine.set_title(&base_title);
+        let pads = pads::Pads::new(settings.pad_ports, settings.pad_deadzone);
 
         Self {
             emulator,
             engine,
             state: UiState::Running,
             settings,
+            bindings: Bindings::default(),
             joypad1_state: Default::default(),
             joypad2_state: Default::default(),
-            joypad1_cable: None,
-            joypad2_cable: None,
+            keyboard_states: Default::default(),
+            keyboard_turbo: Default::default(),
+            pads,
+            joypad_cable: None,
+            turbo: None,
+            movie_mode: None,
+            movie_finished: false,
+            command: None,
+            script: None,
 
-            sample_buffer: Vec::with_capacity(SAMPLE_BUFFER_SIZE),
+            sample_buffer: Vec::new(),
             emulator_state: None,
+            paused: false,
+            frame_advance: false,
+            show_counters: false,
             base_title,
+            disk_side: 0,
+            viewers: Vec::new(),
+            viewer_palette: 0,
         }
     }
 
+    /*
+     * From the normal speed up the emulation follows the audio, which is played faster, and skips
+     * frames. Below it the audio is muted and the frames are timed with the clock instead.
+     */
     pub fn run(&mut self) {
-        let mut prev_video_signal = self.emulator.video_signal();
-
         let mut fps_calc = fps_calc::FpsCalc::new(FPS);
         let sample_clock_ratio = 21_477_272 as f32 / SAMPLE_RATE as f32;
-        let sample_clock = (sample_clock_ratio * self.settings.speed) as usize;
-        let frame_skip = (self.settings.speed as usize).saturating_sub(1);
+        let mut frame_start = std::time::Instant::now();
         while self.state == UiState::Running {
-            if self.sample_buffer.len() < self.sample_buffer.capacity() {
-                self.emulator.clock();
-
-                let video_signal = self.emulator.video_signal();
-                if video_signal != prev_video_signal {
-                    prev_video_signal = video_signal;
-                    self.draw_point(
-                        prev_video_signal.x,
-                        prev_video_signal.y,
-                        prev_video_signal.color,
-                    );
-
-                    if video_signal.x == SCREEN_WIDTH - 1 && video_signal.y == SCREEN_HEIGHT - 1 {
-                        if fps_calc.frame() % (1 + frame_skip) == 0 {
-                            self.engine.present();
-                        }
+            let speed = self.settings.speed.max(1.0);
+            let sample_clock = (sample_clock_ratio * speed) as usize;
+            let frame_skip = (speed as usize).saturating_sub(1);
+            self.run_frame(sample_clock);
+            self.feed_samples();
 
-                        if let Some(fps) = fps_calc.update() {
-                            self.set_title(&format!("{:.02} fps", fps));
-                        }
+            if let Some(script) = self.script.as_mut() {
+                if let Err(err) = script.frame_end(&mut self.emulator) {
+                    self.stop_script(&err);
+                }
+            }
+            if fps_calc.frame() % (1 + frame_skip) == 0 {
+                self.draw_frame();
+                self.engine.present();
+                self.update_viewers();
+            }
+
+            if let Some(fps) = fps_calc.update() {
+                self.set_title(&format!("{:.02} fps", fps));
+            }
+
+            self.process_events();
+            self.check_movie_end();
+            self.wait_while_paused();
+            self.slow_down(&mut frame_start);
+            self.write_input();
+        }
+    }
+
+    // the audio is only sampled from the normal speed up
+    fn run_frame(&mut self, sample_clock: usize) {
+        let sampled = self.settings.speed >= 1.0;
+        let volume = self.settings.volume;
+        let mut error = None;
+        let Self {
+            emulator,
+            script,
+            sample_buffer,
+            ..
+        } = self;
+        emulator.run_frame(|emulator| {
+            if let Err(err) = script::clock(script, emulator) {
+                error = Some(err);
+            }
+            if sampled && emulator.cycle.is_multiple_of(sample_clock) {
+                sample_buffer.push(emulator.audio_signal().sample() * volume);
+            }
+        });
+        if let Some(err) = error {
+            self.stop_script(&err);
+        }
+    }
+
+    // waits for the audio device to play the queued samples, which paces the emulation
+    fn feed_samples(&mut self) {
+        if self.sample_buffer.is_empty() {
+            return;
+        }
+        while !self.engine.feed_samples(self.sample_buffer.as_slice()) {}
+        self.sample_buffer.clear();
+    }
 
-                        self.process_events();
-                        if let Some(joy1) = self.joypad1_cable.as_deref_mut() {
-                            joy1.write(self.joypad1_state.into())
+    // pauses once the last frame of the movie played, before the live input takes over
+    fn check_movie_end(&mut self) {
+        let finished = self
+            .joypad_cable
+            .as_ref()
+            .is_some_and(|cable| cable.finished());
+        if finished && !self.movie_finished {
+            self.paused = true;
+            self.set_title("movie finished");
+        }
+        self.movie_finished = finished;
+    }
+
+    // at the end of a frame, the next one gets the input held when the game goes on
+    fn wait_while_paused(&mut self) {
+        while self.paused && !self.frame_advance && self.state == UiState::Running {
+            std::thread::sleep(PAUSE_POLL_INTERVAL);
+            self.process_events();
+        }
+        self.frame_advance = false;
+    }
+
+    fn slow_down(&mut self, frame_start: &mut std::time::Instant) {
+        if self.settings.speed < 1.0 {
+            let frame_time =
+                std::time::Duration::from_secs_f32(1.0 / (FPS as f32 * self.settings.speed));
+            if let Some(remaining) = frame_time.checked_sub(frame_start.elapsed()) {
+                std::thread::sleep(remaining);
+            }
+        }
+        *frame_start = std::time::Instant::now();
+    }
+
+    fn set_speed(&mut self, speed: f32) {
+        self.settings.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+        self.set_title(&format!("speed {}", self.settings.speed));
+    }
+
+    fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        if self.paused {
+            self.draw_frame();
+            self.engine.present();
+            self.set_title("paused");
+        } else {
+            self.set_title("running");
+        }
+    }
+
+    fn save_state(&mut self) {
+        let position = self
+            .joypad_cable
+            .as_ref()
+            .map_or(0, |cable| cable.position());
+        self.emulator_state = Some((self.emulator.save_state(), position));
+    }
+
+    // the movie goes back to the frame of the state, or fails when the state is not in it
+    fn load_state(&mut self) {
+        let Some((state, position)) = self.emulator_state.clone() else {
+            return;
+        };
+        if let Some(cable) = self.joypad_cable.as_deref_mut() {
+            let mode = self.movie_mode.unwrap_or(joypad_cable::MovieMode::ReadOnly);
+            if let Err(err) = cable.seek(position, mode) {
+                self.set_title(&err);
+                return;
+            }
+        }
+        self.emulator.load_state(state);
+        if self.paused {
+            self.draw_frame();
+            self.engine.present();
+        }
+    }
+
+    fn toggle_movie_mode(&mut self) {
+        use joypad_cable::MovieMode;
+        self.movie_mode = match self.movie_mode {
+            Some(MovieMode::ReadOnly) => Some(MovieMode::ReadWrite),
+            Some(MovieMode::ReadWrite) => Some(MovieMode::ReadOnly),
+            None => None,
+        };
+        match self.movie_mode {
+            Some(MovieMode::ReadOnly) => self.set_title("read-only"),
+            Some(MovieMode::ReadWrite) => self.set_title("read-write"),
+            None => self.set_title("no movie"),
+        }
+    }
+
+    fn write_input(&mut self) {
+        let mut input = joypad_cable::FrameInput {
+            ports: [self.joypad1_state.into(), self.joypad2_state.into()],
+            command: self.command.take(),
+        };
+        if let Some(cable) = self.joypad_cable.as_deref_mut() {
+            input = cable.write(input);
+        }
+        match input.command {
+            Some(joypad_cable::Command::Reset) => self.emulator.reset(),
+            Some(joypad_cable::Command::PowerCycle) => self.emulator.power_cycle(),
+            None => {}
+        }
+    }
+
+    // the game goes on without the script
+    fn stop_script(&mut self, err: &str) {
+        eprintln!("script error: {}", err);
+        if let Some(script) = self.script.take() {
+            script.detach(&mut self.emulator);
+        }
+        self.set_title("script stopped");
+    }
+
+    fn process_events(&mut self) {
+        let events: Vec<UiEvent> = self.engine.poll_events().collect();
+        for event in events {
+            match event {
+                UiEvent::Quit => self.state = UiState::Quit,
+                UiEvent::ViewerClosed(viewer) => self.toggle_viewer(viewer),
+                UiEvent::KeyPress(key) | UiEvent::KeyRelease(key) => {
+                    let is_pressed = matches!(event, UiEvent::KeyPress(_));
+                    match self.bindings.get(key) {
+                        Some(Action::Joypad(port, button)) => {
+                            button.set(&mut self.keyboard_states[port], is_pressed)
                         }
-                        if let Some(joy2) = self.joypad2_cable.as_deref_mut() {
-                            joy2.write(self.joypad2_state.into())
+                        Some(Action::Turbo(port, button)) => {
+                            button.set(&mut self.keyboard_turbo[port], is_pressed)
                         }
+                        Some(action) if is_pressed => self.run_action(action),
+                        _ => {}
                     }
                 }
-
-                if self.emulator.cycle % sample_clock == 0 {
-                    let sample = self.emulator.audio_signal().sample();
-                    self.sample_buffer.push(sample * self.settings.volume);
+                UiEvent::PadAdded(pad, name) => {
+                    let port = match self.pads.ports[pad] {
+                        Some(port) => format!("port {}", port + 1),
+                        None => "no port".to_string(),
+                    };
+                    self.set_title(&format!("{} on {}", name, port));
                 }
-            } else if self.engine.feed_samples(self.sample_buffer.as_slice()) {
-                self.sample_buffer.clear();
+                UiEvent::PadRemoved(pad) => self.pads.disconnect(pad),
+                UiEvent::PadButton(pad, button, pressed) => self.pads.button(pad, button, pressed),
+                UiEvent::PadAxis(pad, axis, value) => self.pads.axis(pad, axis, value),
+            }
+        }
+        let [keyboard1, keyboard2] = self.keyboard_states.map(u8::from);
+        self.joypad1_state = (keyboard1 | self.pads.port_state(0)).into();
+        self.joypad2_state = (keyboard2 | self.pads.port_state(1)).into();
+        if let Some(turbo) = &self.turbo {
+            for port in 0..2 {
+                let held = u8::from(self.keyboard_turbo[port]) | self.pads.port_turbo(port);
+                turbo.hold(port, held);
             }
         }
     }
 
-    fn process_events(&mut self) {
-        for event in self.engine.poll_events() {
-            match event {
-                UiEvent::Quit | UiEvent::KeyPress(27) => self.state = UiState::Quit,
-                UiEvent::KeyPress(keycode) if keycode == '[' as i32 => {
-                    self.emulator_state = Some(self.emulator.save_state())
+    fn run_action(&mut self, action: Action) {
+        match action {
+            Action::Joypad(..) | Action::Turbo(..) => {}
+            Action::Quit => self.state = UiState::Quit,
+            Action::SaveState => self.save_state(),
+            Action::LoadState => self.load_state(),
+            Action::Reset => self.command = Some(joypad_cable::Command::Reset),
+            Action::PowerCycle => self.command = Some(joypad_cable::Command::PowerCycle),
+            Action::Pause => self.toggle_pause(),
+            Action::FrameAdvance => {
+                self.paused = true;
+                self.frame_advance = true;
+            }
+            Action::SlowDown => self.set_speed(self.settings.speed / 2.0),
+            Action::SpeedUp => self.set_speed(self.settings.speed * 2.0),
+            Action::MovieMode => self.toggle_movie_mode(),
+            Action::Counters => self.show_counters = !self.show_counters,
+            Action::Screenshot => self.screenshot(),
+            Action::Cheats => self.toggle_cheats(),
+            Action::EjectDisk => {
+                if let Some(side) = self.emulator.disk_side() {
+                    self.disk_side = side;
                 }
-                UiEvent::KeyPress(keycode) if keycode == ']' as i32 => {
-                    if let Some(state) = self.emulator_state.as_ref().cloned() {
-                        self.emulator.load_state(state);
-                    }
+                self.emulator.eject_disk();
+            }
+            Action::InsertDisk => {
+                if self.emulator.disk_side().is_none() {
+                    self.emulator.insert_disk(self.disk_side);
                 }
-                UiEvent::KeyPress(keycode) | UiEvent::KeyRelease(keycode) => {
-                    let is_pressed = matches!(event, UiEvent::KeyPress(_));
-                    match keycode {
-                        _ if keycode == 'w' as i32 => self.joypad1_state.up = is_pressed,
-                        _ if keycode == 'a' as i32 => self.joypad1_state.left = is_pressed,
-                        _ if keycode == 's' as i32 => self.joypad1_state.down = is_pressed,
-                        _ if keycode == 'd' as i32 => self.joypad1_state.right = is_pressed,
-                        _ if keycode == 'k' as i32 => self.joypad1_state.b = is_pressed,
-                        _ if keycode == 'j' as i32 => self.joypad1_state.a = is_pressed,
-                        _ if keycode == '\r' as i32 => self.joypad1_state.start = is_pressed,
-                        _ if keycode == ' ' as i32 => self.joypad1_state.select = is_pressed,
-                        _ => {}
-                    }
+            }
+            Action::SwitchDiskSide => self.emulator.switch_disk_side(),
+            Action::Viewer(viewer) => self.toggle_viewer(viewer),
+            Action::NextPalette => self.viewer_palette = (self.viewer_palette + 1) % 8,
+        }
+    }
+
+    fn screenshot(&mut self) {
+        let options = screenshot::Options {
+            directory: self.settings.screenshot_dir.clone(),
+            scale: if self.settings.screenshot_scaled {
+                WINDOW_SCALE
+            } else {
+                1
+            },
+            crop_overscan: self.settings.crop_overscan,
+        };
+        let rom_name = self.emulator.rom_info().name;
+        let name = std::path::Path::new(&rom_name)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or(rom_name);
+        match screenshot::capture(&self.emulator, &name, &options) {
+            Ok(path) => self.set_title(&format!("saved {}", path.display())),
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+
+    fn toggle_cheats(&mut self) {
+        let mut cheats = self.emulator.cheats().clone();
+        cheats.enabled = !cheats.enabled;
+        let message = match cheats.enabled {
+            true => format!("{} cheats enabled", cheats.cheats.len()),
+            false => "cheats disabled".to_string(),
+        };
+        self.emulator.set_cheats(cheats);
+        self.set_title(&message);
+    }
+
+    fn toggle_viewer(&mut self, viewer: Viewer) {
+        if let Some(idx) = self.viewers.iter().position(|v| *v == viewer) {
+            self.viewers.remove(idx);
+            self.engine.close_viewer(viewer);
+        } else {
+            self.viewers.push(viewer);
+        }
+    }
+
+    fn update_viewers(&mut self) {
+        for &viewer in &self.viewers {
+            let image = match viewer {
+                Viewer::Nametables => self.emulator.nametables_image(true),
+                Viewer::PatternTables => self.emulator.pattern_tables_image(self.viewer_palette),
+                Viewer::Palettes => self.emulator.palettes_image(),
+                Viewer::Sprites => self.sprites_image(),
+            };
+            self.engine.show_viewer(viewer, &image);
+        }
+    }
+
+    // the sprites in OAM order, each one with its position, palette, priority and flips
+    fn sprites_image(&self) -> emulator::Image {
+        let (cell_width, cell_height) = SPRITE_VIEWER_CELL;
+        let entries = self.emulator.oam();
+        let rows = entries.len().div_ceil(SPRITE_VIEWER_COLUMNS);
+        let mut image =
+            emulator::Image::new(SPRITE_VIEWER_COLUMNS * cell_width, rows * cell_height);
+        for (num, entry) in entries.iter().enumerate() {
+            let left = (num % SPRITE_VIEWER_COLUMNS) * cell_width;
+            let top = (num / SPRITE_VIEWER_COLUMNS) * cell_height;
+            let sprite = self.emulator.sprite_image(entry);
+            for y in 0..sprite.height {
+                for x in 0..sprite.width {
+                    image.set(left + x, top + y, sprite.get(x, y));
                 }
             }
+            let text = format!(
+                "{},{}\nP{} {} {}{}",
+                entry.x,
+                entry.y,
+                entry.palette,
+                if entry.behind { "BG" } else { "FG" },
+                if entry.flip_h { 'H' } else { '-' },
+                if entry.flip_v { 'V' } else { '-' },
+            );
+            let shape = script::overlay::Shape::Text {
+                x: (left + sprite.width + 2) as i32,
+                y: top as i32,
+                text,
+                color: emulator::Color(0xFF, 0xFF, 0xFF),
+            };
+            shape.draw(&mut image);
         }
+        image
     }
 
     fn set_title(&mut self, message: &str) {
@@ -144,9 +485,44 @@ impl<E: engines::UiEngine> Ui<E> {
             .set_title(&format!("{} - {}", self.base_title, message))
     }
 
-    fn draw_point(&mut self, x: usize, y: usize, color: emulator::Color) {
-        if x < SCREEN_WIDTH && y < SCREEN_HEIGHT {
-            self.engine.draw_point(x, y, color);
+    // the frame, the lag frames and the movie mode, in the bottom left corner
+    fn draw_counters(&self, image: &mut emulator::Image) {
+        use joypad_cable::MovieMode;
+        let mut text = format!(
+            "{} lag {}",
+            self.emulator.frame(),
+            self.emulator.lag_frames()
+        );
+        if self.emulator.lagged() {
+            text.push_str(" *");
+        }
+        match self.movie_mode {
+            Some(MovieMode::ReadOnly) => text.push_str("\nread-only"),
+            Some(MovieMode::ReadWrite) => text.push_str("\nread-write"),
+            None => {}
+        }
+        let lines = text.lines().count() as i32;
+        let shape = script::overlay::Shape::Text {
+            x: 8,
+            y: SCREEN_HEIGHT as i32 - 8 - lines * 6,
+            text,
+            color: emulator::Color(0xFF, 0xFF, 0xFF),
+        };
+        shape.draw(image);
+    }
+
+    fn draw_frame(&mut self) {
+        let mut image = self.emulator.framebuffer_image();
+        if let Some(script) = &self.script {
+            script.draw_overlay(&mut image);
+        }
+        if self.show_counters {
+            self.draw_counters(&mut image);
+        }
+        for y in 0..image.height {
+            for x in 0..image.width {
+                self.engine.draw_point(x, y, image.get(x, y));
+            }
         }
     }
 }
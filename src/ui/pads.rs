@@ -0,0 +1,196 @@
+use crate::emulator::input_devices::standard_joypad::State;
+
+use super::keys::Button;
+
+pub const MAX_PADS: usize = 4;
+// the first and the third controllers on the port 1, the others on the port 2
+pub const DEFAULT_PORTS: [Option<usize>; MAX_PADS] = [Some(0), Some(1), Some(0), Some(1)];
+pub const DEFAULT_DEADZONE: f32 = 0.3;
+
+// the buttons by position, South is A on an Xbox pad and Cross on a PlayStation one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PadButton {
+    South,
+    East,
+    West,
+    North,
+    Back,
+    Start,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PadAxis {
+    LeftX,
+    LeftY,
+}
+
+/*
+ * The NES B and A buttons are on the left and on the right, like South and East. West and North
+ * are the turbo B and A.
+ */
+fn nes_button(button: PadButton) -> (Button, bool) {
+    match button {
+        PadButton::South => (Button::B, false),
+        PadButton::East => (Button::A, false),
+        PadButton::West => (Button::B, true),
+        PadButton::North => (Button::A, true),
+        PadButton::Back => (Button::Select, false),
+        PadButton::Start => (Button::Start, false),
+        PadButton::DPadUp => (Button::Up, false),
+        PadButton::DPadDown => (Button::Down, false),
+        PadButton::DPadLeft => (Button::Left, false),
+        PadButton::DPadRight => (Button::Right, false),
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Pad {
+    buttons: State,
+    // the directions of the left stick out of the deadzone
+    stick: State,
+    turbo: State,
+}
+
+/*
+ * The game controllers, in the order they were connected. Each one can be plugged to a joypad
+ * port, several controllers on a port press the buttons of any of them.
+ */
+#[derive(Debug)]
+pub struct Pads {
+    pads: [Pad; MAX_PADS],
+    // the joypad port, 0 or 1, of each controller
+    pub ports: [Option<usize>; MAX_PADS],
+    // the part of the stick range, from 0 to 1, that doesn't press the directions
+    pub deadzone: f32,
+}
+
+impl Pads {
+    pub fn new(ports: [Option<usize>; MAX_PADS], deadzone: f32) -> Self {
+        Self {
+            pads: Default::default(),
+            ports,
+            deadzone,
+        }
+    }
+
+    pub fn button(&mut self, pad: usize, button: PadButton, pressed: bool) {
+        if let Some(pad) = self.pads.get_mut(pad) {
+            let (button, turbo) = nes_button(button);
+            let state = if turbo {
+                &mut pad.turbo
+            } else {
+                &mut pad.buttons
+            };
+            button.set(state, pressed);
+        }
+    }
+
+    // the value goes from -1 (left, up) to 1 (right, down)
+    pub fn axis(&mut self, pad: usize, axis: PadAxis, value: f32) {
+        let deadzone = self.deadzone;
+        let Some(pad) = self.pads.get_mut(pad) else {
+            return;
+        };
+        let (negative, positive) = match axis {
+            PadAxis::LeftX => (Button::Left, Button::Right),
+            PadAxis::LeftY => (Button::Up, Button::Down),
+        };
+        negative.set(&mut pad.stick, value < -deadzone);
+        positive.set(&mut pad.stick, value > deadzone);
+    }
+
+    // releases the buttons of a controller that was unplugged
+    pub fn disconnect(&mut self, pad: usize) {
+        if let Some(pad) = self.pads.get_mut(pad) {
+            *pad = Pad::default();
+        }
+    }
+
+    pub fn port_state(&self, port: usize) -> u8 {
+        self.port_pads(port).fold(0, |state, pad| {
+            state | u8::from(pad.buttons) | u8::from(pad.stick)
+        })
+    }
+
+    // the turbo buttons held on the port
+    pub fn port_turbo(&self, port: usize) -> u8 {
+        self.port_pads(port)
+            .fold(0, |state, pad| state | u8::from(pad.turbo))
+    }
+
+    fn port_pads(&self, port: usize) -> impl Iterator<Item = &Pad> {
+        self.pads
+            .iter()
+            .zip(self.ports)
+            .filter(move |(_, pad_port)| *pad_port == Some(port))
+            .map(|(pad, _)| pad)
+    }
+}
+
+// the ports of the controllers, like 1,2,0,0, where 0 leaves the controller unplugged
+pub fn parse_ports(text: &str) -> Result<[Option<usize>; MAX_PADS], String> {
+    let mut ports = [None; MAX_PADS];
+    let values: Vec<&str> = text.split(',').map(str::trim).collect();
+    if values.len() > MAX_PADS {
+        return Err(format!("at most {} controllers", MAX_PADS));
+    }
+    for (port, value) in ports.iter_mut().zip(values) {
+        *port = match value {
+            "0" => None,
+            "1" => Some(0),
+            "2" => Some(1),
+            _ => return Err(format!("invalid port: {}", value)),
+        };
+    }
+    Ok(ports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_port_state() {
+        let mut pads = Pads::new([Some(0), Some(1), Some(0), None], 0.3);
+        pads.button(0, PadButton::East, true);
+        pads.button(2, PadButton::Start, true);
+        pads.button(3, PadButton::Back, true);
+        pads.axis(1, PadAxis::LeftX, -0.8);
+        pads.axis(1, PadAxis::LeftY, 0.2);
+        assert_eq!(pads.port_state(0), 0x09);
+        assert_eq!(pads.port_state(1), 0x40);
+
+        // the stick and the dpad press the same button
+        pads.button(1, PadButton::DPadLeft, true);
+        pads.axis(1, PadAxis::LeftX, 0.0);
+        assert_eq!(pads.port_state(1), 0x40);
+
+        pads.button(2, PadButton::North, true);
+        assert_eq!(pads.port_state(0), 0x09);
+        assert_eq!(pads.port_turbo(0), 0x01);
+
+        pads.disconnect(0);
+        assert_eq!(pads.port_state(0), 0x08);
+        pads.button(0, PadButton::South, true);
+        pads.button(0, PadButton::South, false);
+        assert_eq!(pads.port_state(0), 0x08);
+    }
+
+    #[test]
+    fn test_parse_ports() {
+        assert_eq!(parse_ports("2,1"), Ok([Some(1), Some(0), None, None]));
+        assert_eq!(
+            parse_ports("1, 0, 2, 2"),
+            Ok([Some(0), None, Some(1), Some(1)])
+        );
+        assert_eq!(parse_ports("3").unwrap_err(), "invalid port: 3");
+        assert_eq!(
+            parse_ports("1,1,1,1,1").unwrap_err(),
+            "at most 4 controllers"
+        );
+    }
+}
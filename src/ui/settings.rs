@@ -1,7 +1,27 @@
+use std::path::PathBuf;
+
+use crate::emulator::RamPattern;
+
+use super::pads::{self, MAX_PADS};
+
 #[derive(Debug)]
 pub struct Settings {
     pub speed: f32,
     pub volume: f32,
+    pub screenshot_dir: PathBuf,
+    // saves the screenshots with the window scale instead of 256x240
+    pub screenshot_scaled: bool,
+    pub crop_overscan: bool,
+    pub ram_pattern: RamPattern,
+    // the key bindings file, the default bindings without it
+    pub keys: Option<PathBuf>,
+    // the joypad port of each game controller
+    pub pad_ports: [Option<usize>; MAX_PADS],
+    pub pad_deadzone: f32,
+    // the frames of a turbo press and release
+    pub turbo_frames: usize,
+    // the turbo buttons start and stop the autofire instead of being held
+    pub turbo_toggle: bool,
 }
 
 impl Default for Settings {
@@ -9,6 +29,15 @@ impl Default for Settings {
         Self {
             speed: 1.0,
             volume: 1.0,
+            screenshot_dir: PathBuf::from("."),
+            screenshot_scaled: false,
+            crop_overscan: false,
+            ram_pattern: RamPattern::default(),
+            keys: None,
+            pad_ports: pads::DEFAULT_PORTS,
+            pad_deadzone: pads::DEFAULT_DEADZONE,
+            turbo_frames: 2,
+            turbo_toggle: false,
         }
     }
 }
@@ -27,6 +56,49 @@ impl Settings {
             .and_then(|s| s.parse().ok())
             .unwrap_or(settings.volume);
 
+        if let Ok(dir) = std::env::var("SUNREST_SCREENSHOT_DIR") {
+            settings.screenshot_dir = PathBuf::from(dir);
+        }
+
+        settings.screenshot_scaled = std::env::var("SUNREST_SCREENSHOT_SCALED")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(settings.screenshot_scaled);
+
+        settings.crop_overscan = std::env::var("SUNREST_CROP_OVERSCAN")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(settings.crop_overscan);
+
+        settings.ram_pattern = std::env::var("SUNREST_RAM")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(settings.ram_pattern);
+
+        settings.keys = std::env::var_os("SUNREST_KEYS").map(PathBuf::from);
+
+        settings.pad_ports = std::env::var("SUNREST_PAD_PORTS")
+            .ok()
+            .and_then(|s| pads::parse_ports(&s).ok())
+            .unwrap_or(settings.pad_ports);
+
+        settings.pad_deadzone = std::env::var("SUNREST_PAD_DEADZONE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|deadzone| (0.0..1.0).contains(deadzone))
+            .unwrap_or(settings.pad_deadzone);
+
+        settings.turbo_frames = std::env::var("SUNREST_TURBO_FRAMES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|&frames| frames >= 2)
+            .unwrap_or(settings.turbo_frames);
+
+        settings.turbo_toggle = std::env::var("SUNREST_TURBO_TOGGLE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(settings.turbo_toggle);
+
         settings
     }
 }